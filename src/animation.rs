@@ -0,0 +1,98 @@
+use std::ops::{Add, Mul, Sub};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interpolation {
+    Linear,
+    // A smooth curve through every key, heading from the key before to the
+    // key after as it passes each one.
+    CatmullRom,
+}
+
+// A value given at key times and interpolated in between. Before the first
+// key and after the last one it holds still.
+#[derive(Debug, Clone)]
+pub struct Track<T> {
+    interpolation: Interpolation,
+    keys: Vec<(f64, T)>,
+}
+
+impl<T> Track<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f64, Output = T>,
+{
+    // The keys have to be in order of time.
+    pub fn new(interpolation: Interpolation, keys: Vec<(f64, T)>) -> Self {
+        assert!(!keys.is_empty(), "a track needs at least one key");
+        assert!(keys.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        Track {
+            interpolation,
+            keys,
+        }
+    }
+
+    pub fn at(&self, time: f64) -> T {
+        let keys = &self.keys;
+        let last = keys.len() - 1;
+        if time <= keys[0].0 {
+            return keys[0].1;
+        }
+        if time >= keys[last].0 {
+            return keys[last].1;
+        }
+        let i = keys.partition_point(|(t, _)| *t <= time) - 1;
+        let ((t0, p0), (t1, p1)) = (keys[i], keys[i + 1]);
+        let length = t1 - t0;
+        let s = (time - t0) / length;
+        match self.interpolation {
+            Interpolation::Linear => p0 + (p1 - p0) * s,
+            Interpolation::CatmullRom => {
+                let (m0, m1) = (self.slope(i), self.slope(i + 1));
+                let s2 = s * s;
+                let s3 = s2 * s;
+                p0 * (2. * s3 - 3. * s2 + 1.)
+                    + m0 * (length * (s3 - 2. * s2 + s))
+                    + p1 * (3. * s2 - 2. * s3)
+                    + m1 * (length * (s3 - s2))
+            }
+        }
+    }
+
+    // How fast the value changes at a key, from its neighbours.
+    fn slope(&self, i: usize) -> T {
+        let keys = &self.keys;
+        let before = keys[i.saturating_sub(1)];
+        let after = keys[(i + 1).min(keys.len() - 1)];
+        (after.1 - before.1) * (1. / (after.0 - before.0))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Interpolation, Track};
+    use crate::point::Point;
+
+    #[test]
+    fn test_tracks() {
+        let keys = vec![(0., 0.), (1., 2.), (3., 2.), (4., 0.)];
+        let linear = Track::new(Interpolation::Linear, keys.clone());
+        assert_eq!(linear.at(-1.), 0.);
+        assert_eq!(linear.at(0.5), 1.);
+        assert_eq!(linear.at(2.), 2.);
+        assert_eq!(linear.at(9.), 0.);
+
+        // The curve passes through the keys and is smooth in between.
+        let smooth = Track::new(Interpolation::CatmullRom, keys);
+        for (time, value) in [(0., 0.), (1., 2.), (3., 2.), (4., 0.)] {
+            assert!((smooth.at(time) - value).abs() < 1e-12);
+        }
+        let step = 1e-6;
+        let slope = |t: f64| (smooth.at(t + step) - smooth.at(t - step)) / (2. * step);
+        assert!((slope(1.) - 2. / 3.).abs() < 1e-4);
+        assert!(smooth.at(2.) > 2.);
+        // Two keys give a straight line.
+        let a = Point::new(0., 1., 2.);
+        let b = Point::new(4., 1., 0.);
+        let line = Track::new(Interpolation::CatmullRom, vec![(0., a), (2., b)]);
+        assert!((line.at(0.5) - (a + (b - a) * 0.25)).length() < 1e-12);
+    }
+}
@@ -0,0 +1,232 @@
+use crate::bodies::HitRecord;
+use crate::output::{self, exr, ImageFormat};
+use crate::point::Point;
+use crate::raytracer::Pixel;
+use crate::tonemap::ToneMapper;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const LAYERS: [&str; 6] = ["depth", "normal", "albedo", "uv", "position", "id"];
+
+// First hit information of a pixel. Depth, normal, uv and position are
+// averaged over the samples that hit something, albedo over all samples with
+// the background colour standing in for misses.
+#[derive(Clone, Copy, Debug)]
+pub struct Aov {
+    pub depth: f64,
+    pub normal: Point,
+    pub albedo: Point,
+    pub uv: Point,
+    pub position: Point,
+    pub object_id: usize,
+    hits: usize,
+    samples: usize,
+}
+
+impl Aov {
+    pub fn default() -> Self {
+        Aov {
+            depth: 0.,
+            normal: Point::default(),
+            albedo: Point::default(),
+            uv: Point::default(),
+            position: Point::default(),
+            object_id: 0,
+            hits: 0,
+            samples: 0,
+        }
+    }
+
+    pub fn add_hit(&mut self, depth: f64, rec: &HitRecord) {
+        if self.hits == 0 {
+            self.object_id = rec.object_id;
+        }
+        self.depth += depth;
+        self.normal = self.normal + rec.normal;
+        self.albedo = self.albedo + rec.body_props.albedo(rec);
+        self.uv = self.uv + Point::new(rec.u, rec.v, 0.);
+        self.position = self.position + rec.p;
+        self.hits += 1;
+        self.samples += 1;
+    }
+    pub fn add_miss(&mut self, background: Point) {
+        self.albedo = self.albedo + background;
+        self.samples += 1;
+    }
+
+    pub fn average(&self) -> Self {
+        let hits = self.hits.max(1) as f64;
+        Aov {
+            depth: self.depth / hits,
+            normal: self.normal / hits,
+            albedo: self.albedo / self.samples.max(1) as f64,
+            uv: self.uv / hits,
+            position: self.position / hits,
+            ..*self
+        }
+    }
+    pub fn is_hit(&self) -> bool {
+        self.hits > 0
+    }
+
+    pub fn layer(&self, name: &str) -> Point {
+        match name {
+            "depth" => Point::new(self.depth, self.depth, self.depth),
+            "normal" => self.normal,
+            "albedo" => self.albedo,
+            "uv" => self.uv,
+            "position" => self.position,
+            _ => {
+                let id = self.object_id as f64;
+                Point::new(id, id, id)
+            }
+        }
+    }
+}
+
+fn id_color(id: usize) -> Point {
+    if id == 0 {
+        return Point::default();
+    }
+    let hash = (id as u32).wrapping_mul(0x9e3779b9);
+    Point::new(
+        (hash >> 24) as f64 / 255.,
+        ((hash >> 16) & 0xff) as f64 / 255.,
+        ((hash >> 8) & 0xff) as f64 / 255.,
+    )
+}
+
+// Maps a layer into [0, 1] so that it can be stored in an 8 bit image.
+fn display_pixel(aov: &Aov, name: &str, max_depth: f64, tone_mapper: &ToneMapper) -> Pixel {
+    match name {
+        "depth" if aov.is_hit() => {
+            let d = 1. - aov.depth / max_depth;
+            Pixel::from_point(Point::new(d, d, d))
+        }
+        "normal" if aov.is_hit() => Pixel::from_point(aov.normal * 0.5 + Point::new(0.5, 0.5, 0.5)),
+        "albedo" => tone_mapper.map(aov.albedo),
+        "id" => Pixel::from_point(id_color(aov.object_id)),
+        _ => Pixel::from_point(aov.layer(name)),
+    }
+}
+
+fn layer_path(path: &Path, name: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}.{}.{}", stem, name, extension))
+}
+
+fn exr_channels(name: &str) -> Vec<String> {
+    let components = match name {
+        "depth" => vec!["Z"],
+        "id" => vec!["ID"],
+        "uv" => vec!["U", "V"],
+        "albedo" => vec!["R", "G", "B"],
+        _ => vec!["X", "Y", "Z"],
+    };
+    components
+        .iter()
+        .map(|c| format!("{}.{}", name, c))
+        .collect()
+}
+
+fn component(point: Point, i: usize) -> f32 {
+    point.as_array()[i] as f32
+}
+
+// Writes every layer next to `path`, as `<stem>.<layer>.<extension>`, or as
+// layers of a single multi-layer file when `path` is an OpenEXR file. Rows are
+// stored bottom row first, like the radiance buffer of the tracer.
+pub fn save(
+    path: &Path,
+    radiance: &[Vec<Point>],
+    aovs: &[Vec<Aov>],
+    tone_mapper: &ToneMapper,
+    compression: exr::Compression,
+) -> io::Result<()> {
+    let height = aovs.len();
+    let width = aovs.first().map_or(0, |row| row.len());
+    let format = ImageFormat::from_path(path)?;
+
+    if format == ImageFormat::Exr {
+        let mut channels: Vec<exr::Channel> = ["R", "G", "B"]
+            .iter()
+            .enumerate()
+            .map(|(c, name)| exr::Channel {
+                name: name.to_string(),
+                data: radiance
+                    .iter()
+                    .rev()
+                    .flatten()
+                    .map(|p| component(*p, c))
+                    .collect(),
+            })
+            .collect();
+        for layer in LAYERS {
+            for (c, name) in exr_channels(layer).into_iter().enumerate() {
+                channels.push(exr::Channel {
+                    name,
+                    data: aovs
+                        .iter()
+                        .rev()
+                        .flatten()
+                        .map(|aov| component(aov.layer(layer), c))
+                        .collect(),
+                });
+            }
+        }
+        return output::write_exr(path, width, height, &channels, compression);
+    }
+
+    let max_depth = aovs
+        .iter()
+        .flatten()
+        .map(|aov| aov.depth)
+        .fold(f64::EPSILON, f64::max);
+    for layer in LAYERS {
+        let path = layer_path(path, layer);
+        if format.is_high_dynamic_range() {
+            let pixels: Vec<[f32; 3]> = aovs
+                .iter()
+                .rev()
+                .flatten()
+                .map(|aov| {
+                    let p = aov.layer(layer);
+                    [p.x as f32, p.y as f32, p.z as f32]
+                })
+                .collect();
+            output::write_float_image(&path, width, height, &pixels, compression)?;
+        } else {
+            let rows = aovs.iter().rev().map(|row| {
+                row.iter()
+                    .flat_map(|aov| display_pixel(aov, layer, max_depth, tone_mapper).to_rgb())
+                    .collect::<Vec<u8>>()
+            });
+            output::write_image(&path, width, height, rows)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::Aov;
+    use crate::bodies::HitRecord;
+    use crate::point::Point;
+
+    #[test]
+    fn test_object_id() {
+        // The id comes from the first sample that hits, even after a miss.
+        let mut aov = Aov::default();
+        let mut rec = HitRecord::default();
+        aov.add_miss(Point::new(1., 1., 1.));
+        rec.object_id = 3;
+        aov.add_hit(2., &rec);
+        rec.object_id = 5;
+        aov.add_hit(2., &rec);
+        let average = aov.average();
+        assert_eq!(average.object_id, 3);
+        assert!(average.is_hit());
+        assert_eq!(average.depth, 2.);
+    }
+}
@@ -1,15 +1,43 @@
 pub mod bodyprops;
 pub mod bvh;
+pub mod capsule;
 pub mod collision;
+pub mod cone;
+pub mod csg;
 pub mod cube;
+pub mod cylinder;
+pub mod disk;
+pub mod distance_field;
+pub mod frame;
+pub mod heightfield;
+pub mod medium;
+pub mod mesh;
+pub mod motion;
+pub mod quad;
 pub mod rect;
 pub mod sphere;
 pub mod texture;
+pub mod torus;
+pub mod transformed;
+pub mod triangle;
 
 pub use bodyprops::BodyProps;
 pub use bvh::BVH;
+pub use capsule::Capsule;
 pub use collision::{Body, HitRecord};
+pub use cone::Cone;
+pub use csg::Csg;
 pub use cube::Cube;
+pub use cylinder::Cylinder;
+pub use disk::Disk;
+pub use distance_field::DistanceField;
+pub use heightfield::Heightfield;
+pub use medium::Medium;
+pub use mesh::TriangleMesh;
+pub use quad::Quad;
 pub use rect::Rect;
 pub use sphere::Sphere;
 pub use texture::Texture;
+pub use torus::Torus;
+pub use transformed::Transformed;
+pub use triangle::Triangle;
@@ -1,7 +1,10 @@
 use crate::bodies::collision::HitRecord;
+use crate::bodies::frame::Frame;
 use crate::bodies::texture::Texture;
 use crate::point::Point;
+use crate::random;
 use crate::raytracer::Ray;
+use rand::Rng;
 
 #[derive(Clone, Copy, Debug)]
 pub enum Material {
@@ -9,6 +12,31 @@ pub enum Material {
     Dielectric(f64),
     Metal(f64),
     Ether,
+    // Scatters inside a medium, in a direction picked by the phase function.
+    Volume(Phase),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Phase {
+    Isotropic,
+    // Mostly forward when `g` is towards 1 and backward towards -1.
+    HenyeyGreenstein(f64),
+}
+
+impl Phase {
+    pub fn sample(self, direction: Point) -> Point {
+        let g = match self {
+            Phase::HenyeyGreenstein(g) if g.abs() > 1e-3 => g,
+            _ => return Point::random_unit_vector(),
+        };
+        let mut rng = random::rng();
+        let s = (1. - g * g) / (1. - g + 2. * g * rng.gen::<f64>());
+        let cos_theta = ((1. + g * g - s * s) / (2. * g)).clamp(-1., 1.);
+        let sin_theta = (1. - cos_theta * cos_theta).sqrt();
+        let phi = 2. * std::f64::consts::PI * rng.gen::<f64>();
+        let local = Point::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+        Frame::new(Point::default(), direction).direction_to_world(local)
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -47,20 +75,37 @@ impl BodyProps {
             material: Material::Ether,
         }
     }
+    pub fn texture(&self) -> Texture {
+        self.texture
+    }
+    pub fn material(&self) -> Material {
+        self.material
+    }
+    pub fn with_texture(self, texture: Texture) -> Self {
+        BodyProps { texture, ..self }
+    }
     pub fn reflectance(cosine: f64, ref_idx: f64) -> f64 {
         let mut r0 = (1. - ref_idx) / (1. + ref_idx);
         r0 = r0 * r0;
         r0 + (1. - r0) * (1. - cosine).powi(5)
     }
+    pub fn albedo(&self, rec: &HitRecord) -> Point {
+        self.texture.color(rec.u, rec.v, rec.p)
+    }
     pub fn scatter(&self, ray_in: &Ray, rec: &HitRecord) -> Option<(Point, Ray)> {
         match self.material {
             Material::Ether => None,
+            Material::Volume(phase) => {
+                let scattered =
+                    Ray::new(rec.p, phase.sample(ray_in.direction)).with_time(ray_in.time);
+                Some((self.texture.color(rec.u, rec.v, rec.p), scattered))
+            }
             Material::Lambertian => {
                 let mut scatter_direction = rec.normal + Point::random_unit_vector();
                 if scatter_direction.near_zero() {
                     scatter_direction = rec.normal;
                 }
-                let scattered = Ray::new(rec.p, scatter_direction);
+                let scattered = Ray::new(rec.p, scatter_direction).with_time(ray_in.time);
                 Some((self.texture.color(rec.u, rec.v, rec.p), scattered))
             }
             Material::Metal(fuzziness) => {
@@ -68,7 +113,8 @@ impl BodyProps {
                 let scattered = Ray::new(
                     rec.p,
                     reflected + fuzziness * Point::random_in_unit_sphere(),
-                );
+                )
+                .with_time(ray_in.time);
                 Some((self.texture.color(rec.u, rec.v, rec.p), scattered))
             }
             Material::Dielectric(index_refraction) => {
@@ -82,16 +128,15 @@ impl BodyProps {
 
                 let cos_theta = -unit_direction.dot(rec.normal).min(1.0);
                 let sin_theta = (1. - cos_theta * cos_theta).sqrt();
-                let directed: Point;
                 let cannot_refract = (refraction_ratio * sin_theta) > 1.;
                 let schlick_approximation =
-                    Self::reflectance(cos_theta, refraction_ratio) > rand::random::<f64>();
-                if cannot_refract || schlick_approximation {
-                    directed = unit_direction.reflect(rec.normal);
+                    Self::reflectance(cos_theta, refraction_ratio) > random::rng().gen::<f64>();
+                let directed = if cannot_refract || schlick_approximation {
+                    unit_direction.reflect(rec.normal)
                 } else {
-                    directed = unit_direction.refract(rec.normal, refraction_ratio);
+                    unit_direction.refract(rec.normal, refraction_ratio)
                 };
-                let scattered = Ray::new(rec.p, directed);
+                let scattered = Ray::new(rec.p, directed).with_time(ray_in.time);
                 Some((self.texture.color(rec.u, rec.v, rec.p), scattered))
             }
         }
@@ -1,78 +1,401 @@
 use crate::bodies::collision::{Body, HitRecord, AABB};
 use crate::ordered_float::OrderedFloat;
-use crate::point::Point;
 use crate::raytracer::Ray;
-use rand::Rng;
+use crate::scenefile::writer::{export, Export};
+use crate::scenefile::{Error, Item};
+
+// Bodies that are tested one by one rather than split further, unless
+// splitting them is still cheaper.
+const LEAF_SIZE: usize = 4;
+// Buckets along each axis that split positions are chosen between.
+const BINS: usize = 12;
+// Testing a box costs about an eighth of testing a body.
+const TRAVERSAL_COST: f64 = 0.125;
+// Below this depth bodies are halved instead, which keeps the hierarchy
+// shallow enough for the traversal stack.
+const SPLIT_DEPTH: usize = 30;
+
+// A node of the hierarchy. Leaves hold `count` bodies from `start`, inner
+// nodes have a `count` of zero, their first child right after them and
+// their second child at `start`. The first child is the one lower along
+// `axis`.
 #[derive(Debug)]
-pub struct BVH {
-    left: Box<dyn Body>,
-    right: Box<dyn Body>,
+struct Node {
     aabb: AABB,
+    start: u32,
+    count: u32,
+    axis: u8,
+}
+
+// The nodes are laid out depth first, and the bodies of each leaf are next
+// to each other in the order the leaves are visited.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug)]
+pub struct BVH {
+    nodes: Vec<Node>,
+    bodies: Vec<Box<dyn Body>>,
+    ids: Vec<usize>,
 }
 
 impl BVH {
-    pub fn new(mut bodies: Vec<Box<dyn Body>>) -> Self {
-        let aabb = AABB::from_bodies(&bodies).unwrap();
-        let length = &bodies.len();
-        match length {
-            0 => panic!("got an empty world"),
-            1 => BVH {
-                left: bodies.pop().unwrap(),
-                right: Box::new(NullBody {}),
-                aabb,
-            },
-            2 => BVH {
-                left: bodies.pop().unwrap(),
-                right: bodies.pop().unwrap(),
-                aabb,
-            },
-            _ => {
-                let mut rng = rand::thread_rng();
-                match rng.gen_range(0..3) {
-                    0 => bodies.sort_unstable_by_key(|b| OrderedFloat(b.bounding_box().min.x)),
-                    1 => bodies.sort_unstable_by_key(|b| OrderedFloat(b.bounding_box().min.y)),
-                    _ => bodies.sort_unstable_by_key(|b| OrderedFloat(b.bounding_box().min.z)),
-                }
+    // Bodies are numbered in the given order, starting at one, and report
+    // that number as the object id of their hits.
+    pub fn new(bodies: Vec<Box<dyn Body>>) -> Self {
+        assert!(!bodies.is_empty(), "got an empty world");
+        let mut bvh = BVH {
+            nodes: vec![],
+            bodies: Vec::with_capacity(bodies.len()),
+            ids: Vec::with_capacity(bodies.len()),
+        };
+        bvh.build(
+            bodies
+                .into_iter()
+                .enumerate()
+                .map(|(i, b)| (i + 1, b))
+                .collect(),
+            0,
+        );
+        bvh
+    }
 
-                let left = bodies.drain(0..(length / 2)).collect();
-                BVH {
-                    left: Box::new(BVH::new(left)),
-                    right: Box::new(BVH::new(bodies)),
-                    aabb,
-                }
+    // Adds the node for `bodies` and the nodes below it.
+    fn build(&mut self, bodies: Vec<(usize, Box<dyn Body>)>, depth: usize) {
+        let boxes: Vec<AABB> = bodies.iter().map(|(_, b)| b.bounding_box()).collect();
+        let aabb = boxes
+            .iter()
+            .copied()
+            .reduce(|a, b| a.surrounding_box(b))
+            .unwrap();
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            aabb,
+            start: self.bodies.len() as u32,
+            count: bodies.len() as u32,
+            axis: 0,
+        });
+        let split = if depth < SPLIT_DEPTH {
+            split(&boxes, &aabb)
+        } else {
+            median(&boxes)
+        };
+        let Some((axis, sides)) = split else {
+            for (id, body) in bodies {
+                self.ids.push(id);
+                self.bodies.push(body);
             }
-        }
+            return;
+        };
+
+        let (first, second): (Vec<_>, Vec<_>) =
+            bodies.into_iter().zip(sides).partition(|(_, right)| !right);
+        let unzip = |side: Vec<_>| side.into_iter().map(|(body, _)| body).collect();
+        self.build(unzip(first), depth + 1);
+        let second_index = self.nodes.len();
+        self.build(unzip(second), depth + 1);
+        self.nodes[index] = Node {
+            aabb,
+            start: second_index as u32,
+            count: 0,
+            axis: axis as u8,
+        };
     }
 }
 
 impl Body for BVH {
     fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
-        if !self.aabb.hit(ray, t_min, t_max) {
-            return false;
+        let backwards = ray.direction.as_array().map(|d| d < 0.);
+        let mut closest = t_max;
+        // Each level leaves at most one node waiting, and the hierarchy is
+        // never deeper than this.
+        let mut stack = [0u32; 64];
+        let mut top = 1;
+        while top > 0 {
+            top -= 1;
+            let index = stack[top];
+            let node = &self.nodes[index as usize];
+            if !node.aabb.hit(ray, t_min, closest) {
+                continue;
+            }
+            if node.count == 0 {
+                // Visit the nearer child first, so that the other one can
+                // often be skipped.
+                let (near, far) = if backwards[node.axis as usize] {
+                    (node.start, index + 1)
+                } else {
+                    (index + 1, node.start)
+                };
+                stack[top] = far;
+                stack[top + 1] = near;
+                top += 2;
+                continue;
+            }
+            let start = node.start as usize;
+            for i in start..start + node.count as usize {
+                if self.bodies[i].hit(ray, t_min, closest, rec) {
+                    closest = rec.t;
+                    rec.object_id = self.ids[i];
+                }
+            }
         }
-
-        let hit_left = self.left.hit(ray, t_min, t_max, rec);
-        let new_t_max = match hit_left {
-            true => rec.t,
-            false => t_max,
-        };
-        let hit_right = self.right.hit(ray, t_min, new_t_max, rec);
-        hit_left || hit_right
+        closest < t_max
     }
     fn bounding_box(&self) -> AABB {
-        self.aabb
+        self.nodes[0].aabb
     }
 }
 
-#[derive(Debug)]
-struct NullBody;
+impl Export for BVH {
+    fn to_items(&self, items: &mut Vec<Item>) -> Result<(), Error> {
+        for body in &self.bodies {
+            export(body.as_ref(), items)?;
+        }
+        Ok(())
+    }
+}
+
+// Chooses where to split bodies with `boxes` by the surface area heuristic:
+// a ray that hits a box hits each child box about in proportion to its area,
+// so a split costs a box test plus the bodies of each child weighted by its
+// share of the area. Bodies are binned by the centres of their boxes along
+// each axis and the cheapest boundary between bins wins. Returns the axis
+// and which bodies go to the second child, or None when a leaf is cheaper.
+fn split(boxes: &[AABB], aabb: &AABB) -> Option<(usize, Vec<bool>)> {
+    let (centers, lowest, highest) = centers(boxes);
+    let bin = |axis: usize, center: &[f64; 3]| {
+        let position = (center[axis] - lowest[axis]) / (highest[axis] - lowest[axis]);
+        ((position * BINS as f64) as usize).min(BINS - 1)
+    };
+
+    let mut best: Option<(f64, usize, usize)> = None;
+    for axis in 0..3 {
+        // Centres all in one place can not be told apart along this axis.
+        if highest[axis] <= lowest[axis] {
+            continue;
+        }
+        let mut bins = [(0, None); BINS];
+        for (aabb, center) in boxes.iter().zip(&centers) {
+            let (count, bounds) = &mut bins[bin(axis, center)];
+            *count += 1;
+            *bounds = Some(surround(*bounds, *aabb));
+        }
+        // How many bodies there are up to each bin, and the area of their
+        // box, from the left and from the right.
+        let mut left = [(0, 0.); BINS];
+        let mut right = [(0, 0.); BINS];
+        let (mut count, mut bounds) = (0, None);
+        for i in 0..BINS {
+            count += bins[i].0;
+            bounds = bins[i].1.map_or(bounds, |b| Some(surround(bounds, b)));
+            left[i] = (count, bounds.map_or(0., |b: AABB| b.surface_area()));
+        }
+        let (mut count, mut bounds) = (0, None);
+        for i in (0..BINS).rev() {
+            count += bins[i].0;
+            bounds = bins[i].1.map_or(bounds, |b| Some(surround(bounds, b)));
+            right[i] = (count, bounds.map_or(0., |b: AABB| b.surface_area()));
+        }
+        for boundary in 0..BINS - 1 {
+            let ((n_left, a_left), (n_right, a_right)) = (left[boundary], right[boundary + 1]);
+            if n_left == 0 || n_right == 0 {
+                continue;
+            }
+            let cost = TRAVERSAL_COST
+                + (n_left as f64 * a_left + n_right as f64 * a_right) / aabb.surface_area();
+            if best.is_none_or(|(c, _, _)| cost < c) {
+                best = Some((cost, axis, boundary));
+            }
+        }
+    }
 
-impl Body for NullBody {
-    fn hit(&self, _ray: &Ray, _t_min: f64, _t_max: f64, _rec: &mut HitRecord) -> bool {
-        false
+    match best {
+        Some((cost, axis, boundary)) if cost < boxes.len() as f64 || boxes.len() > LEAF_SIZE => {
+            Some((
+                axis,
+                centers.iter().map(|c| bin(axis, c) > boundary).collect(),
+            ))
+        }
+        Some(_) => None,
+        // Too many bodies around the same centre for a leaf are halved.
+        None => median(boxes),
     }
+}
 
-    fn bounding_box(&self) -> AABB {
-        AABB::new(Point::new(0., 0., 0.), Point::new(0., 0., 0.))
+fn surround(bounds: Option<AABB>, aabb: AABB) -> AABB {
+    bounds.map_or(aabb, |b| b.surrounding_box(aabb))
+}
+
+// The centres of `boxes`, and the lowest and highest of them along each axis.
+fn centers(boxes: &[AABB]) -> (Vec<[f64; 3]>, [f64; 3], [f64; 3]) {
+    let centers: Vec<[f64; 3]> = boxes.iter().map(|b| b.center().as_array()).collect();
+    let mut lowest = [f64::INFINITY; 3];
+    let mut highest = [f64::NEG_INFINITY; 3];
+    for center in &centers {
+        for axis in 0..3 {
+            lowest[axis] = lowest[axis].min(center[axis]);
+            highest[axis] = highest[axis].max(center[axis]);
+        }
+    }
+    (centers, lowest, highest)
+}
+
+// Halves the bodies along the axis their centres are most spread out on,
+// unless they fit in a leaf.
+fn median(boxes: &[AABB]) -> Option<(usize, Vec<bool>)> {
+    if boxes.len() <= LEAF_SIZE {
+        return None;
+    }
+    let (centers, lowest, highest) = centers(boxes);
+    let axis = match [0, 1, 2].map(|i| highest[i] - lowest[i]) {
+        [x, y, z] if x >= y && x >= z => 0,
+        [_, y, z] if y >= z => 1,
+        _ => 2,
+    };
+    let mut order: Vec<usize> = (0..boxes.len()).collect();
+    order.sort_by_key(|&i| OrderedFloat(centers[i][axis]));
+    let mut sides = vec![false; boxes.len()];
+    for &i in &order[boxes.len() / 2..] {
+        sides[i] = true;
+    }
+    Some((axis, sides))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BVH, SPLIT_DEPTH};
+    use crate::bodies::{Body, BodyProps, Cube, HitRecord, Sphere};
+    use crate::point::Point;
+    use crate::random;
+    use crate::raytracer::Ray;
+    use rand::Rng;
+
+    #[test]
+    fn test_hits_match_bodies() {
+        random::seed(9);
+        let mut rng = random::rng();
+        let mut point = |size: f64| {
+            Point::new(
+                rng.gen_range(-size..size),
+                rng.gen_range(-size..size),
+                rng.gen_range(-size..size),
+            )
+        };
+        // Clumps of spheres, some exactly on top of each other.
+        let mut shapes: Vec<(Point, f64)> = (0..300).map(|_| (point(10.), 0.3)).collect();
+        shapes.extend([(Point::new(1., 1., 1.), 0.5); 20]);
+        let sphere = |(center, radius)| Sphere::new(center, radius, BodyProps::null());
+        let spheres: Vec<Sphere> = shapes.iter().map(|s| sphere(*s)).collect();
+        let bodies = shapes
+            .iter()
+            .map(|s| Box::new(sphere(*s)) as Box<dyn Body>)
+            .collect();
+        let bvh = BVH::new(bodies);
+        for _ in 0..500 {
+            let ray = Ray::new(point(15.), point(1.));
+            let mut expected = HitRecord::default();
+            let mut closest = f64::INFINITY;
+            let mut id = 0;
+            for (i, sphere) in spheres.iter().enumerate() {
+                if sphere.hit(&ray, 0.001, closest, &mut expected) {
+                    closest = expected.t;
+                    id = i + 1;
+                }
+            }
+            let mut rec = HitRecord::default();
+            assert_eq!(
+                bvh.hit(&ray, 0.001, f64::INFINITY, &mut rec),
+                closest.is_finite()
+            );
+            if closest.is_finite() {
+                assert_eq!(rec.t, closest);
+                // Spheres on top of each other are hit at the same place.
+                assert!(rec.object_id == id || id > 300);
+            }
+        }
+    }
+
+    // How many levels there are below and including node `index`.
+    fn depth(bvh: &BVH, index: usize) -> usize {
+        let node = &bvh.nodes[index];
+        if node.count > 0 {
+            return 1;
+        }
+        1 + depth(bvh, index + 1).max(depth(bvh, node.start as usize))
+    }
+
+    #[test]
+    fn test_deep_hierarchy() {
+        // A row of spheres each twice as far out is split one sphere at a
+        // time, and spheres on top of each other can not be told apart at
+        // all, so past SPLIT_DEPTH both are halved instead.
+        let mut shapes: Vec<(Point, f64)> = (0..150)
+            .map(|i| {
+                let scale = 2f64.powi(i);
+                (Point::new(scale, 0., 0.), 0.1 * scale)
+            })
+            .collect();
+        shapes.extend([(Point::new(0.5, 0., 0.), 0.01); 200]);
+        let sphere = |(center, radius)| Sphere::new(center, radius, BodyProps::null());
+        let bodies = shapes
+            .iter()
+            .map(|s| Box::new(sphere(*s)) as Box<dyn Body>)
+            .collect();
+        let bvh = BVH::new(bodies);
+        let levels = depth(&bvh, 0);
+        assert!(levels > SPLIT_DEPTH && levels < 64, "{} levels", levels);
+        let mut ids = bvh.ids.clone();
+        ids.sort();
+        assert_eq!(ids, (1..=shapes.len()).collect::<Vec<_>>());
+
+        for (i, (center, radius)) in shapes.iter().enumerate().take(151) {
+            let origin = *center + Point::new(0., 0., 4. * radius);
+            let ray = Ray::new(origin, Point::new(0., 0., -1.));
+            let mut rec = HitRecord::default();
+            assert!(bvh.hit(&ray, 0.001, f64::INFINITY, &mut rec));
+            assert!((rec.t - 3. * radius).abs() < 1e-9 * radius);
+            // Spheres on top of each other are hit at the same place.
+            assert!(rec.object_id == i + 1 || (i >= 150 && rec.object_id > 150));
+        }
+
+        // A single body is a leaf of its own.
+        let one = BVH::new(vec![Box::new(sphere(shapes[0]))]);
+        assert_eq!((one.nodes.len(), depth(&one, 0)), (1, 1));
+    }
+
+    #[test]
+    fn test_nearest_across_siblings() {
+        // A slab across the whole scene is in a child lower along x than a
+        // sphere, so a ray going right reaches it first in the hierarchy, but
+        // the sphere is nearer along the ray.
+        let slab = Cube::new(
+            Point::new(-10., 4.9, -1.),
+            Point::new(10., 5.1, 1.),
+            BodyProps::null(),
+        );
+        let mut bodies: Vec<Box<dyn Body>> = vec![Box::new(slab)];
+        for i in 0..4 {
+            let center = Point::new(-9. + i as f64 * 0.5, 0., 0.);
+            bodies.push(Box::new(Sphere::new(center, 0.2, BodyProps::null())));
+        }
+        for i in 0..4 {
+            let center = Point::new(8. + i as f64 * 0.5, 2., 0.);
+            bodies.push(Box::new(Sphere::new(center, 0.5, BodyProps::null())));
+        }
+        let bvh = BVH::new(bodies);
+        // Every split is along x, so a ray going right visits the bodies in
+        // the order they are laid out.
+        assert!(bvh.nodes.iter().all(|node| node.axis == 0));
+        let slab_at = bvh.ids.iter().position(|&id| id == 1).unwrap();
+        let sphere_at = bvh.ids.iter().position(|&id| id == 6).unwrap();
+        assert!(slab_at < sphere_at);
+
+        let up = Ray::new(Point::new(8., 0., 0.), Point::new(0.01, 1., 0.));
+        let mut rec = HitRecord::default();
+        assert!(bvh.hit(&up, 0.001, f64::INFINITY, &mut rec));
+        assert_eq!(rec.object_id, 6);
+        assert!((rec.t - 1.5).abs() < 0.01);
+        // Past the sphere only the slab is left.
+        assert!(bvh.hit(&up, 2.5, f64::INFINITY, &mut rec));
+        assert_eq!(rec.object_id, 1);
+        assert!((rec.t - 4.9).abs() < 1e-9);
     }
 }
@@ -0,0 +1,135 @@
+use crate::bodies::bodyprops::BodyProps;
+use crate::bodies::collision::{Body, HitRecord, AABB};
+use crate::bodies::frame::{set_hit, Frame};
+use crate::point::Point;
+use crate::polynomial;
+use crate::raytracer::Ray;
+use crate::scenefile::writer::Export;
+use crate::scenefile::{Error, Item};
+
+// A cylinder from `base` to `top` with half a sphere on either end.
+#[derive(Debug)]
+pub struct Capsule {
+    base: Point,
+    top: Point,
+    radius: f64,
+    frame: Frame,
+    height: f64,
+    body_props: BodyProps,
+}
+
+impl Capsule {
+    pub fn new(base: Point, top: Point, radius: f64, body_props: BodyProps) -> Capsule {
+        Capsule {
+            base,
+            top,
+            radius,
+            frame: Frame::new(base, top - base),
+            height: (top - base).length(),
+            body_props,
+        }
+    }
+}
+
+impl Body for Capsule {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let (o, d) = self.frame.ray_to_local(ray);
+        let (r, h) = (self.radius, self.height);
+        let mut closest = t_max;
+        let mut found = None;
+        let mut consider = |t: f64, normal: Point, p: Point| {
+            if t >= t_min && t < closest {
+                closest = t;
+                // Along the length from the tip of the base to the tip of the top.
+                found = Some((t, normal, (Frame::angle(p), (p.z + r) / (h + 2. * r))));
+            }
+        };
+
+        let a = d.x * d.x + d.y * d.y;
+        let b = 2. * (o.x * d.x + o.y * d.y);
+        let c = o.x * o.x + o.y * o.y - r * r;
+        for t in polynomial::solve_quadratic(a, b, c).unwrap_or([f64::NAN; 2]) {
+            let p = o + t * d;
+            if (0. ..=h).contains(&p.z) {
+                consider(t, Point::new(p.x, p.y, 0.), p);
+            }
+        }
+
+        // Each end only counts on its own side of the cylinder.
+        for (z, outside) in [(0., -1.), (h, 1.)] {
+            let center = Point::new(0., 0., z);
+            let oc = o - center;
+            let roots = polynomial::solve_quadratic(d.dot(d), 2. * oc.dot(d), oc.dot(oc) - r * r);
+            for t in roots.unwrap_or([f64::NAN; 2]) {
+                let p = o + t * d;
+                if (p.z - z) * outside >= 0. {
+                    consider(t, p - center, p);
+                }
+            }
+        }
+
+        match found {
+            Some((t, normal, uv)) => {
+                set_hit(rec, ray, &self.frame, t, normal, uv);
+                rec.body_props = self.body_props;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn bounding_box(&self) -> AABB {
+        let r = Point::new(self.radius, self.radius, self.radius);
+        AABB::from_points(&[self.base - r, self.base + r, self.top - r, self.top + r])
+    }
+}
+
+impl Export for Capsule {
+    fn to_items(&self, items: &mut Vec<Item>) -> Result<(), Error> {
+        items.push(
+            Item::new("capsule")
+                .field("base", self.base)
+                .field("top", self.top)
+                .field("radius", self.radius)
+                .body_props(&self.body_props),
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Capsule;
+    use crate::bodies::{Body, BodyProps, HitRecord};
+    use crate::point::Point;
+    use crate::raytracer::Ray;
+
+    #[test]
+    fn test_capsule() {
+        let capsule = Capsule::new(
+            Point::new(0., 0., 0.),
+            Point::new(0., 2., 0.),
+            0.5,
+            BodyProps::null(),
+        );
+        let mut rec = HitRecord::default();
+        let side = Ray::new(Point::new(3., 1., 0.), Point::new(-1., 0., 0.));
+        assert!(capsule.hit(&side, 0.001, f64::INFINITY, &mut rec));
+        assert!((rec.p - Point::new(0.5, 1., 0.)).length() < 1e-12);
+        assert!((rec.normal - Point::new(1., 0., 0.)).length() < 1e-12);
+        assert!((rec.v - 0.5).abs() < 1e-12);
+
+        let down = Ray::new(Point::new(0.3, 5., 0.), Point::new(0., -1., 0.));
+        assert!(capsule.hit(&down, 0.001, f64::INFINITY, &mut rec));
+        assert!((rec.p - Point::new(0.3, 2.4, 0.)).length() < 1e-12);
+        assert!((rec.normal - Point::new(0.6, 0.8, 0.)).length() < 1e-12);
+        // Inside the capsule the far end is hit from within.
+        assert!(capsule.hit(&down, 3., f64::INFINITY, &mut rec));
+        assert!((rec.p - Point::new(0.3, -0.4, 0.)).length() < 1e-12);
+        assert!(!rec.front_face);
+
+        let bbox = capsule.bounding_box();
+        assert!((bbox.min.y + 0.5).abs() < 1e-12 && (bbox.max.y - 2.5).abs() < 1e-12);
+        assert!((bbox.max.x - 0.5).abs() < 1e-12);
+    }
+}
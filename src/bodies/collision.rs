@@ -1,8 +1,10 @@
+use std::any::Any;
 use std::fmt::Debug;
 
 use crate::bodies::bodyprops::BodyProps;
 use crate::point::Point;
 use crate::raytracer::Ray;
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Clone, Copy, Debug)]
 pub struct AABB {
     pub min: Point,
@@ -13,30 +15,56 @@ impl AABB {
         AABB { min, max }
     }
 
-    pub fn from_bodies(bodies: &Vec<Box<dyn Body>>) -> Option<AABB> {
-        if bodies.is_empty() {
-            return None;
+    // The smallest box around `points`, padded where it is flat, like the
+    // box of a `Rect`, so that it has a volume to hit.
+    pub fn from_points(points: &[Point]) -> AABB {
+        let mut min = points[0];
+        let mut max = points[0];
+        for p in &points[1..] {
+            min = Point::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z));
+            max = Point::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z));
         }
-        let mut bbox = bodies[0].bounding_box();
-        for body in bodies.iter().skip(1) {
-            bbox = bbox.surrounding_box(body.bounding_box())
+        let padding = 0.0001;
+        for (min, max) in [
+            (&mut min.x, &mut max.x),
+            (&mut min.y, &mut max.y),
+            (&mut min.z, &mut max.z),
+        ] {
+            if *max - *min < padding {
+                *min -= padding / 2.;
+                *max += padding / 2.;
+            }
         }
-        Some(bbox)
+        AABB::new(min, max)
     }
 
     pub fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> bool {
+        self.interval(ray, t_min, t_max).is_some()
+    }
+    // The part of the ray between `t_min` and `t_max` that is in the box.
+    pub fn interval(&self, ray: &Ray, mut t_min: f64, mut t_max: f64) -> Option<(f64, f64)> {
         let min = self.min.as_array();
         let max = self.max.as_array();
         let origin = ray.origin.as_array();
         let direction = ray.direction.as_array();
+        // The ray has to be inside all three slabs at once.
         for i in 0..3 {
             let t0 = ((min[i] - origin[i]) / direction[i]).min((max[i] - origin[i]) / direction[i]);
             let t1 = ((min[i] - origin[i]) / direction[i]).max((max[i] - origin[i]) / direction[i]);
-            if t1.min(t_max) <= t0.max(t_min) {
-                return false;
+            t_min = t0.max(t_min);
+            t_max = t1.min(t_max);
+            if t_max <= t_min {
+                return None;
             }
         }
-        true
+        Some((t_min, t_max))
+    }
+    pub fn center(&self) -> Point {
+        (self.min + self.max) * 0.5
+    }
+    pub fn surface_area(&self) -> f64 {
+        let d = self.max - self.min;
+        2. * (d.x * d.y + d.y * d.z + d.z * d.x)
     }
     pub fn surrounding_box(&self, other: AABB) -> AABB {
         Self::new(
@@ -54,6 +82,7 @@ impl AABB {
     }
 }
 
+#[derive(Clone, Copy)]
 pub struct HitRecord {
     pub p: Point,
     pub normal: Point,
@@ -62,6 +91,7 @@ pub struct HitRecord {
     pub t: f64,
     pub u: f64,
     pub v: f64,
+    pub object_id: usize,
 }
 
 impl HitRecord {
@@ -74,6 +104,7 @@ impl HitRecord {
             t: 0.,
             u: 0.,
             v: 0.,
+            object_id: 0,
         }
     }
 
@@ -86,12 +117,25 @@ impl HitRecord {
     }
 }
 
-pub trait Body: Sync + Send + Debug {
+const MAX_CROSSINGS: usize = 64;
+
+pub trait Body: Any + Sync + Send + Debug {
     fn hit(&self, _ray: &Ray, _t_min: f64, _t_max: f64, _rec: &mut HitRecord) -> bool {
         false
     }
     fn bounding_box(&self) -> AABB;
-    fn color(&self, _ray: &Ray, _angle: f64) -> Point {
-        Point::new(1., 0., 0.)
+    // Appends every crossing of the surface from `t_min` on, in order. A
+    // closed body is entered where a crossing is a front face and left
+    // where it is not. By default they are found one hit after another.
+    fn crossings(&self, ray: &Ray, t_min: f64, crossings: &mut Vec<HitRecord>) {
+        let mut t_min = t_min;
+        let mut rec = HitRecord::default();
+        for _ in 0..MAX_CROSSINGS {
+            if !self.hit(ray, t_min, f64::INFINITY, &mut rec) {
+                break;
+            }
+            crossings.push(rec);
+            t_min = rec.t + 1e-9 * rec.t.abs().max(1.);
+        }
     }
 }
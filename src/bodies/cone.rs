@@ -0,0 +1,180 @@
+use crate::bodies::bodyprops::BodyProps;
+use crate::bodies::collision::{Body, HitRecord, AABB};
+use crate::bodies::frame::{circle_extent, set_hit, Frame};
+use crate::point::Point;
+use crate::polynomial;
+use crate::raytracer::Ray;
+use crate::scenefile::writer::Export;
+use crate::scenefile::{Error, Item};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Caps {
+    Both,
+    Base,
+    Top,
+    None,
+}
+
+impl Caps {
+    fn base(self) -> bool {
+        matches!(self, Caps::Both | Caps::Base)
+    }
+    fn top(self) -> bool {
+        matches!(self, Caps::Both | Caps::Top)
+    }
+}
+
+// A cone from a circle at `base` to a circle at `top`, truncated when both
+// radii are larger than zero.
+#[derive(Debug)]
+pub struct Cone {
+    base: Point,
+    top: Point,
+    base_radius: f64,
+    top_radius: f64,
+    caps: Caps,
+    frame: Frame,
+    height: f64,
+    body_props: BodyProps,
+}
+
+impl Cone {
+    pub fn new(
+        base: Point,
+        top: Point,
+        base_radius: f64,
+        top_radius: f64,
+        body_props: BodyProps,
+    ) -> Cone {
+        Cone {
+            base,
+            top,
+            base_radius,
+            top_radius,
+            caps: Caps::Both,
+            frame: Frame::new(base, top - base),
+            height: (top - base).length(),
+            body_props,
+        }
+    }
+    pub fn with_caps(self, caps: Caps) -> Self {
+        Cone { caps, ..self }
+    }
+    pub fn caps(&self) -> Caps {
+        self.caps
+    }
+}
+
+impl Body for Cone {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let (o, d) = self.frame.ray_to_local(ray);
+        let (r0, h) = (self.base_radius, self.height);
+        // How fast the radius changes along the axis.
+        let k = (self.top_radius - r0) / h;
+        let mut closest = t_max;
+        let mut found = None;
+
+        // The side, where x^2 + y^2 = (r0 + k z)^2.
+        let radius = r0 + k * o.z;
+        let a = d.x * d.x + d.y * d.y - k * k * d.z * d.z;
+        let b = 2. * (o.x * d.x + o.y * d.y - k * d.z * radius);
+        let c = o.x * o.x + o.y * o.y - radius * radius;
+        for t in polynomial::solve_quadratic(a, b, c).unwrap_or([f64::NAN; 2]) {
+            let p = o + t * d;
+            if t >= t_min && t < closest && (0. ..=h).contains(&p.z) {
+                let normal = Point::new(p.x, p.y, -k * (r0 + k * p.z));
+                closest = t;
+                found = Some((t, normal, (Frame::angle(p), p.z / h)));
+            }
+        }
+
+        let caps = [
+            (self.caps.base(), 0., r0, -1.),
+            (self.caps.top(), h, self.top_radius, 1.),
+        ];
+        for (_, z, radius, facing) in caps.into_iter().filter(|cap| cap.0 && cap.2 > 0.) {
+            let t = (z - o.z) / d.z;
+            let p = o + t * d;
+            if t >= t_min && t < closest && p.x * p.x + p.y * p.y <= radius * radius {
+                let distance = (p.x * p.x + p.y * p.y).sqrt() / radius;
+                closest = t;
+                found = Some((t, Point::new(0., 0., facing), (Frame::angle(p), distance)));
+            }
+        }
+
+        match found {
+            Some((t, normal, uv)) => {
+                set_hit(rec, ray, &self.frame, t, normal, uv);
+                rec.body_props = self.body_props;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn bounding_box(&self) -> AABB {
+        let [a, b] = circle_extent(self.base, self.frame.axis(), self.base_radius);
+        let [c, d] = circle_extent(self.top, self.frame.axis(), self.top_radius);
+        AABB::from_points(&[a, b, c, d])
+    }
+}
+
+impl Export for Cone {
+    fn to_items(&self, items: &mut Vec<Item>) -> Result<(), Error> {
+        items.push(
+            Item::new("cone")
+                .field("base", self.base)
+                .field("top", self.top)
+                .field("base_radius", self.base_radius)
+                .field("top_radius", self.top_radius)
+                .field("caps", self.caps)
+                .body_props(&self.body_props),
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Caps, Cone};
+    use crate::bodies::{Body, BodyProps, HitRecord};
+    use crate::point::Point;
+    use crate::raytracer::Ray;
+
+    #[test]
+    fn test_truncated_cone() {
+        // Along x, narrowing from a radius of 2 to 1 over a length of 2.
+        let cone = Cone::new(
+            Point::new(1., 0., 0.),
+            Point::new(3., 0., 0.),
+            2.,
+            1.,
+            BodyProps::null(),
+        );
+        let mut rec = HitRecord::default();
+        let down = Ray::new(Point::new(2., 5., 0.), Point::new(0., -1., 0.));
+        assert!(cone.hit(&down, 0.001, f64::INFINITY, &mut rec));
+        assert!((rec.p - Point::new(2., 1.5, 0.)).length() < 1e-12);
+        assert!((rec.normal - Point::new(0.5, 1., 0.).unit_vector()).length() < 1e-12);
+        assert!((rec.v - 0.5).abs() < 1e-12);
+        assert!(rec.front_face);
+
+        // From inside only the far side is left.
+        assert!(cone.hit(&down, 4., f64::INFINITY, &mut rec));
+        assert!((rec.p - Point::new(2., -1.5, 0.)).length() < 1e-12);
+        assert!(!rec.front_face);
+
+        let along = Ray::new(Point::new(5., 0.5, 0.), Point::new(-1., 0., 0.));
+        assert!(cone.hit(&along, 0.001, f64::INFINITY, &mut rec));
+        assert!((rec.p - Point::new(3., 0.5, 0.)).length() < 1e-12);
+        assert!((rec.normal - Point::new(1., 0., 0.)).length() < 1e-12);
+        let open = cone.with_caps(Caps::Base);
+        assert!(open.hit(&along, 0.001, f64::INFINITY, &mut rec));
+        assert!((rec.p - Point::new(1., 0.5, 0.)).length() < 1e-12);
+        assert!(!rec.front_face);
+
+        let bbox = open.bounding_box();
+        assert!((bbox.min.x - 1.).abs() < 1e-12 && (bbox.max.x - 3.).abs() < 1e-12);
+        assert!((bbox.max.y - 2.).abs() < 1e-12 && (bbox.min.z + 2.).abs() < 1e-12);
+    }
+}
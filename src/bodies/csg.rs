@@ -0,0 +1,210 @@
+use std::sync::Arc;
+
+use crate::bodies::collision::{Body, HitRecord, AABB};
+use crate::point::Point;
+use crate::raytracer::Ray;
+use crate::scenefile::writer::{export, Export};
+use crate::scenefile::{Error, Item, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operation {
+    Union,
+    Intersection,
+    Difference,
+}
+
+impl Operation {
+    fn inside(self, left: bool, right: bool) -> bool {
+        match self {
+            Operation::Union => left || right,
+            Operation::Intersection => left && right,
+            Operation::Difference => left && !right,
+        }
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            Operation::Union => "union",
+            Operation::Intersection => "intersection",
+            Operation::Difference => "difference",
+        }
+    }
+}
+
+// Two closed bodies combined into one. Its surface is where the ray goes
+// in or out of the combination, with the material of the body it belongs to.
+#[derive(Debug)]
+pub struct Csg {
+    operation: Operation,
+    left: Arc<dyn Body>,
+    right: Arc<dyn Body>,
+    aabb: AABB,
+}
+
+impl Csg {
+    pub fn new(operation: Operation, left: Arc<dyn Body>, right: Arc<dyn Body>) -> Self {
+        let (a, b) = (left.bounding_box(), right.bounding_box());
+        let aabb = match operation {
+            Operation::Union => a.surrounding_box(b),
+            Operation::Intersection => AABB::new(
+                Point::new(
+                    a.min.x.max(b.min.x),
+                    a.min.y.max(b.min.y),
+                    a.min.z.max(b.min.z),
+                ),
+                Point::new(
+                    a.max.x.min(b.max.x),
+                    a.max.y.min(b.max.y),
+                    a.max.z.min(b.max.z),
+                ),
+            ),
+            Operation::Difference => a,
+        };
+        Csg {
+            operation,
+            left,
+            right,
+            aabb,
+        }
+    }
+}
+
+impl Body for Csg {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        if !self.aabb.hit(ray, t_min, t_max) {
+            return false;
+        }
+        let mut crossings = vec![];
+        self.crossings(ray, t_min, &mut crossings);
+        match crossings.first() {
+            Some(crossing) if crossing.t <= t_max => {
+                *rec = *crossing;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn crossings(&self, ray: &Ray, t_min: f64, crossings: &mut Vec<HitRecord>) {
+        let mut left = vec![];
+        let mut right = vec![];
+        self.left.crossings(ray, t_min, &mut left);
+        self.right.crossings(ray, t_min, &mut right);
+
+        // The ray starts inside a body when it leaves it first.
+        let mut in_left = left.first().is_some_and(|c| !c.front_face);
+        let mut in_right = right.first().is_some_and(|c| !c.front_face);
+        let mut inside = self.operation.inside(in_left, in_right);
+        let (mut i, mut j) = (0, 0);
+        while i < left.len() || j < right.len() {
+            let mut crossing = if j == right.len() || (i < left.len() && left[i].t <= right[j].t) {
+                i += 1;
+                in_left = left[i - 1].front_face;
+                left[i - 1]
+            } else {
+                j += 1;
+                in_right = right[j - 1].front_face;
+                right[j - 1]
+            };
+            let now = self.operation.inside(in_left, in_right);
+            if now != inside {
+                // The normal already faces the ray, but a subtracted surface
+                // is entered where its body is left.
+                crossing.front_face = now;
+                crossings.push(crossing);
+                inside = now;
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.aabb
+    }
+}
+
+impl Export for Csg {
+    // The two bodies are written first, with a name to refer to them by.
+    fn to_items(&self, items: &mut Vec<Item>) -> Result<(), Error> {
+        let names = vec![
+            named_part(self.left.as_ref(), self.operation.name(), items)?,
+            named_part(self.right.as_ref(), self.operation.name(), items)?,
+        ];
+        items.push(Item::new(self.operation.name()).field("bodies", Value::list(names)));
+        Ok(())
+    }
+}
+
+// Writes a body that is part of a `whole` with a name, and gives that name.
+pub fn named_part(body: &dyn Body, whole: &str, items: &mut Vec<Item>) -> Result<Value, Error> {
+    let start = items.len();
+    export(body, items)?;
+    let unnamed = items[start..]
+        .iter()
+        .filter(|item| !item.fields.iter().any(|f| f.name == "name"))
+        .count();
+    if unnamed != 1 {
+        return Err(Error::without_position(&format!(
+            "{:?} can not be exported as part of a {}",
+            body, whole
+        )));
+    }
+    let name = format!("part{}", items.len());
+    let item = items.pop().unwrap();
+    items.push(item.with_name(&name));
+    Ok(Value::quoted(&name))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Csg, Operation};
+    use crate::bodies::{Body, BodyProps, Cube, HitRecord, Sphere, Texture};
+    use crate::point::Point;
+    use crate::raytracer::Ray;
+    use std::sync::Arc;
+
+    fn crossings(body: &dyn Body, ray: &Ray) -> Vec<(f64, bool)> {
+        let mut crossings = vec![];
+        body.crossings(ray, 0.001, &mut crossings);
+        crossings.iter().map(|c| (c.t, c.front_face)).collect()
+    }
+
+    #[test]
+    fn test_operations() {
+        let red = BodyProps::matte(Texture::new_color(1., 0., 0.));
+        let blue = BodyProps::matte(Texture::new_color(0., 0., 1.));
+        let cube: Arc<dyn Body> = Arc::new(Cube::new(
+            Point::new(-1., -1., -1.),
+            Point::new(1., 1., 1.),
+            red,
+        ));
+        let sphere: Arc<dyn Body> = Arc::new(Sphere::new(Point::new(1., 0., 0.), 1., blue));
+        // Along the ray the cube spans 4 to 6 and the sphere 5 to 7.
+        let ray = Ray::new(Point::new(-5., 0., 0.), Point::new(1., 0., 0.));
+        let close = |found: Vec<(f64, bool)>, expected: &[(f64, bool)]| {
+            assert_eq!(found.len(), expected.len(), "{:?}", found);
+            for ((t, front), (e, e_front)) in found.into_iter().zip(expected) {
+                assert!((t - e).abs() < 1e-9 && front == *e_front);
+            }
+        };
+
+        let union = Csg::new(Operation::Union, cube.clone(), sphere.clone());
+        close(crossings(&union, &ray), &[(4., true), (7., false)]);
+        let intersection = Csg::new(Operation::Intersection, cube.clone(), sphere.clone());
+        close(crossings(&intersection, &ray), &[(5., true), (6., false)]);
+        let difference = Csg::new(Operation::Difference, cube.clone(), sphere.clone());
+        close(crossings(&difference, &ray), &[(4., true), (5., false)]);
+
+        // Where the sphere was cut out, its surface faces into the hollow
+        // and keeps its material.
+        let back = Ray::new(Point::new(5., 0., 0.), Point::new(-1., 0., 0.));
+        let mut rec = HitRecord::default();
+        assert!(difference.hit(&back, 0.001, f64::INFINITY, &mut rec));
+        assert!((rec.t - 5.).abs() < 1e-9 && rec.front_face);
+        assert!((rec.normal - Point::new(1., 0., 0.)).length() < 1e-9);
+        assert_eq!(rec.body_props.texture().color(0., 0., rec.p).z, 1.);
+        assert!(!difference.hit(&back, 0.001, 4.9, &mut rec));
+
+        // Starting inside the cube, the way out is through the sphere.
+        let inside = Ray::new(Point::new(-0.5, 0., 0.), Point::new(1., 0., 0.));
+        close(crossings(&difference, &inside), &[(0.5, false)]);
+    }
+}
@@ -1,27 +1,51 @@
 use crate::bodies::bodyprops::BodyProps;
 use crate::bodies::collision::{Body, HitRecord, AABB};
-use crate::bodies::rect::{Axis, Rect};
+use crate::bodies::quad::Quad;
 use crate::point::Point;
 use crate::raytracer::Ray;
+use crate::scenefile::writer::Export;
+use crate::scenefile::{Error, Item};
 #[derive(Debug)]
 pub struct Cube {
-    min: Point,
-    max: Point,
-    sides: [Rect; 6],
+    corner: Point,
+    edges: [Point; 3],
+    body_props: BodyProps,
+    sides: [Quad; 6],
 }
 
 impl Cube {
     pub fn new(p0: Point, p1: Point, body_props: BodyProps) -> Cube {
+        let size = p1 - p0;
+        let edges = [
+            Point::new(size.x, 0., 0.),
+            Point::new(0., size.y, 0.),
+            Point::new(0., 0., size.z),
+        ];
+        Cube::from_edges(p0, edges, body_props)
+    }
+    // The box spanned by three edges from `corner`, which need not be along
+    // the axes or even at right angles.
+    pub fn from_edges(corner: Point, edges: [Point; 3], body_props: BodyProps) -> Cube {
+        let [a, b, c] = edges;
+        let center = corner + (a + b + c) / 2.;
+        // Sides spanned by `u` and `v`, with their normal turned outwards.
+        let side = |corner: Point, u: Point, v: Point| {
+            if (corner - center).dot(u.cross(v)) < 0. {
+                return Quad::new(corner, v, u, body_props);
+            }
+            Quad::new(corner, u, v, body_props)
+        };
         Cube {
-            min: p0,
-            max: p1,
+            corner,
+            edges,
+            body_props,
             sides: [
-                Rect::new(p0.x, p1.x, p0.y, p1.y, p1.z, Axis::XY, body_props),
-                Rect::new(p0.x, p1.x, p0.y, p1.y, p0.z, Axis::XY, body_props),
-                Rect::new(p0.x, p1.x, p0.z, p1.z, p1.y, Axis::XZ, body_props),
-                Rect::new(p0.x, p1.x, p0.z, p1.z, p0.y, Axis::XZ, body_props),
-                Rect::new(p0.y, p1.y, p0.z, p1.z, p1.x, Axis::YZ, body_props),
-                Rect::new(p0.y, p1.y, p0.z, p1.z, p0.x, Axis::YZ, body_props),
+                side(corner, a, b),
+                side(corner + c, a, b),
+                side(corner, a, c),
+                side(corner + b, a, c),
+                side(corner, b, c),
+                side(corner + a, b, c),
             ],
         }
     }
@@ -42,9 +66,29 @@ impl Body for Cube {
                 closest_so_for = rec.t;
             }
         }
-        return hit_anything;
+        hit_anything
     }
     fn bounding_box(&self) -> AABB {
-        AABB::new(self.min, self.max)
+        let [a, b, c] = self.edges;
+        let corners = [Point::default(), a, b, c, a + b, a + c, b + c, a + b + c];
+        AABB::from_points(&corners.map(|offset| self.corner + offset))
+    }
+}
+
+impl Export for Cube {
+    fn to_items(&self, items: &mut Vec<Item>) -> Result<(), Error> {
+        let [a, b, c] = self.edges;
+        let along_axes = a.y == 0. && a.z == 0. && b.x == 0. && b.z == 0. && c.x == 0. && c.y == 0.;
+        let item = if along_axes {
+            Item::new("cube")
+                .field("min", self.corner)
+                .field("max", self.corner + a + b + c)
+        } else {
+            Item::new("cube")
+                .field("corner", self.corner)
+                .field("edges", self.edges)
+        };
+        items.push(item.body_props(&self.body_props));
+        Ok(())
     }
 }
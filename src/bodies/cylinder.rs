@@ -0,0 +1,58 @@
+use crate::bodies::bodyprops::BodyProps;
+use crate::bodies::collision::{Body, HitRecord, AABB};
+use crate::bodies::cone::{Caps, Cone};
+use crate::point::Point;
+use crate::raytracer::Ray;
+use crate::scenefile::writer::Export;
+use crate::scenefile::{Error, Item};
+
+// A cone that is equally wide at both ends.
+#[derive(Debug)]
+pub struct Cylinder {
+    base: Point,
+    top: Point,
+    radius: f64,
+    body_props: BodyProps,
+    cone: Cone,
+}
+
+impl Cylinder {
+    pub fn new(base: Point, top: Point, radius: f64, body_props: BodyProps) -> Cylinder {
+        Cylinder {
+            base,
+            top,
+            radius,
+            body_props,
+            cone: Cone::new(base, top, radius, radius, body_props),
+        }
+    }
+    pub fn with_caps(self, caps: Caps) -> Self {
+        Cylinder {
+            cone: self.cone.with_caps(caps),
+            ..self
+        }
+    }
+}
+
+impl Body for Cylinder {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        self.cone.hit(ray, t_min, t_max, rec)
+    }
+    fn bounding_box(&self) -> AABB {
+        self.cone.bounding_box()
+    }
+}
+
+impl Export for Cylinder {
+    fn to_items(&self, items: &mut Vec<Item>) -> Result<(), Error> {
+        items.push(
+            Item::new("cylinder")
+                .field("base", self.base)
+                .field("top", self.top)
+                .field("radius", self.radius)
+                .field("caps", self.cone.caps())
+                .body_props(&self.body_props),
+        );
+        Ok(())
+    }
+}
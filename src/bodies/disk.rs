@@ -0,0 +1,118 @@
+use std::f64::consts::PI;
+
+use crate::bodies::bodyprops::BodyProps;
+use crate::bodies::collision::{Body, HitRecord, AABB};
+use crate::point::Point;
+use crate::raytracer::Ray;
+use crate::scenefile::writer::Export;
+use crate::scenefile::{Error, Item};
+
+#[derive(Debug)]
+pub struct Disk {
+    center: Point,
+    normal: Point,
+    radius: f64,
+    // Directions in the plane of the disk, for texture coordinates.
+    tangent: Point,
+    bitangent: Point,
+    body_props: BodyProps,
+}
+
+impl Disk {
+    pub fn new(center: Point, normal: Point, radius: f64, body_props: BodyProps) -> Disk {
+        let normal = normal.unit_vector();
+        let other = if normal.x.abs() > 0.9 {
+            Point::new(0., 1., 0.)
+        } else {
+            Point::new(1., 0., 0.)
+        };
+        let tangent = normal.cross(other).unit_vector();
+        Disk {
+            center,
+            normal,
+            radius,
+            tangent,
+            bitangent: normal.cross(tangent),
+            body_props,
+        }
+    }
+}
+
+impl Body for Disk {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let denominator = self.normal.dot(ray.direction);
+        if denominator.abs() < 1e-12 {
+            return false;
+        }
+        let t = self.normal.dot(self.center - ray.origin) / denominator;
+        if t < t_min || t > t_max {
+            return false;
+        }
+        let p = ray.at(t);
+        let offset = p - self.center;
+        if offset.length_squared() > self.radius * self.radius {
+            return false;
+        }
+
+        rec.t = t;
+        rec.p = p;
+        // The angle around the centre and the distance from it.
+        let angle = offset.dot(self.bitangent).atan2(offset.dot(self.tangent));
+        rec.u = angle / (2. * PI) + 0.5;
+        rec.v = offset.length() / self.radius;
+        rec.set_face_normal(ray, self.normal);
+        rec.body_props = self.body_props;
+        true
+    }
+
+    fn bounding_box(&self) -> AABB {
+        // Along each axis the rim reaches out by the radius times the sine of
+        // the angle between the axis and the normal.
+        let n = self.normal;
+        let extent = |n: f64| self.radius * (1. - n * n).max(0.).sqrt();
+        let extent = Point::new(extent(n.x), extent(n.y), extent(n.z));
+        AABB::from_points(&[self.center - extent, self.center + extent])
+    }
+}
+
+impl Export for Disk {
+    fn to_items(&self, items: &mut Vec<Item>) -> Result<(), Error> {
+        items.push(
+            Item::new("disk")
+                .field("center", self.center)
+                .field("normal", self.normal)
+                .field("radius", self.radius)
+                .body_props(&self.body_props),
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Disk;
+    use crate::bodies::{Body, BodyProps, HitRecord};
+    use crate::point::Point;
+    use crate::raytracer::Ray;
+
+    #[test]
+    fn test_disk() {
+        let normal = Point::new(1., 1., 0.).unit_vector();
+        let disk = Disk::new(Point::new(1., 2., 3.), normal, 2., BodyProps::null());
+        let ray = Ray::new(Point::new(5., 6., 3.), Point::new(-1., -1., 0.));
+        let mut rec = HitRecord::default();
+        assert!(disk.hit(&ray, 0.001, f64::INFINITY, &mut rec));
+        assert!((rec.p - Point::new(1., 2., 3.)).length() < 1e-12);
+        assert!(rec.front_face);
+        assert!((rec.normal - normal).length() < 1e-12);
+        assert!(rec.v.abs() < 1e-12);
+
+        let off_rim = Ray::new(Point::new(5., 6., 5.1), Point::new(-1., -1., 0.));
+        assert!(!disk.hit(&off_rim, 0.001, f64::INFINITY, &mut rec));
+
+        let bbox = disk.bounding_box();
+        let reach = 2. * 0.5f64.sqrt();
+        assert!((bbox.max.x - 1. - reach).abs() < 1e-12);
+        assert!((bbox.max.z - 5.).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,141 @@
+use crate::bodies::bodyprops::BodyProps;
+use crate::bodies::collision::{Body, HitRecord, AABB};
+use crate::point::Point;
+use crate::raytracer::Ray;
+use crate::scenefile::writer::Export;
+use crate::scenefile::{Error, Item};
+use crate::sdf::Sdf;
+
+// A shape given by its distance, found by stepping along the ray as far as
+// the distance allows. The shape has to fit in `aabb`, and it is missed when
+// the surface is not reached within `steps` steps. Hits are closer than
+// `epsilon` to the surface.
+#[derive(Debug)]
+pub struct DistanceField {
+    sdf: Box<dyn Sdf>,
+    aabb: AABB,
+    steps: usize,
+    epsilon: f64,
+    body_props: BodyProps,
+}
+
+impl DistanceField {
+    pub fn new(sdf: Box<dyn Sdf>, aabb: AABB, body_props: BodyProps) -> Self {
+        DistanceField {
+            sdf,
+            aabb,
+            steps: 256,
+            epsilon: 0.0001,
+            body_props,
+        }
+    }
+    pub fn with_steps(self, steps: usize) -> Self {
+        DistanceField { steps, ..self }
+    }
+    pub fn with_epsilon(self, epsilon: f64) -> Self {
+        DistanceField { epsilon, ..self }
+    }
+
+    fn normal(&self, p: Point) -> Point {
+        let h = self.epsilon;
+        let slope = |offset: Point| self.sdf.distance(p + offset) - self.sdf.distance(p - offset);
+        Point::new(
+            slope(Point::new(h, 0., 0.)),
+            slope(Point::new(0., h, 0.)),
+            slope(Point::new(0., 0., h)),
+        )
+        .unit_vector()
+    }
+}
+
+impl Body for DistanceField {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let Some((start, end)) = self.aabb.interval(ray, t_min, t_max) else {
+            return false;
+        };
+        let length = ray.direction.length();
+        let mut t = start;
+        for step in 0..self.steps {
+            // Inside the shape the distance is negative, and the ray steps
+            // towards the way out.
+            let distance = self.sdf.distance(ray.at(t)).abs();
+            if distance < self.epsilon && step > 0 {
+                rec.t = t;
+                rec.p = ray.at(t);
+                rec.u = 0.;
+                rec.v = 0.;
+                rec.set_face_normal(ray, self.normal(rec.p));
+                rec.body_props = self.body_props;
+                return true;
+            }
+            t += distance.max(self.epsilon) / length;
+            if t > end {
+                break;
+            }
+        }
+        false
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.aabb
+    }
+}
+
+impl Export for DistanceField {
+    fn to_items(&self, items: &mut Vec<Item>) -> Result<(), Error> {
+        items.push(
+            Item::new("sdf")
+                .field("shape", self.sdf.to_value())
+                .field("min", self.aabb.min)
+                .field("max", self.aabb.max)
+                .field("steps", self.steps)
+                .field("epsilon", self.epsilon)
+                .body_props(&self.body_props),
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DistanceField;
+    use crate::bodies::collision::AABB;
+    use crate::bodies::{Body, BodyProps, HitRecord, Sphere};
+    use crate::point::Point;
+    use crate::raytracer::Ray;
+    use crate::sdf;
+
+    #[test]
+    fn test_matches_sphere() {
+        let center = Point::new(0.5, 0., -1.);
+        let shape = sdf::Translate {
+            offset: center,
+            shape: Box::new(sdf::Sphere { radius: 1. }),
+        };
+        let bounds = AABB::new(
+            center - Point::new(1., 1., 1.),
+            center + Point::new(1., 1., 1.),
+        );
+        let field = DistanceField::new(Box::new(shape), bounds, BodyProps::null());
+        let sphere = Sphere::new(center, 1., BodyProps::null());
+        for (origin, direction) in [
+            (Point::new(0., 0., 5.), Point::new(0.1, 0.05, -1.)),
+            (Point::new(3., 2., 0.), Point::new(-1., -0.8, -0.3)),
+            // From inside to the far side.
+            (Point::new(0.5, 0., -1.), Point::new(0., 2., 0.)),
+        ] {
+            let ray = Ray::new(origin, direction);
+            // The first crossing of the sphere, which unlike its hit is also
+            // found from inside.
+            let mut crossings = vec![];
+            sphere.crossings(&ray, 0.001, &mut crossings);
+            let (expected, mut rec) = (crossings[0], HitRecord::default());
+            assert!(field.hit(&ray, 0.001, f64::INFINITY, &mut rec));
+            assert!((rec.p - expected.p).length() < 1e-3);
+            assert!((rec.normal - expected.normal).length() < 1e-3);
+            assert_eq!(rec.front_face, expected.front_face);
+        }
+        let miss = Ray::new(Point::new(0., 2., 5.), Point::new(0., 0., -1.));
+        assert!(!field.hit(&miss, 0.001, f64::INFINITY, &mut HitRecord::default()));
+    }
+}
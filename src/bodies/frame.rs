@@ -0,0 +1,70 @@
+use std::f64::consts::PI;
+
+use crate::bodies::collision::HitRecord;
+use crate::point::Point;
+use crate::raytracer::Ray;
+
+// Coordinates around an axis, with z along the axis from `origin`.
+#[derive(Debug, Clone, Copy)]
+pub struct Frame {
+    origin: Point,
+    u: Point,
+    v: Point,
+    w: Point,
+}
+
+impl Frame {
+    pub fn new(origin: Point, axis: Point) -> Frame {
+        let w = axis.unit_vector();
+        let other = if w.x.abs() > 0.9 {
+            Point::new(0., 1., 0.)
+        } else {
+            Point::new(1., 0., 0.)
+        };
+        let u = w.cross(other).unit_vector();
+        Frame {
+            origin,
+            u,
+            v: w.cross(u),
+            w,
+        }
+    }
+    pub fn axis(&self) -> Point {
+        self.w
+    }
+    pub fn ray_to_local(&self, ray: &Ray) -> (Point, Point) {
+        let local = |p: Point| Point::new(p.dot(self.u), p.dot(self.v), p.dot(self.w));
+        (local(ray.origin - self.origin), local(ray.direction))
+    }
+    pub fn direction_to_world(&self, direction: Point) -> Point {
+        direction.x * self.u + direction.y * self.v + direction.z * self.w
+    }
+    // Around the axis from 0 to 1, starting and ending on -u.
+    pub fn angle(p: Point) -> f64 {
+        p.y.atan2(p.x) / (2. * PI) + 0.5
+    }
+}
+
+// Fills in a hit found in the local coordinates of `frame`.
+pub fn set_hit(
+    rec: &mut HitRecord,
+    ray: &Ray,
+    frame: &Frame,
+    t: f64,
+    local_normal: Point,
+    (u, v): (f64, f64),
+) {
+    rec.t = t;
+    rec.p = ray.at(t);
+    rec.u = u;
+    rec.v = v;
+    rec.set_face_normal(ray, frame.direction_to_world(local_normal).unit_vector());
+}
+
+// The corners of the box around a circle of `radius` at `center`, which faces
+// along `normal`.
+pub fn circle_extent(center: Point, normal: Point, radius: f64) -> [Point; 2] {
+    let extent = |n: f64| radius * (1. - n * n).max(0.).sqrt();
+    let extent = Point::new(extent(normal.x), extent(normal.y), extent(normal.z));
+    [center - extent, center + extent]
+}
@@ -0,0 +1,333 @@
+use crate::bodies::bodyprops::BodyProps;
+use crate::bodies::collision::{Body, HitRecord, AABB};
+use crate::bodies::triangle;
+use crate::noise::Noise;
+use crate::point::Point;
+use crate::raytracer::Ray;
+use crate::scenefile::writer::Export;
+use crate::scenefile::{Error, Item, Value};
+use std::path::PathBuf;
+
+// Heights from 0 to 1 on a grid of `columns` by `rows` samples.
+#[derive(Debug, Clone)]
+pub struct Grid {
+    columns: usize,
+    rows: usize,
+    heights: Vec<f64>,
+}
+
+impl Grid {
+    pub fn new(columns: usize, rows: usize, heights: Vec<f64>) -> Self {
+        assert!(
+            columns >= 2 && rows >= 2,
+            "a grid needs at least 2 by 2 samples"
+        );
+        assert_eq!(heights.len(), columns * rows);
+        Grid {
+            columns,
+            rows,
+            heights,
+        }
+    }
+    // `resolution` samples along each side of the unit square.
+    pub fn from_noise(noise: &Noise, resolution: usize) -> Self {
+        let side = (resolution - 1) as f64;
+        let heights = (0..resolution * resolution)
+            .map(|i| {
+                noise.value(
+                    (i % resolution) as f64 / side,
+                    (i / resolution) as f64 / side,
+                )
+            })
+            .collect();
+        Grid::new(resolution, resolution, heights)
+    }
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+    pub fn height(&self, column: usize, row: usize) -> f64 {
+        self.heights[row * self.columns + column]
+    }
+}
+
+// Where the heights came from, to write them back to a scene file.
+#[derive(Debug, Clone)]
+pub enum Source {
+    Image(PathBuf),
+    Noise(Noise, usize),
+}
+
+// A terrain over the rectangle from `corner` that is `size.x` wide along x
+// and `size.z` deep along z, with heights from `corner.y` up to
+// `corner.y + size.y`. Columns of the grid run along x and rows along z.
+// Each cell is made of two triangles with normals interpolated between the
+// samples, and rays step through the cells in order.
+#[derive(Debug)]
+pub struct Heightfield {
+    grid: Grid,
+    corner: Point,
+    size: Point,
+    normals: Vec<Point>,
+    // The lowest and highest point of each cell.
+    ranges: Vec<(f64, f64)>,
+    aabb: AABB,
+    source: Option<Source>,
+    body_props: BodyProps,
+}
+
+impl Heightfield {
+    pub fn new(grid: Grid, corner: Point, size: Point, body_props: BodyProps) -> Self {
+        let (columns, rows) = (grid.columns(), grid.rows());
+        let mut field = Heightfield {
+            grid,
+            corner,
+            size,
+            normals: vec![],
+            ranges: vec![],
+            aabb: AABB::new(corner, corner + size),
+            source: None,
+            body_props,
+        };
+        for row in 0..rows {
+            for column in 0..columns {
+                // The slopes between the neighbouring samples.
+                let (left, right) = (column.saturating_sub(1), (column + 1).min(columns - 1));
+                let (back, front) = (row.saturating_sub(1), (row + 1).min(rows - 1));
+                let along_x = field.vertex(right, row) - field.vertex(left, row);
+                let along_z = field.vertex(column, front) - field.vertex(column, back);
+                field.normals.push(along_z.cross(along_x).unit_vector());
+            }
+        }
+        let mut lowest = f64::INFINITY;
+        let mut highest = f64::NEG_INFINITY;
+        for row in 0..rows - 1 {
+            for column in 0..columns - 1 {
+                let ys = field.cell(column, row).map(|v| v.y);
+                let low = ys.iter().fold(f64::INFINITY, |a, &b| a.min(b));
+                let high = ys.iter().fold(f64::NEG_INFINITY, |a, &b| a.max(b));
+                field.ranges.push((low, high));
+                lowest = lowest.min(low);
+                highest = highest.max(high);
+            }
+        }
+        let far = corner + size;
+        field.aabb = AABB::from_points(&[
+            Point::new(corner.x, lowest, corner.z),
+            Point::new(far.x, highest, far.z),
+        ]);
+        field
+    }
+    pub fn with_source(self, source: Source) -> Self {
+        Heightfield {
+            source: Some(source),
+            ..self
+        }
+    }
+
+    fn vertex(&self, column: usize, row: usize) -> Point {
+        let (u, v) = self.uv(column, row);
+        Point::new(
+            self.corner.x + self.size.x * u,
+            self.corner.y + self.size.y * self.grid.height(column, row),
+            self.corner.z + self.size.z * v,
+        )
+    }
+    fn uv(&self, column: usize, row: usize) -> (f64, f64) {
+        (
+            column as f64 / (self.grid.columns - 1) as f64,
+            row as f64 / (self.grid.rows - 1) as f64,
+        )
+    }
+    // The corners of a cell, in the order of `TRIANGLES`.
+    fn cell(&self, column: usize, row: usize) -> [Point; 4] {
+        [
+            self.vertex(column, row),
+            self.vertex(column, row + 1),
+            self.vertex(column + 1, row),
+            self.vertex(column + 1, row + 1),
+        ]
+    }
+
+    // Tries the two triangles of a cell and keeps the nearer hit.
+    fn hit_cell(
+        &self,
+        (column, row): (usize, usize),
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+    ) -> bool {
+        let offsets = [(0, 0), (0, 1), (1, 0), (1, 1)];
+        let corners = offsets.map(|(c, r)| (column + c, row + r));
+        let mut closest = t_max;
+        let mut found = None;
+        for triangle in TRIANGLES {
+            let [a, b, c] = triangle.map(|i| corners[i]);
+            let vertices = [a, b, c].map(|(c, r)| self.vertex(c, r));
+            if let Some(hit) = triangle::intersect(vertices, ray, t_min, closest) {
+                closest = hit.t;
+                found = Some((hit, vertices, [a, b, c]));
+            }
+        }
+        let Some((hit, vertices, corners)) = found else {
+            return false;
+        };
+        let normals = corners.map(|(c, r)| self.normals[r * self.grid.columns + c]);
+        let uvs = corners.map(|(c, r)| self.uv(c, r));
+        triangle::set_hit(rec, ray, hit, vertices, Some(normals), Some(uvs));
+        rec.body_props = self.body_props;
+        true
+    }
+}
+
+// The corners of the two triangles of a cell, facing up.
+const TRIANGLES: [[usize; 3]; 2] = [[0, 1, 2], [2, 1, 3]];
+
+impl Body for Heightfield {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let Some((start, end)) = self.aabb.interval(ray, t_min, t_max) else {
+            return false;
+        };
+        let (columns, rows) = (self.grid.columns as i64, self.grid.rows as i64);
+        let width = self.size.x / (columns - 1) as f64;
+        let depth = self.size.z / (rows - 1) as f64;
+        let p = ray.at(start);
+        let mut column = (((p.x - self.corner.x) / width).floor() as i64).clamp(0, columns - 2);
+        let mut row = (((p.z - self.corner.z) / depth).floor() as i64).clamp(0, rows - 2);
+
+        // Where the ray crosses into the next column or row, and how far
+        // apart those crossings are.
+        let crossing = |origin: f64, direction: f64, start: f64, size: f64, index: i64| {
+            if direction == 0. {
+                return (0, f64::INFINITY, f64::INFINITY);
+            }
+            let step = direction.signum() as i64;
+            let edge = start + size * (index + (step + 1) / 2) as f64;
+            (step, (edge - origin) / direction, size / direction.abs())
+        };
+        let (o, d) = (ray.origin, ray.direction);
+        let (step_x, mut next_x, delta_x) = crossing(o.x, d.x, self.corner.x, width, column);
+        let (step_z, mut next_z, delta_z) = crossing(o.z, d.z, self.corner.z, depth, row);
+
+        let mut enter = start;
+        loop {
+            let exit = next_x.min(next_z).min(end);
+            let (y0, y1) = (ray.at(enter).y, ray.at(exit).y);
+            let (low, high) = self.ranges[(row * (columns - 1) + column) as usize];
+            let cell = (column as usize, row as usize);
+            if y0.max(y1) >= low
+                && y0.min(y1) <= high
+                && self.hit_cell(cell, ray, t_min, t_max, rec)
+            {
+                return true;
+            }
+            if exit >= end {
+                return false;
+            }
+            if next_x < next_z {
+                column += step_x;
+                next_x += delta_x;
+            } else {
+                row += step_z;
+                next_z += delta_z;
+            }
+            if column < 0 || row < 0 || column > columns - 2 || row > rows - 2 {
+                return false;
+            }
+            enter = exit;
+        }
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.aabb
+    }
+}
+
+impl Export for Heightfield {
+    fn to_items(&self, items: &mut Vec<Item>) -> Result<(), Error> {
+        let item = Item::new("heightfield");
+        let item = match &self.source {
+            Some(Source::Image(path)) => {
+                let path = path.to_str().ok_or_else(|| {
+                    Error::without_position(&format!("{} is not valid UTF-8", path.display()))
+                })?;
+                item.field("image", Value::quoted(path))
+            }
+            Some(Source::Noise(noise, resolution)) => item
+                .field(
+                    "noise",
+                    [noise.seed as f64, noise.frequency, noise.octaves as f64],
+                )
+                .field("resolution", *resolution),
+            None => {
+                return Err(Error::without_position(
+                    "a heightfield from a grid can not be exported",
+                ))
+            }
+        };
+        items.push(
+            item.field("corner", self.corner)
+                .field("size", self.size)
+                .body_props(&self.body_props),
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Grid, Heightfield};
+    use crate::bodies::{Body, BodyProps, HitRecord, Triangle};
+    use crate::point::Point;
+    use crate::random;
+    use crate::raytracer::Ray;
+    use rand::Rng;
+
+    #[test]
+    fn test_hits_match_triangles() {
+        random::seed(5);
+        let mut rng = random::rng();
+        let heights = (0..6 * 4).map(|_| rng.gen::<f64>()).collect();
+        let corner = Point::new(-3., -1., -2.);
+        let size = Point::new(6., 2., 4.);
+        let field = Heightfield::new(Grid::new(6, 4, heights), corner, size, BodyProps::null());
+        let mut triangles = vec![];
+        for row in 0..3 {
+            for column in 0..5 {
+                let [a, b, c, d] = field.cell(column, row);
+                triangles.push(Triangle::new(a, b, c, BodyProps::null()));
+                triangles.push(Triangle::new(c, b, d, BodyProps::null()));
+            }
+        }
+        for _ in 0..500 {
+            let origin = Point::new(
+                rng.gen_range(-5.0..5.0),
+                rng.gen_range(1.0..4.0),
+                rng.gen_range(-4.0..4.0),
+            );
+            let target = Point::new(rng.gen_range(-3.0..3.0), -1., rng.gen_range(-2.0..2.0));
+            let ray = Ray::new(origin, target - origin);
+            let mut expected = HitRecord::default();
+            let mut closest = f64::INFINITY;
+            for triangle in &triangles {
+                if triangle.hit(&ray, 0.001, closest, &mut expected) {
+                    closest = expected.t;
+                }
+            }
+            let mut rec = HitRecord::default();
+            assert_eq!(
+                field.hit(&ray, 0.001, f64::INFINITY, &mut rec),
+                closest.is_finite()
+            );
+            if closest.is_finite() {
+                assert!((rec.t - closest).abs() < 1e-9);
+                assert!(rec.normal.y > 0.);
+                assert!((rec.u - (rec.p.x + 3.) / 6.).abs() < 1e-9);
+                assert!((rec.v - (rec.p.z + 2.) / 4.).abs() < 1e-9);
+            }
+        }
+    }
+}
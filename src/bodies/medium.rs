@@ -0,0 +1,296 @@
+use std::sync::Arc;
+
+use crate::bodies::bodyprops::BodyProps;
+use crate::bodies::collision::{Body, HitRecord, AABB};
+use crate::bodies::csg::named_part;
+use crate::noise::Noise;
+use crate::point::Point;
+use crate::random;
+use crate::raytracer::Ray;
+use crate::scenefile::writer::Export;
+use crate::scenefile::{Error, Item, Value};
+use rand::Rng;
+
+// How likely a ray is to scatter at each point of a medium, per unit of
+// length.
+#[derive(Debug, Clone)]
+pub enum Density {
+    Constant(f64),
+    // Samples spread evenly over the box around the medium from corner to
+    // corner, with x changing fastest and z slowest, and blended in between.
+    Grid {
+        resolution: [usize; 3],
+        values: Vec<f64>,
+    },
+    // Puffs where noise rises above its middle, up to `density`.
+    Noise(Noise, f64),
+}
+
+impl Density {
+    pub fn at(&self, p: Point, aabb: &AABB) -> f64 {
+        match self {
+            Density::Constant(density) => *density,
+            Density::Noise(noise, density) => density * (2. * noise.value_at(p) - 1.).max(0.),
+            Density::Grid { resolution, values } => {
+                let [nx, ny, nz] = *resolution;
+                let size = aabb.max - aabb.min;
+                // The sample before `p` along an axis, and how far along to the next.
+                let locate = |x: f64, min: f64, size: f64, n: usize| {
+                    let x = ((x - min) / size * (n - 1) as f64).clamp(0., (n - 1) as f64);
+                    let i = (x.floor() as usize).min(n - 2);
+                    (i, x - i as f64)
+                };
+                let (i, fx) = locate(p.x, aabb.min.x, size.x, nx);
+                let (j, fy) = locate(p.y, aabb.min.y, size.y, ny);
+                let (k, fz) = locate(p.z, aabb.min.z, size.z, nz);
+                let value = |di, dj, dk| values[((k + dk) * ny + j + dj) * nx + i + di];
+                let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+                let layer = |dk| {
+                    lerp(
+                        lerp(value(0, 0, dk), value(1, 0, dk), fx),
+                        lerp(value(0, 1, dk), value(1, 1, dk), fx),
+                        fy,
+                    )
+                };
+                lerp(layer(0), layer(1), fz)
+            }
+        }
+    }
+    // No point is denser than this.
+    pub fn majorant(&self) -> f64 {
+        match self {
+            Density::Constant(density) | Density::Noise(_, density) => *density,
+            Density::Grid { values, .. } => values.iter().fold(0., |m: f64, v| m.max(*v)),
+        }
+    }
+    pub fn to_value(&self) -> Value {
+        match self {
+            Density::Constant(density) => (*density).into(),
+            Density::Noise(noise, density) => Value::called(
+                "noise",
+                vec![
+                    (noise.seed as f64).into(),
+                    noise.frequency.into(),
+                    noise.octaves.into(),
+                    (*density).into(),
+                ],
+            ),
+            Density::Grid { resolution, values } => Value::called(
+                "grid",
+                vec![
+                    (*resolution).into(),
+                    Value::list(values.iter().map(|v| (*v).into()).collect()),
+                ],
+            ),
+        }
+    }
+}
+
+// Fog or smoke that fills a closed `boundary`. Rays scatter inside it with
+// the phase function of its material, which should be a volume.
+//
+// Each stretch of the ray inside the boundary is walked by delta tracking:
+// steps are drawn as if the whole medium were as dense as its densest point,
+// and each step scatters with the chance that the density there is of that.
+// With a constant density every step scatters.
+#[derive(Debug)]
+pub struct Medium {
+    boundary: Arc<dyn Body>,
+    density: Density,
+    majorant: f64,
+    body_props: BodyProps,
+}
+
+impl Medium {
+    pub fn new(boundary: Arc<dyn Body>, density: Density, body_props: BodyProps) -> Self {
+        Medium {
+            boundary,
+            majorant: density.majorant(),
+            density,
+            body_props,
+        }
+    }
+
+    // Where the ray first scatters between `start` and `end`, if it does.
+    fn track(&self, ray: &Ray, start: f64, end: f64, aabb: &AABB) -> Option<f64> {
+        let speed = ray.direction.length();
+        let mut rng = random::rng();
+        let mut t = start;
+        loop {
+            t -= (1. - rng.gen::<f64>()).ln() / (self.majorant * speed);
+            if t >= end {
+                return None;
+            }
+            if rng.gen::<f64>() * self.majorant < self.density.at(ray.at(t), aabb) {
+                return Some(t);
+            }
+        }
+    }
+}
+
+impl Body for Medium {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let aabb = self.boundary.bounding_box();
+        if self.majorant <= 0. || !aabb.hit(ray, t_min, t_max) {
+            return false;
+        }
+        let mut crossings = vec![];
+        self.boundary.crossings(ray, t_min, &mut crossings);
+
+        // The ray starts inside when it leaves the boundary first.
+        let mut enter = match crossings.first() {
+            Some(crossing) if !crossing.front_face => Some(t_min),
+            _ => None,
+        };
+        for crossing in &crossings {
+            if crossing.t > t_max {
+                break;
+            }
+            match (crossing.front_face, enter) {
+                (true, _) => enter = Some(crossing.t),
+                (false, Some(start)) => {
+                    enter = None;
+                    if let Some(t) = self.track(ray, start, crossing.t, &aabb) {
+                        set_hit(rec, ray, t, self.body_props);
+                        return true;
+                    }
+                }
+                (false, None) => {}
+            }
+        }
+        // Still inside where the search ends.
+        if let Some(start) = enter.filter(|_| t_max.is_finite()) {
+            if let Some(t) = self.track(ray, start, t_max, &aabb) {
+                set_hit(rec, ray, t, self.body_props);
+                return true;
+            }
+        }
+        false
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.boundary.bounding_box()
+    }
+}
+
+impl Export for Medium {
+    fn to_items(&self, items: &mut Vec<Item>) -> Result<(), Error> {
+        let boundary = named_part(self.boundary.as_ref(), "medium", items)?;
+        items.push(
+            Item::new("medium")
+                .field("boundary", boundary)
+                .field("density", self.density.to_value())
+                .body_props(&self.body_props),
+        );
+        Ok(())
+    }
+}
+
+// A point inside a medium has no surface, so the normal just faces the ray.
+fn set_hit(rec: &mut HitRecord, ray: &Ray, t: f64, body_props: BodyProps) {
+    rec.t = t;
+    rec.p = ray.at(t);
+    rec.u = 0.;
+    rec.v = 0.;
+    rec.normal = -ray.direction.unit_vector();
+    rec.front_face = true;
+    rec.body_props = body_props;
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Density, Medium};
+    use crate::bodies::bodyprops::{Material, Phase};
+    use crate::bodies::{Body, BodyProps, Cube, HitRecord, Sphere, Texture};
+    use crate::noise::Noise;
+    use crate::point::Point;
+    use crate::random;
+    use crate::raytracer::Ray;
+    use std::sync::Arc;
+
+    // How many of `count` rays get through without scattering.
+    fn passing(medium: &Medium, ray: &Ray, count: usize) -> f64 {
+        let mut rec = HitRecord::default();
+        let passed = (0..count)
+            .filter(|_| !medium.hit(ray, 0.001, f64::INFINITY, &mut rec))
+            .count();
+        passed as f64 / count as f64
+    }
+
+    #[test]
+    fn test_transmittance() {
+        random::seed(11);
+        let props = BodyProps::new(
+            Texture::new_color(1., 1., 1.),
+            Material::Volume(Phase::Isotropic),
+        );
+        let cube: Arc<dyn Body> = Arc::new(Cube::new(
+            Point::new(0., 0., 0.),
+            Point::new(1., 1., 1.),
+            props,
+        ));
+        let along = Ray::new(Point::new(-1., 0.5, 0.5), Point::new(2., 0., 0.));
+
+        // Through a constant density the light falls off exponentially.
+        let fog = Medium::new(cube.clone(), Density::Constant(0.7), props);
+        assert!((passing(&fog, &along, 20000) - (-0.7f64).exp()).abs() < 0.01);
+        // Nothing scatters before the ray reaches the boundary.
+        let mut rec = HitRecord::default();
+        assert!(!fog.hit(&along, 0.001, 0.5, &mut rec));
+
+        // From 0 to 2 along x, which is 1 on average.
+        let resolution = [2, 2, 2];
+        let values = vec![0., 2., 0., 2., 0., 2., 0., 2.];
+        let ramp = Medium::new(cube.clone(), Density::Grid { resolution, values }, props);
+        assert!(
+            (ramp
+                .density
+                .at(Point::new(0.25, 0.5, 0.5), &cube.bounding_box())
+                - 0.5)
+                .abs()
+                < 1e-12
+        );
+        assert!((passing(&ramp, &along, 20000) - (-1f64).exp()).abs() < 0.01);
+        // Along y at x = 0.25 it is 0.5 throughout.
+        let up = Ray::new(Point::new(0.25, -1., 0.5), Point::new(0., 1., 0.));
+        assert!((passing(&ramp, &up, 20000) - (-0.5f64).exp()).abs() < 0.01);
+
+        // A ray that starts inside only goes through the rest, and scatters
+        // inside the boundary.
+        let inside = Ray::new(Point::new(0.5, 0.5, 0.5), Point::new(1., 0., 0.));
+        assert!((passing(&fog, &inside, 20000) - (-0.35f64).exp()).abs() < 0.01);
+        for _ in 0..100 {
+            if fog.hit(&inside, 0.001, f64::INFINITY, &mut rec) {
+                assert!(rec.p.x > 0.5 && rec.p.x < 1.);
+            }
+        }
+
+        // The noise is never denser than its scale, so rays pass at least as
+        // often as through that constant density.
+        let noise = Density::Noise(Noise::new(3, 4., 3), 2.);
+        let smoke = Medium::new(
+            Arc::new(Sphere::new(Point::new(0., 0., 0.), 1., props)),
+            noise,
+            props,
+        );
+        let through = Ray::new(Point::new(-2., 0., 0.), Point::new(1., 0., 0.));
+        let passed = passing(&smoke, &through, 20000);
+        assert!(passed > (-4f64).exp() && passed < 0.99);
+    }
+
+    #[test]
+    fn test_phase() {
+        random::seed(12);
+        let direction = Point::new(0., 0., 2.);
+        for g in [0., 0.6, -0.3] {
+            let phase = match g {
+                0. => Phase::Isotropic,
+                g => Phase::HenyeyGreenstein(g),
+            };
+            // The average cosine of the turn is `g`.
+            let cosines = (0..20000).map(|_| phase.sample(direction).unit_vector().z);
+            let mean = cosines.sum::<f64>() / 20000.;
+            assert!((mean - g).abs() < 0.02, "{} != {}", mean, g);
+        }
+    }
+}
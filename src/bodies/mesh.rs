@@ -0,0 +1,300 @@
+use crate::bodies::bodyprops::BodyProps;
+use crate::bodies::collision::{Body, HitRecord, AABB};
+use crate::bodies::texture::Texture;
+use crate::bodies::triangle::{self, Intersection};
+use crate::ordered_float::OrderedFloat;
+use crate::point::Point;
+use crate::raytracer::Ray;
+use crate::scenefile::writer::Export;
+use crate::scenefile::{Error, Item, Value};
+
+// Triangles per leaf of the mesh hierarchy.
+const LEAF_SIZE: usize = 4;
+
+// A node of the mesh hierarchy. Leaves hold `count` triangles from `start`,
+// inner nodes have a `count` of zero, their first child right after them and
+// their second child at `start`.
+#[derive(Debug)]
+struct Node {
+    aabb: AABB,
+    start: u32,
+    count: u32,
+}
+
+// Triangles sharing vertex arrays and body properties. The mesh keeps its
+// own hierarchy of boxes over the triangles, so that it is a single body to
+// the world `BVH` however many triangles it has.
+#[derive(Debug)]
+pub struct TriangleMesh {
+    positions: Vec<Point>,
+    normals: Option<Vec<Point>>,
+    uvs: Option<Vec<(f64, f64)>>,
+    colors: Option<Vec<Point>>,
+    indices: Vec<[u32; 3]>,
+    body_props: BodyProps,
+    nodes: Vec<Node>,
+}
+
+impl TriangleMesh {
+    // Every triple of `indices` is a triangle of `positions`.
+    pub fn new(positions: Vec<Point>, indices: Vec<[u32; 3]>, body_props: BodyProps) -> Self {
+        assert!(!indices.is_empty(), "got an empty mesh");
+        assert!(
+            indices
+                .iter()
+                .flatten()
+                .all(|&i| (i as usize) < positions.len()),
+            "mesh index out of range"
+        );
+        let mut mesh = TriangleMesh {
+            positions,
+            normals: None,
+            uvs: None,
+            colors: None,
+            indices,
+            body_props,
+            nodes: vec![],
+        };
+        mesh.build(0, mesh.indices.len());
+        mesh
+    }
+    // Shade with vertex normals, one for each position.
+    pub fn with_normals(self, normals: Vec<Point>) -> Self {
+        assert_eq!(normals.len(), self.positions.len());
+        TriangleMesh {
+            normals: Some(normals.into_iter().map(|n| n.unit_vector()).collect()),
+            ..self
+        }
+    }
+    // Texture coordinates, one for each position.
+    pub fn with_uvs(self, uvs: Vec<(f64, f64)>) -> Self {
+        assert_eq!(uvs.len(), self.positions.len());
+        TriangleMesh {
+            uvs: Some(uvs),
+            ..self
+        }
+    }
+    // Colours, one for each position, for a `Texture::VertexColor`.
+    pub fn with_colors(self, colors: Vec<Point>) -> Self {
+        assert_eq!(colors.len(), self.positions.len());
+        TriangleMesh {
+            colors: Some(colors),
+            ..self
+        }
+    }
+    pub fn body_props(&self) -> BodyProps {
+        self.body_props
+    }
+    fn vertices(&self, triangle: usize) -> [Point; 3] {
+        self.indices[triangle].map(|i| self.positions[i as usize])
+    }
+
+    // Adds the node for `count` triangles from `start`, sorting them so that
+    // each child covers a contiguous range.
+    fn build(&mut self, start: usize, count: usize) {
+        let aabb = (start..start + count)
+            .map(|i| AABB::from_points(&self.vertices(i)))
+            .reduce(|a, b| a.surrounding_box(b))
+            .unwrap();
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            aabb,
+            start: start as u32,
+            count: count as u32,
+        });
+        if count <= LEAF_SIZE {
+            return;
+        }
+
+        // Split at the median centroid along the longest side of the box.
+        let extent = aabb.max - aabb.min;
+        let axis = match extent.as_array() {
+            [x, y, z] if x >= y && x >= z => 0,
+            [_, y, z] if y >= z => 1,
+            _ => 2,
+        };
+        let half = count / 2;
+        let mut range: Vec<[u32; 3]> = self.indices[start..start + count].to_vec();
+        range.select_nth_unstable_by_key(half, |indices| {
+            let [a, b, c] = indices.map(|i| self.positions[i as usize].as_array()[axis]);
+            OrderedFloat(a + b + c)
+        });
+        self.indices[start..start + count].copy_from_slice(&range);
+
+        self.build(start, half);
+        let second = self.nodes.len();
+        self.build(start + half, count - half);
+        self.nodes[index].start = second as u32;
+        self.nodes[index].count = 0;
+    }
+}
+
+impl Body for TriangleMesh {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let mut closest: Option<(usize, Intersection)> = None;
+        let mut t_max = t_max;
+        // The hierarchy is balanced, so this is deep enough for any mesh
+        // that fits in memory.
+        let mut stack = [0u32; 64];
+        let mut top = 1;
+        while top > 0 {
+            top -= 1;
+            let index = stack[top];
+            let node = &self.nodes[index as usize];
+            if !node.aabb.hit(ray, t_min, t_max) {
+                continue;
+            }
+            if node.count == 0 {
+                stack[top] = node.start;
+                stack[top + 1] = index + 1;
+                top += 2;
+                continue;
+            }
+            let start = node.start as usize;
+            for i in start..start + node.count as usize {
+                if let Some(hit) = triangle::intersect(self.vertices(i), ray, t_min, t_max) {
+                    t_max = hit.t;
+                    closest = Some((i, hit));
+                }
+            }
+        }
+
+        let Some((i, hit)) = closest else {
+            return false;
+        };
+        let corners = self.indices[i].map(|i| i as usize);
+        triangle::set_hit(
+            rec,
+            ray,
+            hit,
+            self.vertices(i),
+            self.normals.as_ref().map(|n| corners.map(|i| n[i])),
+            self.uvs.as_ref().map(|uv| corners.map(|i| uv[i])),
+        );
+        rec.body_props = self.body_props;
+        if let (Some(colors), Texture::VertexColor(tint)) =
+            (&self.colors, self.body_props.texture())
+        {
+            let [a, b, c] = corners.map(|i| colors[i]);
+            let color = a * (1. - hit.u - hit.v) + b * hit.u + c * hit.v;
+            rec.body_props = self
+                .body_props
+                .with_texture(Texture::VertexColor(tint * color));
+        }
+        true
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.nodes[0].aabb
+    }
+}
+
+impl Export for TriangleMesh {
+    fn to_items(&self, items: &mut Vec<Item>) -> Result<(), Error> {
+        let mut item = Item::new("mesh")
+            .field(
+                "positions",
+                Value::list(self.positions.iter().map(|&p| p.into()).collect()),
+            )
+            .field(
+                "indices",
+                Value::list(
+                    self.indices
+                        .iter()
+                        .map(|t| t.map(|i| i as usize).into())
+                        .collect(),
+                ),
+            );
+        if let Some(normals) = &self.normals {
+            item = item.field(
+                "normals",
+                Value::list(normals.iter().map(|&n| n.into()).collect()),
+            );
+        }
+        if let Some(colors) = &self.colors {
+            item = item.field(
+                "colors",
+                Value::list(colors.iter().map(|&c| c.into()).collect()),
+            );
+        }
+        if let Some(uvs) = &self.uvs {
+            item = item.field(
+                "uvs",
+                Value::list(uvs.iter().map(|&(u, v)| [u, v].into()).collect()),
+            );
+        }
+        items.push(item.body_props(&self.body_props));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TriangleMesh;
+    use crate::bodies::{Body, BodyProps, HitRecord, Triangle};
+    use crate::point::Point;
+    use crate::random;
+    use crate::raytracer::Ray;
+    use rand::Rng;
+
+    // A bumpy grid of `n` by `n` cells, two triangles each.
+    fn grid(n: u32) -> (Vec<Point>, Vec<[u32; 3]>) {
+        let positions = (0..=n)
+            .flat_map(|i| {
+                (0..=n).map(move |j| {
+                    let (x, z) = (i as f64 / n as f64, j as f64 / n as f64);
+                    Point::new(x, 0.1 * (10. * x).sin() * (7. * z).cos(), z)
+                })
+            })
+            .collect();
+        let vertex = |i: u32, j: u32| i * (n + 1) + j;
+        let indices = (0..n)
+            .flat_map(|i| (0..n).map(move |j| (i, j)))
+            .flat_map(|(i, j)| {
+                [
+                    [vertex(i, j), vertex(i + 1, j), vertex(i, j + 1)],
+                    [vertex(i + 1, j), vertex(i + 1, j + 1), vertex(i, j + 1)],
+                ]
+            })
+            .collect();
+        (positions, indices)
+    }
+
+    #[test]
+    fn test_hits_match_triangles() {
+        random::seed(7);
+        let (positions, indices) = grid(12);
+        let triangles: Vec<Triangle> = indices
+            .iter()
+            .map(|t| {
+                let [a, b, c] = t.map(|i| positions[i as usize]);
+                Triangle::new(a, b, c, BodyProps::null())
+            })
+            .collect();
+        let mesh = TriangleMesh::new(positions, indices, BodyProps::null());
+
+        let mut rng = random::rng();
+        for _ in 0..500 {
+            let origin = Point::new(rng.gen_range(-0.5..1.5), 1., rng.gen_range(-0.5..1.5));
+            let target = Point::new(rng.gen::<f64>(), 0., rng.gen::<f64>());
+            let ray = Ray::new(origin, target - origin);
+
+            let mut expected = HitRecord::default();
+            let mut t_max = f64::INFINITY;
+            for triangle in &triangles {
+                if triangle.hit(&ray, 0.001, t_max, &mut expected) {
+                    t_max = expected.t;
+                }
+            }
+            let mut rec = HitRecord::default();
+            assert_eq!(
+                mesh.hit(&ray, 0.001, f64::INFINITY, &mut rec),
+                t_max.is_finite()
+            );
+            if t_max.is_finite() {
+                assert_eq!(rec.t, expected.t);
+                assert_eq!((rec.u, rec.v), (expected.u, expected.v));
+            }
+        }
+    }
+}
@@ -0,0 +1,21 @@
+// When a body moves from where it starts to where it ends. Before `start`
+// and after `end` it stays put, so the box around both places holds it at
+// any time the shutter may be open.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Motion {
+    pub start: f64,
+    pub end: f64,
+}
+
+impl Motion {
+    pub fn new(start: f64, end: f64) -> Self {
+        Motion { start, end }
+    }
+    // How far along the body is at `time`, from 0 to 1.
+    pub fn progress(&self, time: f64) -> f64 {
+        if self.end <= self.start {
+            return if time < self.start { 0. } else { 1. };
+        }
+        ((time - self.start) / (self.end - self.start)).clamp(0., 1.)
+    }
+}
@@ -0,0 +1,154 @@
+use crate::bodies::bodyprops::BodyProps;
+use crate::bodies::collision::{Body, HitRecord, AABB};
+use crate::point::Point;
+use crate::raytracer::Ray;
+use crate::scenefile::writer::Export;
+use crate::scenefile::{Error, Item, Value};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shape {
+    Parallelogram,
+    // The half of the parallelogram next to the corner.
+    Triangle,
+}
+
+// A flat shape spanned by the edges `u` and `v` from `corner`. Its normal is
+// `u` cross `v` and its texture coordinates run along the edges.
+#[derive(Debug)]
+pub struct Quad {
+    corner: Point,
+    u: Point,
+    v: Point,
+    shape: Shape,
+    normal: Point,
+    d: f64,
+    w: Point,
+    body_props: BodyProps,
+}
+
+impl Quad {
+    pub fn new(corner: Point, u: Point, v: Point, body_props: BodyProps) -> Quad {
+        let n = u.cross(v);
+        let normal = n.unit_vector();
+        Quad {
+            corner,
+            u,
+            v,
+            shape: Shape::Parallelogram,
+            normal,
+            d: normal.dot(corner),
+            w: n / n.dot(n),
+            body_props,
+        }
+    }
+    pub fn triangle(corner: Point, u: Point, v: Point, body_props: BodyProps) -> Quad {
+        Quad {
+            shape: Shape::Triangle,
+            ..Quad::new(corner, u, v, body_props)
+        }
+    }
+}
+
+impl Body for Quad {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let denominator = self.normal.dot(ray.direction);
+        if denominator.abs() < 1e-12 {
+            return false;
+        }
+        let t = (self.d - self.normal.dot(ray.origin)) / denominator;
+        if t < t_min || t > t_max {
+            return false;
+        }
+        // Coordinates of the hit along the edges.
+        let p = ray.at(t);
+        let planar = p - self.corner;
+        let alpha = self.w.dot(planar.cross(self.v));
+        let beta = self.w.dot(self.u.cross(planar));
+        let inside = match self.shape {
+            Shape::Parallelogram => (0. ..=1.).contains(&alpha) && (0. ..=1.).contains(&beta),
+            Shape::Triangle => alpha >= 0. && beta >= 0. && alpha + beta <= 1.,
+        };
+        if !inside {
+            return false;
+        }
+
+        rec.t = t;
+        rec.p = p;
+        rec.u = alpha;
+        rec.v = beta;
+        rec.set_face_normal(ray, self.normal);
+        rec.body_props = self.body_props;
+        true
+    }
+
+    fn bounding_box(&self) -> AABB {
+        let c = self.corner;
+        match self.shape {
+            Shape::Parallelogram => {
+                AABB::from_points(&[c, c + self.u, c + self.v, c + self.u + self.v])
+            }
+            Shape::Triangle => AABB::from_points(&[c, c + self.u, c + self.v]),
+        }
+    }
+}
+
+impl Export for Quad {
+    fn to_items(&self, items: &mut Vec<Item>) -> Result<(), Error> {
+        let mut item = Item::new("quad")
+            .field("corner", self.corner)
+            .field("u", self.u)
+            .field("v", self.v);
+        if self.shape == Shape::Triangle {
+            item = item.field("shape", Value::named("triangle"));
+        }
+        items.push(item.body_props(&self.body_props));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Quad;
+    use crate::bodies::{Body, BodyProps, HitRecord};
+    use crate::point::Point;
+    use crate::raytracer::Ray;
+
+    fn hit(quad: &Quad, origin: Point, direction: Point) -> Option<HitRecord> {
+        let mut rec = HitRecord::default();
+        if quad.hit(&Ray::new(origin, direction), 0.001, f64::INFINITY, &mut rec) {
+            Some(rec)
+        } else {
+            None
+        }
+    }
+
+    #[test]
+    fn test_tilted_quad() {
+        // A square leaning back from the x axis, facing up and towards +z.
+        let u = Point::new(2., 0., 0.);
+        let v = Point::new(0., 1., -1.);
+        let quad = Quad::new(Point::new(0., 0., 0.), u, v, BodyProps::null());
+        let rec = hit(&quad, Point::new(0.5, 0.5, 5.), Point::new(0., 0., -1.)).unwrap();
+        assert!((rec.p - Point::new(0.5, 0.5, -0.5)).length() < 1e-12);
+        assert!((rec.u - 0.25).abs() < 1e-12 && (rec.v - 0.5).abs() < 1e-12);
+        let normal = Point::new(0., 1., 1.).unit_vector();
+        assert!(rec.front_face);
+        assert!((rec.normal - normal).length() < 1e-12);
+        assert!(hit(&quad, Point::new(2.5, 0.5, 5.), Point::new(0., 0., -1.)).is_none());
+
+        let bbox = quad.bounding_box();
+        assert_eq!((bbox.min.x, bbox.min.y, bbox.min.z), (0., 0., -1.));
+        assert_eq!((bbox.max.x, bbox.max.y, bbox.max.z), (2., 1., 0.));
+    }
+    #[test]
+    fn test_triangle() {
+        let u = Point::new(1., 0., 0.);
+        let v = Point::new(0., 1., 0.);
+        let triangle = Quad::triangle(Point::new(0., 0., 0.), u, v, BodyProps::null());
+        let down = Point::new(0., 0., -1.);
+        assert!(hit(&triangle, Point::new(0.2, 0.2, 1.), down).is_some());
+        assert!(hit(&triangle, Point::new(0.6, 0.6, 1.), down).is_none());
+        let bbox = triangle.bounding_box();
+        assert!(bbox.max.z > bbox.min.z);
+    }
+}
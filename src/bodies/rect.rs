@@ -2,6 +2,8 @@ use crate::bodies::bodyprops::BodyProps;
 use crate::bodies::collision::{Body, HitRecord, AABB};
 use crate::point::Point;
 use crate::raytracer::Ray;
+use crate::scenefile::writer::Export;
+use crate::scenefile::{Error, Item, Value};
 #[derive(Debug)]
 pub struct Rect {
     a0: f64,
@@ -67,7 +69,12 @@ impl Body for Rect {
         rec.t = t;
         rec.u = (a - self.a0) / (self.a1 - self.a0);
         rec.v = (b - self.b0) / (self.b1 - self.b0);
-        rec.set_face_normal(ray, Point::new(0., 0., 1.));
+        let outward_normal = match self.axis {
+            Axis::XY => Point::new(0., 0., 1.),
+            Axis::XZ => Point::new(0., 1., 0.),
+            Axis::YZ => Point::new(1., 0., 0.),
+        };
+        rec.set_face_normal(ray, outward_normal);
         rec.p = ray.at(t);
         rec.body_props = self.body_props;
         true
@@ -90,3 +97,24 @@ impl Body for Rect {
         }
     }
 }
+
+impl Export for Rect {
+    fn to_items(&self, items: &mut Vec<Item>) -> Result<(), Error> {
+        let axis = match self.axis {
+            Axis::XY => "xy",
+            Axis::XZ => "xz",
+            Axis::YZ => "yz",
+        };
+        items.push(
+            Item::new("rect")
+                .field("axis", Value::named(axis))
+                .field("a0", self.a0)
+                .field("a1", self.a1)
+                .field("b0", self.b0)
+                .field("b1", self.b1)
+                .field("k", self.k)
+                .body_props(&self.body_props),
+        );
+        Ok(())
+    }
+}
@@ -2,12 +2,17 @@ use std::f64::consts::PI;
 
 use crate::bodies::bodyprops::BodyProps;
 use crate::bodies::collision::{Body, HitRecord, AABB};
+use crate::bodies::motion::Motion;
 use crate::point::Point;
 use crate::raytracer::Ray;
+use crate::scenefile::writer::Export;
+use crate::scenefile::{Error, Item};
 #[derive(Debug)]
 pub struct Sphere {
     center: Point,
     radius: f64,
+    // Where the centre ends up, and when it moves there.
+    motion: Option<(Point, Motion)>,
     body_props: BodyProps,
 }
 
@@ -16,43 +21,138 @@ impl Sphere {
         Sphere {
             center,
             radius,
+            motion: None,
             body_props,
         }
     }
-}
-
-impl Body for Sphere {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
-        let oc = ray.origin - self.center;
+    pub fn with_motion(self, end_center: Point, motion: Motion) -> Self {
+        Sphere {
+            motion: Some((end_center, motion)),
+            ..self
+        }
+    }
+    // Where the ray meets the sphere, the near side first, if it does.
+    fn roots(&self, ray: &Ray) -> Option<[f64; 2]> {
+        let oc = ray.origin - self.center_at(ray.time);
         let a = ray.direction.dot(ray.direction);
         let half_b = oc.dot(ray.direction);
         let c = oc.length_squared() - self.radius * self.radius;
         let discriminant = half_b * half_b - a * c;
         if discriminant < 0. {
-            return false;
+            return None;
         }
         let sqrtd = discriminant.sqrt();
-        let root = (-half_b - sqrtd) / a;
-
-        if root < t_min || t_max < root {
-            return false;
-        }
-
-        rec.t = root;
+        Some([(-half_b - sqrtd) / a, (-half_b + sqrtd) / a])
+    }
+    fn set_hit(&self, ray: &Ray, t: f64, rec: &mut HitRecord) {
+        rec.t = t;
         rec.p = ray.at(rec.t);
-        let outward_normal = (rec.p - self.center) / self.radius;
+        let outward_normal = (rec.p - self.center_at(ray.time)) / self.radius;
         let theta = (-outward_normal.y).acos();
         let phi = -outward_normal.z.atan2(outward_normal.x + PI);
         rec.u = phi / (2. * PI);
         rec.v = theta / PI;
         rec.set_face_normal(ray, outward_normal);
         rec.body_props = self.body_props;
-        true
+    }
+    fn center_at(&self, time: f64) -> Point {
+        match self.motion {
+            Some((end_center, motion)) => {
+                self.center + motion.progress(time) * (end_center - self.center)
+            }
+            None => self.center,
+        }
+    }
+}
+
+impl Body for Sphere {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        match self.roots(ray) {
+            Some([root, _]) if t_min <= root && root <= t_max => {
+                self.set_hit(ray, root, rec);
+                true
+            }
+            _ => false,
+        }
+    }
+    // Unlike `hit`, this also finds the far side from inside the sphere,
+    // where the ray leaves it.
+    fn crossings(&self, ray: &Ray, t_min: f64, crossings: &mut Vec<HitRecord>) {
+        if let Some(roots) = self.roots(ray) {
+            for root in roots.into_iter().filter(|&t| t >= t_min) {
+                let mut rec = HitRecord::default();
+                self.set_hit(ray, root, &mut rec);
+                crossings.push(rec);
+            }
+        }
     }
     fn bounding_box(&self) -> AABB {
-        AABB::new(
-            self.center - Point::new(self.radius, self.radius, self.radius),
-            self.center + Point::new(self.radius, self.radius, self.radius),
-        )
+        let r = Point::new(self.radius, self.radius, self.radius);
+        let aabb = AABB::new(self.center - r, self.center + r);
+        match self.motion {
+            Some((end_center, _)) => {
+                aabb.surrounding_box(AABB::new(end_center - r, end_center + r))
+            }
+            None => aabb,
+        }
+    }
+}
+
+impl Export for Sphere {
+    fn to_items(&self, items: &mut Vec<Item>) -> Result<(), Error> {
+        let mut item = Item::new("sphere")
+            .field("center", self.center)
+            .field("radius", self.radius);
+        if let Some((end_center, motion)) = self.motion {
+            item = item.field("end_center", end_center).field("motion", motion);
+        }
+        items.push(item.body_props(&self.body_props));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Sphere;
+    use crate::bodies::motion::Motion;
+    use crate::bodies::{Body, BodyProps, HitRecord};
+    use crate::point::Point;
+    use crate::raytracer::Ray;
+
+    #[test]
+    fn test_moving_sphere() {
+        let sphere = Sphere::new(Point::new(0., 0., 0.), 0.5, BodyProps::null())
+            .with_motion(Point::new(0., 2., 0.), Motion::new(0., 1.));
+        let bbox = sphere.bounding_box();
+        assert_eq!((bbox.min.y, bbox.max.y), (-0.5, 2.5));
+
+        let mut rec = HitRecord::default();
+        let along =
+            |time: f64| Ray::new(Point::new(-5., 1., 0.), Point::new(1., 0., 0.)).with_time(time);
+        assert!(!sphere.hit(&along(0.), 0.001, f64::INFINITY, &mut rec));
+        assert!(sphere.hit(&along(0.5), 0.001, f64::INFINITY, &mut rec));
+        assert!((rec.p - Point::new(-0.5, 1., 0.)).length() < 1e-12);
+        assert!((rec.normal - Point::new(-1., 0., 0.)).length() < 1e-12);
+        // Before and after the motion it rests where it starts or ends.
+        assert!(!sphere.hit(&along(2.), 0.001, f64::INFINITY, &mut rec));
+        let high = Ray::new(Point::new(-5., 2.2, 0.), Point::new(1., 0., 0.)).with_time(7.);
+        assert!(sphere.hit(&high, 0.001, f64::INFINITY, &mut rec));
+    }
+    #[test]
+    fn test_crossings() {
+        let sphere = Sphere::new(Point::new(0., 0., -3.), 1., BodyProps::null());
+        let mut rec = HitRecord::default();
+        let from_outside = Ray::new(Point::new(0., 0., 0.), Point::new(0., 0., -1.));
+        assert!(sphere.hit(&from_outside, 0.001, f64::INFINITY, &mut rec));
+        assert!(rec.t == 2. && rec.front_face);
+        // From inside the sphere is not hit, but it is crossed where the ray
+        // leaves it.
+        let from_inside = Ray::new(Point::new(0., 0., -3.), Point::new(0., 0., -1.));
+        assert!(!sphere.hit(&from_inside, 0.001, f64::INFINITY, &mut rec));
+        let mut crossings = vec![];
+        sphere.crossings(&from_outside, 0.001, &mut crossings);
+        sphere.crossings(&from_inside, 0.001, &mut crossings);
+        let found: Vec<(f64, bool)> = crossings.iter().map(|c| (c.t, c.front_face)).collect();
+        assert_eq!(found, [(2., true), (4., false), (1., false)]);
     }
 }
@@ -1,4 +1,6 @@
 use crate::point::Point;
+use crate::random;
+use rand::Rng;
 use std::fmt::Debug;
 
 #[allow(dead_code)]
@@ -7,6 +9,9 @@ pub enum Texture {
     SolidColor(Point),
     Noise(Point),
     Checkered(Point, Point),
+    // A tint for the colours of the vertices of a mesh. Meshes with vertex
+    // colours hand out hits with the tinted colour at the hit point.
+    VertexColor(Point),
 }
 
 impl Texture {
@@ -18,8 +23,8 @@ impl Texture {
     }
     pub fn color(&self, _u: f64, _v: f64, p: Point) -> Point {
         match self {
-            Texture::SolidColor(s) => *s,
-            Texture::Noise(s) => *s * rand::random::<f64>(),
+            Texture::SolidColor(s) | Texture::VertexColor(s) => *s,
+            Texture::Noise(s) => *s * random::rng().gen::<f64>(),
             Texture::Checkered(even, odd) => {
                 let sines = (10. * p.x).sin() * (10. * p.y).sin() * (10. * p.z).sin();
                 if sines < 0. {
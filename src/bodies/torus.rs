@@ -0,0 +1,171 @@
+use std::f64::consts::PI;
+
+use crate::bodies::bodyprops::BodyProps;
+use crate::bodies::collision::{Body, HitRecord, AABB};
+use crate::bodies::frame::{circle_extent, set_hit, Frame};
+use crate::point::Point;
+use crate::polynomial;
+use crate::raytracer::Ray;
+use crate::scenefile::writer::Export;
+use crate::scenefile::{Error, Item};
+
+// A ring around `axis` through `center`, with a tube of `minor_radius` at
+// `major_radius` from the centre.
+#[derive(Debug)]
+pub struct Torus {
+    center: Point,
+    axis: Point,
+    major_radius: f64,
+    minor_radius: f64,
+    frame: Frame,
+    body_props: BodyProps,
+}
+
+impl Torus {
+    pub fn new(
+        center: Point,
+        axis: Point,
+        major_radius: f64,
+        minor_radius: f64,
+        body_props: BodyProps,
+    ) -> Torus {
+        Torus {
+            center,
+            axis: axis.unit_vector(),
+            major_radius,
+            minor_radius,
+            frame: Frame::new(center, axis),
+            body_props,
+        }
+    }
+}
+
+impl Body for Torus {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let (o, d) = self.frame.ray_to_local(ray);
+        let (big, small) = (self.major_radius, self.minor_radius);
+        // With a unit direction and the origin moved up to the sphere around
+        // the torus the coefficients stay small, which keeps the roots precise.
+        let length = d.length();
+        let d = d / length;
+        // Rays that pass the sphere around the torus miss it.
+        let closest = o.dot(d);
+        if o.dot(o) - closest * closest > (big + small).powi(2) {
+            return false;
+        }
+        let skip = (o.length() - big - small).max(0.);
+        let o = o + skip * d;
+
+        // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + y^2) along the ray.
+        let e = o.dot(o) + big * big - small * small;
+        let f = o.dot(d);
+        let four_r2 = 4. * big * big;
+        let roots = polynomial::solve_quartic(
+            1.,
+            4. * f,
+            4. * f * f + 2. * e - four_r2 * (d.x * d.x + d.y * d.y),
+            4. * e * f - 2. * four_r2 * (o.x * d.x + o.y * d.y),
+            e * e - four_r2 * (o.x * o.x + o.y * o.y),
+        );
+        let Some((s, t)) = roots
+            .as_slice()
+            .iter()
+            .map(|s| (*s, (s + skip) / length))
+            .find(|(_, t)| *t >= t_min && *t <= t_max)
+        else {
+            return false;
+        };
+
+        // Away from the nearest point on the ring through the tube.
+        let p = o + s * d;
+        let around = (p.x * p.x + p.y * p.y).sqrt();
+        let ring = Point::new(p.x, p.y, 0.) * (big / around);
+        let normal = p - ring;
+        let tube = p.z.atan2(around - big) / (2. * PI) + 0.5;
+        set_hit(rec, ray, &self.frame, t, normal, (Frame::angle(p), tube));
+        rec.body_props = self.body_props;
+        true
+    }
+
+    fn bounding_box(&self) -> AABB {
+        let [min, max] = circle_extent(self.center, self.axis, self.major_radius);
+        let tube = Point::new(self.minor_radius, self.minor_radius, self.minor_radius);
+        AABB::new(min - tube, max + tube)
+    }
+}
+
+impl Export for Torus {
+    fn to_items(&self, items: &mut Vec<Item>) -> Result<(), Error> {
+        items.push(
+            Item::new("torus")
+                .field("center", self.center)
+                .field("axis", self.axis)
+                .field("major_radius", self.major_radius)
+                .field("minor_radius", self.minor_radius)
+                .body_props(&self.body_props),
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Torus;
+    use crate::bodies::{Body, BodyProps, HitRecord};
+    use crate::point::Point;
+    use crate::random;
+    use crate::raytracer::Ray;
+    use rand::Rng;
+
+    // Steps along the ray until it first gets inside the tube.
+    fn march(ray: &Ray, center: Point, axis: Point, big: f64, small: f64) -> Option<f64> {
+        let inside = |t: f64| {
+            let p = ray.at(t) - center;
+            let height = p.dot(axis);
+            let around = (p - height * axis).length();
+            (around - big).powi(2) + height * height < small * small
+        };
+        let step = 1e-4;
+        let mut t = 0.;
+        while t < 5. {
+            if inside(t + step) {
+                return Some(t + step);
+            }
+            t += step;
+        }
+        None
+    }
+
+    #[test]
+    fn test_against_marching() {
+        random::seed(3);
+        let mut rng = random::rng();
+        let center = Point::new(0.5, -0.2, 0.1);
+        let axis = Point::new(1., 2., 0.5).unit_vector();
+        let torus = Torus::new(center, axis, 1.5, 0.4, BodyProps::null());
+        let bbox = torus.bounding_box();
+        let mut hits = 0;
+        for _ in 0..200 {
+            let origin = Point::random_unit_vector() * 5. + center;
+            let target = center + Point::random_in_unit_sphere() * 2.;
+            let ray = Ray::new(origin, (target - origin) * rng.gen_range(0.5..2.));
+            let mut rec = HitRecord::default();
+            let hit = torus.hit(&ray, 0.001, f64::INFINITY, &mut rec);
+            let expected = march(&ray, center, axis, 1.5, 0.4);
+            assert_eq!(hit, expected.is_some());
+            if let Some(t) = expected {
+                hits += 1;
+                assert!((rec.t - t).abs() < 1e-3);
+                assert!(rec.front_face);
+                let p = rec.p;
+                assert!(p.x >= bbox.min.x && p.y >= bbox.min.y && p.z >= bbox.min.z);
+                assert!(p.x <= bbox.max.x && p.y <= bbox.max.y && p.z <= bbox.max.z);
+                // The normal points away from the middle of the tube.
+                let height = (p - center).dot(axis);
+                let ring = (p - center - height * axis).unit_vector() * 1.5 + center;
+                assert!((rec.normal - (p - ring) / 0.4).length() < 1e-6);
+            }
+        }
+        assert!(hits > 30);
+    }
+}
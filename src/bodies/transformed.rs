@@ -0,0 +1,259 @@
+use crate::bodies::collision::{Body, HitRecord, AABB};
+use crate::bodies::motion::Motion;
+use crate::matrix::{Decomposed, Matrix4};
+use crate::point::Point;
+use crate::raytracer::Ray;
+use crate::scenefile::writer::{export, Export};
+use crate::scenefile::{Error, Item};
+use std::sync::Arc;
+
+// A body placed in the world by an affine transform. The body itself can be
+// shared, so a mesh can be placed many times without copying it.
+#[derive(Debug)]
+pub struct Transformed {
+    body: Arc<dyn Body>,
+    to_world: Matrix4,
+    to_object: Matrix4,
+    // Where the body ends up, when it moves there, and both placements split
+    // up to blend them.
+    motion: Option<(Matrix4, Motion, [Decomposed; 2])>,
+    aabb: AABB,
+}
+
+impl Transformed {
+    pub fn new(body: Arc<dyn Body>, to_world: Matrix4) -> Self {
+        let to_object = to_world
+            .inverse()
+            .expect("a transform has to keep the body solid");
+        Transformed {
+            aabb: placed_box(body.as_ref(), &[to_world]),
+            body,
+            to_world,
+            to_object,
+            motion: None,
+        }
+    }
+    // Moves the body to where `end_to_world` places it, blending the
+    // translation, turn and stretch on their own so that it keeps its shape.
+    pub fn with_motion(self, end_to_world: Matrix4, motion: Motion) -> Self {
+        let parts = [
+            Decomposed::new(&self.to_world),
+            Decomposed::new(&end_to_world),
+        ];
+        Transformed {
+            aabb: moving_box(self.body.as_ref(), &parts),
+            motion: Some((end_to_world, motion, parts)),
+            ..self
+        }
+    }
+}
+
+fn corners(bbox: &AABB) -> [Point; 8] {
+    let (min, max) = (bbox.min, bbox.max);
+    [
+        Point::new(min.x, min.y, min.z),
+        Point::new(max.x, min.y, min.z),
+        Point::new(min.x, max.y, min.z),
+        Point::new(min.x, min.y, max.z),
+        Point::new(max.x, max.y, min.z),
+        Point::new(max.x, min.y, max.z),
+        Point::new(min.x, max.y, max.z),
+        Point::new(max.x, max.y, max.z),
+    ]
+}
+
+// The box around the box of `body` placed by each of `transforms`.
+fn placed_box(body: &dyn Body, transforms: &[Matrix4]) -> AABB {
+    let corners = corners(&body.bounding_box());
+    let points: Vec<Point> = transforms
+        .iter()
+        .flat_map(|m| corners.map(|c| m.transform_point(c)))
+        .collect();
+    AABB::from_points(&points)
+}
+
+// The box around a body moving from one placement to the other. Points of a
+// turning body move along arcs, so the box is taken around the body at many
+// steps and grown by how far an arc bulges out between two of them.
+fn moving_box(body: &dyn Body, parts: &[Decomposed; 2]) -> AABB {
+    const STEPS: usize = 64;
+    let transforms: Vec<Matrix4> = (0..=STEPS)
+        .map(|i| parts[0].lerp(&parts[1], i as f64 / STEPS as f64))
+        .collect();
+    let bbox = placed_box(body, &transforms);
+    // How far the corners of the box reach from the point the body turns
+    // around, which only grows or shrinks steadily as the body stretches.
+    let radius = corners(&body.bounding_box())
+        .iter()
+        .flat_map(|&c| [transforms[0], transforms[STEPS]].map(|m| m.transform_vector(c)))
+        .map(|v| v.length())
+        .fold(0., f64::max);
+    let step = parts[0].turn(&parts[1]) / STEPS as f64;
+    let bulge = Point::new(1., 1., 1.) * (radius * (1. - step.cos()));
+    AABB::new(bbox.min - bulge, bbox.max + bulge)
+}
+
+impl Body for Transformed {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let (to_world, to_object) = match self.motion {
+            Some((_, motion, [start, end])) => {
+                let to_world = start.lerp(&end, motion.progress(ray.time));
+                match to_world.inverse() {
+                    Some(to_object) => (to_world, to_object),
+                    None => return false,
+                }
+            }
+            None => (self.to_world, self.to_object),
+        };
+        // The direction is not normalised, so distances along the ray are
+        // the same in both spaces.
+        let local = Ray::new(
+            to_object.transform_point(ray.origin),
+            to_object.transform_vector(ray.direction),
+        )
+        .with_time(ray.time);
+        if !self.body.hit(&local, t_min, t_max, rec) {
+            return false;
+        }
+        rec.p = to_world.transform_point(rec.p);
+        // Normals stay perpendicular to the surface with the inverse-transpose.
+        rec.normal = to_object
+            .transpose()
+            .transform_vector(rec.normal)
+            .unit_vector();
+        true
+    }
+    fn bounding_box(&self) -> AABB {
+        self.aabb
+    }
+}
+
+impl Export for Transformed {
+    fn to_items(&self, items: &mut Vec<Item>) -> Result<(), Error> {
+        let start = items.len();
+        export(self.body.as_ref(), items)?;
+        // Named parts of the body are placed along with the body itself.
+        for item in items.split_off(start) {
+            if item.fields.iter().any(|f| f.name == "name") {
+                items.push(item);
+            } else {
+                items.push(match self.motion {
+                    Some((end_to_world, motion, _)) => {
+                        item.moved(self.to_world, end_to_world, motion)?
+                    }
+                    None => item.transformed(self.to_world)?,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Transformed;
+    use crate::bodies::bodyprops::Material;
+    use crate::bodies::collision::Body;
+    use crate::bodies::motion::Motion;
+    use crate::bodies::{BodyProps, Cube, HitRecord, Sphere, Texture};
+    use crate::matrix::Matrix4;
+    use crate::point::Point;
+    use crate::raytracer::Ray;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_turned_cube() {
+        let props = BodyProps::new(Texture::new_color(1., 1., 1.), Material::Lambertian);
+        let cube = Arc::new(Cube::new(
+            Point::new(-1., -1., -1.),
+            Point::new(1., 1., 1.),
+            props,
+        ));
+        let to_world = Matrix4::translation(Point::new(0., 0., -5.))
+            * Matrix4::rotation(Point::new(0., 1., 0.), 45.)
+            * Matrix4::scaling(Point::new(1., 1., 2.));
+        let turned = Transformed::new(cube, to_world);
+
+        // Stretched along z and then turned, the corners reach out
+        // 3 / sqrt(2) along x and z.
+        let reach = 3. / 2f64.sqrt();
+        let bbox = turned.bounding_box();
+        assert!((bbox.max.x - reach).abs() < 1e-9 && (bbox.min.z + 5. + reach).abs() < 1e-9);
+
+        let mut rec = HitRecord::default();
+        let ray = Ray::new(Point::new(1.5, 0., 0.), Point::new(0., 0., -1.));
+        assert!(turned.hit(&ray, 0.001, f64::INFINITY, &mut rec));
+        assert!((rec.normal - Point::new(1., 0., 1.).unit_vector()).length() < 1e-9);
+        assert!((ray.at(rec.t) - rec.p).length() < 1e-9);
+    }
+    #[test]
+    fn test_stretched_sphere() {
+        let props = BodyProps::new(Texture::new_color(1., 1., 1.), Material::Lambertian);
+        let sphere = Arc::new(Sphere::new(Point::new(0., 0., 0.), 1., props));
+        let to_world = Matrix4::translation(Point::new(0., 0., -5.))
+            * Matrix4::scaling(Point::new(2., 1., 1.));
+        let ellipsoid = Transformed::new(sphere, to_world);
+
+        let mut rec = HitRecord::default();
+        let ray = Ray::new(Point::new(1., 0., 0.), Point::new(0., 0., -1.));
+        assert!(ellipsoid.hit(&ray, 0.001, f64::INFINITY, &mut rec));
+        let z = 0.75f64.sqrt();
+        assert!((rec.p - Point::new(1., 0., z - 5.)).length() < 1e-9);
+        // The gradient of x^2 / 4 + y^2 + z^2, not the stretched normal.
+        assert!((rec.normal - Point::new(0.25, 0., z).unit_vector()).length() < 1e-9);
+        assert!(rec.front_face);
+    }
+    #[test]
+    fn test_moving_instance() {
+        let props = BodyProps::new(Texture::new_color(1., 1., 1.), Material::Lambertian);
+        let sphere = Arc::new(Sphere::new(Point::new(0., 0., 0.), 1., props));
+        // From x = 0 at time 1 to x = 4 at time 3, growing to twice the size.
+        let end =
+            Matrix4::translation(Point::new(4., 0., 0.)) * Matrix4::scaling(Point::new(2., 2., 2.));
+        let moving =
+            Transformed::new(sphere, Matrix4::identity()).with_motion(end, Motion::new(1., 3.));
+        let bbox = moving.bounding_box();
+        assert!((bbox.min.x + 1.).abs() < 1e-9 && (bbox.max.x - 6.).abs() < 1e-9);
+        assert!((bbox.max.y - 2.).abs() < 1e-9);
+
+        let mut rec = HitRecord::default();
+        let down = |x: f64, time: f64| {
+            Ray::new(Point::new(x, 5., 0.), Point::new(0., -1., 0.)).with_time(time)
+        };
+        // Halfway it is at x = 2 with a radius of 1.5, and it stays put
+        // before and after it moves.
+        assert!(moving.hit(&down(2., 2.), 0.001, f64::INFINITY, &mut rec));
+        assert!((rec.p.y - 1.5).abs() < 1e-9);
+        assert!((rec.normal - Point::new(0., 1., 0.)).length() < 1e-9);
+        assert!(!moving.hit(&down(2., 0.), 0.001, f64::INFINITY, &mut rec));
+        assert!(moving.hit(&down(0., 0.), 0.001, f64::INFINITY, &mut rec));
+        assert!(moving.hit(&down(4., 5.), 0.001, f64::INFINITY, &mut rec));
+        assert!((rec.p.y - 2.).abs() < 1e-9);
+    }
+    #[test]
+    fn test_half_turn() {
+        let props = BodyProps::new(Texture::new_color(1., 1., 1.), Material::Lambertian);
+        let plank = Arc::new(Cube::new(
+            Point::new(-2., -0.5, -0.25),
+            Point::new(2., 0.5, 0.25),
+            props,
+        ));
+        // Half a turn around y, which would squash the plank flat halfway if
+        // the matrices were blended.
+        let end = Matrix4::rotation(Point::new(0., 1., 0.), 180.);
+        let turning =
+            Transformed::new(plank, Matrix4::identity()).with_motion(end, Motion::new(0., 1.));
+        let bbox = turning.bounding_box();
+        assert!(bbox.max.z >= 2. && bbox.min.z <= -2. && bbox.max.x >= 2.);
+
+        let mut rec = HitRecord::default();
+        let down = |z: f64, time: f64| {
+            Ray::new(Point::new(0.1, 5., z), Point::new(0., -1., 0.)).with_time(time)
+        };
+        // Halfway it lies along z, at its full length.
+        assert!(turning.hit(&down(1.8, 0.5), 0.001, f64::INFINITY, &mut rec));
+        assert!((rec.p.y - 0.5).abs() < 1e-9);
+        assert!((rec.normal - Point::new(0., 1., 0.)).length() < 1e-9);
+        assert!(!turning.hit(&down(1.8, 0.), 0.001, f64::INFINITY, &mut rec));
+    }
+}
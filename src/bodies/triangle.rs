@@ -0,0 +1,158 @@
+use crate::bodies::bodyprops::BodyProps;
+use crate::bodies::collision::{Body, HitRecord, AABB};
+use crate::point::Point;
+use crate::raytracer::Ray;
+use crate::scenefile::writer::Export;
+use crate::scenefile::{Error, Item};
+#[derive(Debug)]
+pub struct Triangle {
+    vertices: [Point; 3],
+    normals: Option<[Point; 3]>,
+    uvs: Option<[(f64, f64); 3]>,
+    body_props: BodyProps,
+}
+
+impl Triangle {
+    pub fn new(a: Point, b: Point, c: Point, body_props: BodyProps) -> Triangle {
+        Triangle {
+            vertices: [a, b, c],
+            normals: None,
+            uvs: None,
+            body_props,
+        }
+    }
+    // Shade with normals interpolated between these vertex normals.
+    pub fn with_normals(self, normals: [Point; 3]) -> Triangle {
+        Triangle {
+            normals: Some(normals.map(|n| n.unit_vector())),
+            ..self
+        }
+    }
+    // Texture coordinates of the vertices, without them the barycentric
+    // coordinates of the hit are used.
+    pub fn with_uvs(self, uvs: [(f64, f64); 3]) -> Triangle {
+        Triangle {
+            uvs: Some(uvs),
+            ..self
+        }
+    }
+}
+
+// Combines the values at the vertices by barycentric coordinates `u` and `v`.
+fn interpolate<T>(values: [T; 3], u: f64, v: f64) -> T
+where
+    T: std::ops::Mul<f64, Output = T> + std::ops::Add<Output = T>,
+{
+    let [a, b, c] = values;
+    a * (1. - u - v) + b * u + c * v
+}
+
+// Where a ray meets a triangle: the ray parameter and the barycentric
+// coordinates of the second and third vertex.
+#[derive(Clone, Copy, Debug)]
+pub struct Intersection {
+    pub t: f64,
+    pub u: f64,
+    pub v: f64,
+}
+
+// Möller–Trumbore intersection.
+pub fn intersect(vertices: [Point; 3], ray: &Ray, t_min: f64, t_max: f64) -> Option<Intersection> {
+    let [a, b, c] = vertices;
+    let edge_1 = b - a;
+    let edge_2 = c - a;
+    let p = ray.direction.cross(edge_2);
+    let determinant = edge_1.dot(p);
+    // The ray runs along the plane of the triangle. The determinant grows
+    // with the lengths of the edges and of the direction, so it is compared
+    // relative to them, which keeps tiny triangles and short rays.
+    let scale = edge_1.length() * edge_2.length() * ray.direction.length();
+    if determinant.abs() <= 1e-12 * scale {
+        return None;
+    }
+    let inverse = 1. / determinant;
+    let s = ray.origin - a;
+    let u = s.dot(p) * inverse;
+    if !(0. ..=1.).contains(&u) {
+        return None;
+    }
+    let q = s.cross(edge_1);
+    let v = ray.direction.dot(q) * inverse;
+    if v < 0. || u + v > 1. {
+        return None;
+    }
+    let t = edge_2.dot(q) * inverse;
+    if t < t_min || t > t_max {
+        return None;
+    }
+    Some(Intersection { t, u, v })
+}
+
+// Fills in everything but the body properties of `rec` for a hit found by
+// `intersect`.
+pub fn set_hit(
+    rec: &mut HitRecord,
+    ray: &Ray,
+    hit: Intersection,
+    vertices: [Point; 3],
+    normals: Option<[Point; 3]>,
+    uvs: Option<[(f64, f64); 3]>,
+) {
+    let Intersection { t, u, v } = hit;
+    rec.t = t;
+    rec.p = ray.at(t);
+    (rec.u, rec.v) = match uvs {
+        Some([a, b, c]) => (
+            interpolate([a.0, b.0, c.0], u, v),
+            interpolate([a.1, b.1, c.1], u, v),
+        ),
+        None => (u, v),
+    };
+    let [a, b, c] = vertices;
+    let outward_normal = (b - a).cross(c - a).unit_vector();
+    rec.set_face_normal(ray, outward_normal);
+    if let Some(normals) = normals {
+        // The side that was hit follows the geometry, the shading normal
+        // is only turned to that side.
+        let mut normal = interpolate(normals, u, v).unit_vector();
+        if normal.dot(outward_normal) < 0. {
+            normal = -normal;
+        }
+        rec.normal = if rec.front_face { normal } else { -normal };
+    }
+}
+
+impl Body for Triangle {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        match intersect(self.vertices, ray, t_min, t_max) {
+            Some(hit) => {
+                set_hit(rec, ray, hit, self.vertices, self.normals, self.uvs);
+                rec.body_props = self.body_props;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn bounding_box(&self) -> AABB {
+        AABB::from_points(&self.vertices)
+    }
+}
+
+impl Export for Triangle {
+    fn to_items(&self, items: &mut Vec<Item>) -> Result<(), Error> {
+        let [a, b, c] = self.vertices;
+        let mut item = Item::new("triangle")
+            .field("a", a)
+            .field("b", b)
+            .field("c", c);
+        if let Some(normals) = self.normals {
+            item = item.field("normals", normals);
+        }
+        if let Some(uvs) = self.uvs {
+            item = item.field("uvs", uvs.map(|(u, v)| [u, v]));
+        }
+        items.push(item.body_props(&self.body_props));
+        Ok(())
+    }
+}
@@ -0,0 +1,278 @@
+use crate::denoise::Denoiser;
+use crate::output::exr::Compression;
+use crate::tonemap::{Operator, ToneMapper, Transfer};
+use std::fmt;
+use std::str::FromStr;
+
+pub const USAGE: &str = "\
+Usage: raytracer [OPTIONS]
+
+Options:
+  -s, --scene <NAME>      scene to render (default: two_spheres)
+  -f, --file <PATH>       render a scene description file instead of a built-in scene
+  -l, --list              list the available scenes and exit
+  -W, --width <PIXELS>    image width, keeps the aspect ratio if no height is given
+  -H, --height <PIXELS>   image height, keeps the aspect ratio if no width is given
+  -n, --samples <COUNT>   samples per pixel
+  -d, --max-depth <COUNT> maximum number of bounces per ray
+  -j, --threads <COUNT>   number of render threads (default: all cores)
+      --seed <NUMBER>     seed the random number generator for reproducible renders
+      --camera <INDEX>    camera of the scene to render from (default: 0)
+  -o, --output <PATH>     output image, .png, .ppm, .hdr or .exr (default: output.png)
+      --export <PATH>     write the scene and its settings to a scene file instead of rendering
+      --aovs <PATH>       also write depth, normal, albedo, uv, position and id buffers
+      --exr-compression <NAME>
+                          none or zip, for OpenEXR images (default: zip)
+      --frames <RANGE>    render frames FIRST-LAST (or a single frame) of an animated scene
+                          file, numbering the output at its # characters or before its extension
+      --resume            skip the frames of --frames that were already written
+      --denoise <AMOUNT>  denoise the image, 1 is a good starting point
+      --tone-map <NAME>   clamp, reinhard, reinhard-extended, aces or hable (default: clamp)
+      --white <VALUE>     white point of reinhard-extended (default: 4)
+      --exposure <STOPS>  exposure adjustment before tone mapping (default: 0)
+      --gamma <VALUE>     encode with a plain gamma curve (default: 2)
+      --srgb              encode with the sRGB transfer function instead of gamma
+  -h, --help              print this help and exit
+";
+
+#[derive(Debug)]
+pub struct UsageError(pub String);
+
+impl std::error::Error for UsageError {}
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Debug)]
+pub struct Options {
+    pub scene: String,
+    pub file: Option<String>,
+    pub width: Option<usize>,
+    pub height: Option<usize>,
+    pub samples_per_pixel: Option<usize>,
+    pub max_depth: Option<usize>,
+    pub threads: Option<usize>,
+    pub seed: Option<u64>,
+    pub camera: usize,
+    pub output: String,
+    pub export: Option<String>,
+    pub aovs: Option<String>,
+    pub compression: Compression,
+    pub frames: Option<(usize, usize)>,
+    pub resume: bool,
+    pub denoiser: Option<Denoiser>,
+    pub tone_mapper: ToneMapper,
+}
+
+#[derive(Debug)]
+pub enum Command {
+    Render(Box<Options>),
+    List,
+    Help,
+}
+
+fn parse_value<T: FromStr>(flag: &str, value: &str) -> Result<T, UsageError> {
+    value
+        .parse()
+        .map_err(|_| UsageError(format!("invalid value '{}' for {}", value, flag)))
+}
+
+// A range of frames such as `10-20`, or a single frame.
+fn parse_frames(flag: &str, value: &str) -> Result<(usize, usize), UsageError> {
+    let (first, last) = match value.split_once('-') {
+        Some((first, last)) => (parse_value(flag, first)?, parse_value(flag, last)?),
+        None => {
+            let frame = parse_value(flag, value)?;
+            (frame, frame)
+        }
+    };
+    if first > last {
+        return Err(UsageError(format!(
+            "{} must not end before it starts",
+            flag
+        )));
+    }
+    Ok((first, last))
+}
+
+fn parse_positive(flag: &str, value: &str) -> Result<usize, UsageError> {
+    match parse_value(flag, value)? {
+        0 => Err(UsageError(format!("{} must be at least 1", flag))),
+        n => Ok(n),
+    }
+}
+
+// Images are at least 2 pixels wide and high, as rays are spread from the
+// first pixel to the last.
+fn parse_size(flag: &str, value: &str) -> Result<usize, UsageError> {
+    match parse_value(flag, value)? {
+        0 | 1 => Err(UsageError(format!("{} must be at least 2", flag))),
+        n => Ok(n),
+    }
+}
+
+pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Command, UsageError> {
+    let mut options = Options {
+        scene: "two_spheres".to_string(),
+        file: None,
+        width: None,
+        height: None,
+        samples_per_pixel: None,
+        max_depth: None,
+        threads: None,
+        seed: None,
+        camera: 0,
+        output: "output.png".to_string(),
+        export: None,
+        aovs: None,
+        compression: Compression::Zip,
+        frames: None,
+        resume: false,
+        denoiser: None,
+        tone_mapper: ToneMapper::default(),
+    };
+    let mut operator = "clamp".to_string();
+    let mut white = 4.;
+    let mut exposure = 0.;
+    let mut transfer = Transfer::Gamma(2.);
+
+    let mut args = args.into_iter();
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-l" | "--list" => return Ok(Command::List),
+            "--srgb" => {
+                transfer = Transfer::Srgb;
+                continue;
+            }
+            "--resume" => {
+                options.resume = true;
+                continue;
+            }
+            _ => {}
+        }
+        let value = match flag.as_str() {
+            "-s" | "--scene" | "-f" | "--file" | "-W" | "--width" | "-H" | "--height" | "-n"
+            | "--samples" | "-d" | "--max-depth" | "-j" | "--threads" | "--seed" | "--camera"
+            | "-o" | "--output" | "--export" | "--aovs" | "--exr-compression" | "--frames"
+            | "--denoise" | "--tone-map" | "--white" | "--exposure" | "--gamma" => args
+                .next()
+                .ok_or_else(|| UsageError(format!("{} requires a value", flag)))?,
+            _ => return Err(UsageError(format!("unknown argument '{}'", flag))),
+        };
+        match flag.as_str() {
+            "-s" | "--scene" => options.scene = value,
+            "-f" | "--file" => options.file = Some(value),
+            "-W" | "--width" => options.width = Some(parse_size(&flag, &value)?),
+            "-H" | "--height" => options.height = Some(parse_size(&flag, &value)?),
+            "-n" | "--samples" => options.samples_per_pixel = Some(parse_positive(&flag, &value)?),
+            "-d" | "--max-depth" => options.max_depth = Some(parse_value(&flag, &value)?),
+            "-j" | "--threads" => options.threads = Some(parse_positive(&flag, &value)?),
+            "--seed" => options.seed = Some(parse_value(&flag, &value)?),
+            "--camera" => options.camera = parse_value(&flag, &value)?,
+            "-o" | "--output" => options.output = value,
+            "--export" => options.export = Some(value),
+            "--aovs" => options.aovs = Some(value),
+            "--exr-compression" => {
+                options.compression = match value.as_str() {
+                    "none" => Compression::None,
+                    "zip" => Compression::Zip,
+                    _ => return Err(UsageError(format!("unknown EXR compression '{}'", value))),
+                }
+            }
+            "--frames" => options.frames = Some(parse_frames(&flag, &value)?),
+            "--denoise" => options.denoiser = Some(Denoiser::new(parse_value(&flag, &value)?)),
+            "--tone-map" => operator = value,
+            "--white" => white = parse_value(&flag, &value)?,
+            "--exposure" => exposure = parse_value(&flag, &value)?,
+            _ => transfer = Transfer::Gamma(parse_value(&flag, &value)?),
+        }
+    }
+
+    let operator = match operator.as_str() {
+        "clamp" => Operator::Clamp,
+        "reinhard" => Operator::Reinhard,
+        "reinhard-extended" => Operator::ReinhardExtended(white),
+        "aces" => Operator::AcesFilmic,
+        "hable" => Operator::Hable,
+        _ => {
+            return Err(UsageError(format!(
+                "unknown tone mapping operator '{}'",
+                operator
+            )))
+        }
+    };
+    options.tone_mapper = ToneMapper::new(operator, exposure, transfer);
+    if options.frames.is_some() && options.file.is_none() {
+        return Err(UsageError("--frames needs a scene file".to_string()));
+    }
+    if options.frames.is_some() && options.export.is_some() {
+        return Err(UsageError(
+            "--frames can not be combined with --export".to_string(),
+        ));
+    }
+    if options.resume && options.frames.is_none() {
+        return Err(UsageError("--resume needs --frames".to_string()));
+    }
+    Ok(Command::Render(Box::new(options)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse, Command, Compression};
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_overrides() {
+        match parse(args("-s book_cover -W 300 --samples 16 --seed 3 -o a.exr")) {
+            Ok(Command::Render(options)) => {
+                assert_eq!(options.scene, "book_cover");
+                assert_eq!(options.width, Some(300));
+                assert_eq!(options.height, None);
+                assert_eq!(options.samples_per_pixel, Some(16));
+                assert_eq!(options.seed, Some(3));
+                assert_eq!(options.output, "a.exr");
+                assert_eq!(options.compression, Compression::Zip);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        match parse(args("-f a.scene --frames 3-12 --resume")) {
+            Ok(Command::Render(options)) => {
+                assert_eq!(options.frames, Some((3, 12)));
+                assert!(options.resume);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        match parse(args("-o a.exr --exr-compression none")) {
+            Ok(Command::Render(options)) => assert_eq!(options.compression, Compression::None),
+            other => panic!("unexpected {:?}", other),
+        }
+        match parse(args("-f a.scene --frames 7")) {
+            Ok(Command::Render(options)) => assert_eq!(options.frames, Some((7, 7))),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse(args("--width")).is_err());
+        assert!(parse(args("--width 0")).is_err());
+        assert!(parse(args("--width 1")).is_err());
+        assert!(parse(args("--height 1")).is_err());
+        assert!(parse(args("--samples many")).is_err());
+        assert!(parse(args("--tone-map sepia")).is_err());
+        assert!(parse(args("--exr-compression rle")).is_err());
+        assert!(parse(args("--frobnicate")).is_err());
+        assert!(parse(args("-f a.scene --frames 5-2")).is_err());
+        assert!(parse(args("-f a.scene --frames 1-x")).is_err());
+        assert!(parse(args("--frames 1-2")).is_err());
+        assert!(parse(args("-f a.scene --frames 1-2 --export b.scene")).is_err());
+        assert!(parse(args("-f a.scene --resume")).is_err());
+        assert!(matches!(parse(args("--list")), Ok(Command::List)));
+    }
+}
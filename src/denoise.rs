@@ -0,0 +1,203 @@
+use crate::aov::Aov;
+use crate::point::Point;
+use rayon::prelude::*;
+
+const KERNEL: [f64; 5] = [1. / 16., 1. / 4., 3. / 8., 1. / 4., 1. / 16.];
+
+// Edge avoiding à-trous wavelet filter (Dammertz et al. 2010). The albedo is
+// divided out before filtering so that texture detail survives, and the
+// first hit normal, albedo and depth stop the filter at geometric edges.
+#[derive(Clone, Copy, Debug)]
+pub struct Denoiser {
+    iterations: usize,
+    sigma_color: f64,
+    sigma_normal: f64,
+    sigma_albedo: f64,
+    sigma_depth: f64,
+}
+
+struct Features {
+    width: usize,
+    height: usize,
+    normal: Vec<Point>,
+    albedo: Vec<Point>,
+    depth: Vec<f64>,
+    hit: Vec<bool>,
+}
+
+fn demodulate(color: Point, albedo: Point) -> Point {
+    let eps = 1e-3;
+    Point::new(
+        color.x / albedo.x.max(eps),
+        color.y / albedo.y.max(eps),
+        color.z / albedo.z.max(eps),
+    )
+}
+
+impl Denoiser {
+    // `strength` scales how different two pixels may be in colour and still
+    // be averaged, 1 is a good default for previews.
+    pub fn new(strength: f64) -> Self {
+        Denoiser {
+            iterations: 5,
+            sigma_color: 0.25 * strength,
+            sigma_normal: 0.3,
+            sigma_albedo: 0.1,
+            sigma_depth: 0.05,
+        }
+    }
+
+    fn weight(&self, features: &Features, p: usize, q: usize, colors: &[Point], step: f64) -> f64 {
+        if features.hit[p] != features.hit[q] {
+            return 0.;
+        }
+        let color = (colors[p] - colors[q]).length_squared();
+        let sigma_color = self.sigma_color * self.sigma_color / step;
+        let mut exponent = color / sigma_color.max(1e-12);
+        if features.hit[p] {
+            let normal = (features.normal[p] - features.normal[q]).length_squared();
+            let albedo = (features.albedo[p] - features.albedo[q]).length_squared();
+            let depth = (features.depth[p] - features.depth[q]).abs()
+                / (features.depth[p].abs() * step).max(1e-6);
+            exponent += normal / (self.sigma_normal * self.sigma_normal)
+                + albedo / (self.sigma_albedo * self.sigma_albedo)
+                + depth / self.sigma_depth;
+        }
+        (-exponent).exp()
+    }
+
+    fn pass(&self, features: &Features, colors: &[Point], level: usize) -> Vec<Point> {
+        let (width, height) = (features.width as i64, features.height as i64);
+        let step = 1 << level;
+        (0..features.height)
+            .into_par_iter()
+            .flat_map_iter(|y| {
+                (0..features.width).map(move |x| {
+                    let p = y * features.width + x;
+                    let mut sum = Point::default();
+                    let mut total = 0.;
+                    for (j, kj) in KERNEL.iter().enumerate() {
+                        let qy = y as i64 + (j as i64 - 2) * step;
+                        if qy < 0 || qy >= height {
+                            continue;
+                        }
+                        for (i, ki) in KERNEL.iter().enumerate() {
+                            let qx = x as i64 + (i as i64 - 2) * step;
+                            if qx < 0 || qx >= width {
+                                continue;
+                            }
+                            let q = (qy * width + qx) as usize;
+                            let w = ki * kj * self.weight(features, p, q, colors, step as f64);
+                            sum = sum + colors[q] * w;
+                            total += w;
+                        }
+                    }
+                    sum / total
+                })
+            })
+            .collect()
+    }
+
+    // Filters a radiance buffer with the AOVs collected in the same render.
+    pub fn denoise(&self, radiance: &[Vec<Point>], aovs: &[Vec<Aov>]) -> Vec<Vec<Point>> {
+        let height = radiance.len();
+        let width = radiance.first().map_or(0, |row| row.len());
+        let aovs: Vec<&Aov> = aovs.iter().flatten().collect();
+        let features = Features {
+            width,
+            height,
+            normal: aovs.iter().map(|aov| aov.normal).collect(),
+            albedo: aovs.iter().map(|aov| aov.albedo).collect(),
+            depth: aovs.iter().map(|aov| aov.depth).collect(),
+            hit: aovs.iter().map(|aov| aov.is_hit()).collect(),
+        };
+
+        let mut colors: Vec<Point> = radiance
+            .iter()
+            .flatten()
+            .zip(&features.albedo)
+            .map(|(color, albedo)| demodulate(*color, *albedo))
+            .collect();
+        for level in 0..self.iterations {
+            colors = self.pass(&features, &colors, level);
+        }
+
+        colors
+            .iter()
+            .zip(&features.albedo)
+            .map(|(color, albedo)| {
+                let albedo = Point::new(albedo.x.max(1e-3), albedo.y.max(1e-3), albedo.z.max(1e-3));
+                *color * albedo
+            })
+            .collect::<Vec<Point>>()
+            .chunks(width)
+            .map(|row| row.to_vec())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Denoiser;
+    use crate::aov::Aov;
+    use crate::bodies::HitRecord;
+    use crate::point::Point;
+    use crate::random;
+    use rand::Rng;
+
+    #[test]
+    fn test_keeps_edges() {
+        // Two walls meeting at a vertical edge, a dark one facing the camera
+        // on the left and a bright one turned away on the right.
+        random::seed(4);
+        let mut rng = random::rng();
+        let (width, height) = (32, 16);
+        let side = |x: usize| {
+            if x < width / 2 {
+                (0.2, Point::new(0., 0., 1.))
+            } else {
+                (0.8, Point::new(1., 0., 0.))
+            }
+        };
+        let mut radiance = vec![];
+        let mut aovs = vec![];
+        for _ in 0..height {
+            let mut colors = vec![];
+            let mut row = vec![];
+            for x in 0..width {
+                let (brightness, normal) = side(x);
+                let noisy = brightness + rng.gen_range(-0.15..0.15);
+                colors.push(Point::new(noisy, noisy, noisy));
+                let mut aov = Aov::default();
+                aov.add_hit(1., &HitRecord::default());
+                aov.normal = normal;
+                aov.albedo = Point::new(1., 1., 1.);
+                row.push(aov);
+            }
+            radiance.push(colors);
+            aovs.push(row);
+        }
+
+        let denoised = Denoiser::new(1.).denoise(&radiance, &aovs);
+        // How far the pixels of each wall are from its true brightness.
+        let error = |image: &[Vec<Point>], left: bool| {
+            let mut sum = 0.;
+            for row in image {
+                for (x, color) in row.iter().enumerate() {
+                    if (x < width / 2) == left {
+                        sum += (color.x - side(x).0).powi(2);
+                    }
+                }
+            }
+            (sum / (width * height / 2) as f64).sqrt()
+        };
+        for left in [true, false] {
+            assert!(error(&denoised, left) < 0.3 * error(&radiance, left));
+        }
+        // Neither wall bleeds into the other at the edge.
+        for row in &denoised {
+            assert!((row[width / 2 - 1].x - 0.2).abs() < 0.05);
+            assert!((row[width / 2].x - 0.8).abs() < 0.1);
+        }
+    }
+}
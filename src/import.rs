@@ -0,0 +1,125 @@
+pub mod netpbm;
+pub mod obj;
+pub mod ply;
+
+use crate::bodies::bodyprops::Material;
+use crate::bodies::collision::AABB;
+use crate::bodies::{Body, BodyProps, HitRecord, Texture, BVH};
+use crate::raytracer::Ray;
+use crate::scenefile::writer::Export;
+use crate::scenefile::{Error, Item, Position, Value};
+use std::path::{Path, PathBuf};
+
+// Reads a file, returning its name for error messages with its contents.
+pub fn read(path: &Path) -> Result<(String, String), Error> {
+    let file = path.display().to_string();
+    let source = std::fs::read_to_string(path)
+        .map_err(|e| Error::without_position(&e.to_string()).in_file(&file))?;
+    Ok((file, source))
+}
+
+// Splits a line into whitespace separated words with their positions,
+// stopping at a `#` comment.
+pub fn words(line_number: usize, line: &str) -> Vec<(Position, &str)> {
+    let line = line.split('#').next().unwrap_or("");
+    let mut words = vec![];
+    let mut start = None;
+    for (i, c) in line.char_indices().chain([(line.len(), ' ')]) {
+        match (c.is_whitespace(), start) {
+            (false, None) => start = Some(i),
+            (true, Some(s)) => {
+                let column = line[..s].chars().count() + 1;
+                let position = Position {
+                    line: line_number,
+                    column,
+                };
+                words.push((position, &line[s..i]));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    words
+}
+
+// The bodies of a model file, which export as a reference to that file
+// rather than as the bodies themselves.
+#[derive(Debug)]
+pub struct Model {
+    kind: &'static str,
+    path: PathBuf,
+    object: Option<String>,
+    body_props: BodyProps,
+    world: BVH,
+}
+
+impl Model {
+    pub fn load_obj(
+        path: &Path,
+        object: Option<&str>,
+        body_props: BodyProps,
+    ) -> Result<Model, Error> {
+        let bodies = obj::load(path, object, body_props)?;
+        Ok(Model {
+            kind: "obj",
+            path: path.canonicalize().unwrap_or(path.to_path_buf()),
+            object: object.map(String::from),
+            body_props,
+            world: BVH::new(bodies),
+        })
+    }
+    pub fn load_ply(
+        path: &Path,
+        material: Material,
+        texture: Option<Texture>,
+    ) -> Result<Model, Error> {
+        let mesh = ply::load(path, material, texture)?;
+        Ok(Model {
+            kind: "ply",
+            path: path.canonicalize().unwrap_or(path.to_path_buf()),
+            object: None,
+            body_props: mesh.body_props(),
+            world: BVH::new(vec![Box::new(mesh)]),
+        })
+    }
+}
+
+impl Body for Model {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        self.world.hit(ray, t_min, t_max, rec)
+    }
+    fn bounding_box(&self) -> AABB {
+        self.world.bounding_box()
+    }
+}
+
+impl Export for Model {
+    fn to_items(&self, items: &mut Vec<Item>) -> Result<(), Error> {
+        let path = self.path.to_str().ok_or_else(|| {
+            Error::without_position(&format!("{} is not valid UTF-8", self.path.display()))
+        })?;
+        let item = Item::new(self.kind).field("path", Value::quoted(path));
+        let item = match &self.object {
+            Some(object) => item.field("object", Value::quoted(object)),
+            None => item,
+        };
+        items.push(item.body_props(&self.body_props));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::words;
+    use crate::scenefile::Position;
+
+    #[test]
+    fn test_words() {
+        let at = |column| Position { line: 3, column };
+        assert_eq!(
+            words(3, "f 1/2  3//4\t5 # comment"),
+            vec![(at(1), "f"), (at(3), "1/2"), (at(8), "3//4"), (at(13), "5")]
+        );
+        assert!(words(3, "  # only a comment").is_empty());
+    }
+}
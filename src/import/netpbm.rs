@@ -0,0 +1,114 @@
+use crate::bodies::heightfield::Grid;
+use crate::scenefile::Error;
+use std::path::Path;
+
+// Header numbers and ASCII samples, separated by whitespace and comments.
+struct Tokens<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Tokens<'a> {
+    fn next(&mut self) -> Option<&'a [u8]> {
+        let bytes = self.bytes;
+        loop {
+            while self.at < bytes.len() && bytes[self.at].is_ascii_whitespace() {
+                self.at += 1;
+            }
+            if bytes.get(self.at) != Some(&b'#') {
+                break;
+            }
+            while self.at < bytes.len() && bytes[self.at] != b'\n' {
+                self.at += 1;
+            }
+        }
+        let start = self.at;
+        while self.at < bytes.len() && !bytes[self.at].is_ascii_whitespace() {
+            self.at += 1;
+        }
+        (self.at > start).then(|| &bytes[start..self.at])
+    }
+    fn number(&mut self, what: &str) -> Result<usize, Error> {
+        self.next()
+            .and_then(|t| std::str::from_utf8(t).ok())
+            .and_then(|t| t.parse().ok())
+            .ok_or_else(|| Error::without_position(&format!("expected the {}", what)))
+    }
+}
+
+// Reads the grey values of a PGM image, or the brightness of a PPM image,
+// from 0 to 1, in ASCII (P2, P3) or binary (P5, P6) form.
+pub fn parse(bytes: &[u8]) -> Result<Grid, Error> {
+    let error = |message: &str| Error::without_position(message);
+    let mut tokens = Tokens { bytes, at: 0 };
+    let (channels, binary) = match tokens.next() {
+        Some(b"P2") => (1, false),
+        Some(b"P3") => (3, false),
+        Some(b"P5") => (1, true),
+        Some(b"P6") => (3, true),
+        _ => return Err(error("expected a PGM or PPM image")),
+    };
+    let columns = tokens.number("width")?;
+    let rows = tokens.number("height")?;
+    let largest = tokens.number("largest value")?;
+    if columns < 2 || rows < 2 {
+        return Err(error("a height map needs at least 2 by 2 pixels"));
+    }
+    if largest == 0 || largest > 65535 {
+        return Err(error("the largest value must be between 1 and 65535"));
+    }
+
+    let count = columns * rows * channels;
+    // A single whitespace separates the header from binary pixels, which
+    // take two bytes, most significant first, when they do not fit in one.
+    let samples: Vec<usize> = if binary {
+        let data = bytes.get(tokens.at + 1..).unwrap_or(&[]);
+        let size = if largest > 255 { 2 } else { 1 };
+        if data.len() < count * size {
+            return Err(error("the image ends early"));
+        }
+        data.chunks(size)
+            .take(count)
+            .map(|c| c.iter().fold(0, |sum, b| sum * 256 + *b as usize))
+            .collect()
+    } else {
+        (0..count)
+            .map(|_| tokens.number("next value"))
+            .collect::<Result<_, _>>()?
+    };
+    let heights = samples
+        .chunks(channels)
+        .map(|pixel| pixel.iter().sum::<usize>() as f64 / (channels * largest) as f64)
+        .collect();
+    Ok(Grid::new(columns, rows, heights))
+}
+
+pub fn load(path: &Path) -> Result<Grid, Error> {
+    let file = path.display().to_string();
+    let bytes =
+        std::fs::read(path).map_err(|e| Error::without_position(&e.to_string()).in_file(&file))?;
+    parse(&bytes).map_err(|e| e.in_file(&file))
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse;
+
+    #[test]
+    fn test_formats() {
+        let ascii = parse(b"P2\n# a ramp\n3 2\n4\n0 1 2\n2 3 4\n").unwrap();
+        let mut binary = b"P5 3 2 255\n".to_vec();
+        binary.extend([0, 64, 128, 128, 192, 255]);
+        let binary = parse(&binary).unwrap();
+        for grid in [ascii, binary] {
+            assert_eq!((grid.columns(), grid.rows()), (3, 2));
+            assert!((grid.height(1, 0) - 0.25).abs() < 0.01);
+            assert_eq!(grid.height(2, 1), 1.);
+        }
+        let color = parse(b"P3 2 2 255 255 0 0 0 0 0 0 0 0 255 255 255").unwrap();
+        assert_eq!(color.height(0, 0), 1. / 3.);
+
+        assert!(parse(b"P5 3 2 255\n\x00\x01").is_err());
+        assert!(parse(b"P7 3 2 255").is_err());
+    }
+}
@@ -0,0 +1,379 @@
+use crate::bodies::{Body, BodyProps, Texture, TriangleMesh};
+use crate::import::{read, words};
+use crate::point::Point;
+use crate::scenefile::{Error, Position};
+use std::collections::HashMap;
+use std::path::Path;
+
+// A material from an MTL file, with the defaults of the format.
+struct Material {
+    diffuse: Point,
+    specular: Point,
+    shininess: f64,
+    index_refraction: f64,
+    dissolve: f64,
+}
+
+impl Material {
+    fn default() -> Self {
+        Material {
+            diffuse: Point::new(0.8, 0.8, 0.8),
+            specular: Point::default(),
+            shininess: 0.,
+            index_refraction: 1.5,
+            dissolve: 1.,
+        }
+    }
+
+    // Transparent materials become glass. Materials that reflect more than
+    // they diffuse become metal, with a fuzziness that falls with the
+    // specular exponent. Everything else is matte.
+    fn body_props(&self) -> BodyProps {
+        let brightest = |c: Point| c.x.max(c.y).max(c.z);
+        if self.dissolve < 1. {
+            BodyProps::glass(self.index_refraction)
+        } else if brightest(self.specular) > brightest(self.diffuse) {
+            let fuzziness = (2. / (self.shininess.max(0.) + 2.)).sqrt();
+            BodyProps::metal(Texture::SolidColor(self.specular), fuzziness)
+        } else {
+            BodyProps::matte(Texture::SolidColor(self.diffuse))
+        }
+    }
+}
+
+fn number(word: &(Position, &str)) -> Result<f64, Error> {
+    word.1
+        .parse()
+        .map_err(|_| Error::new(word.0, &format!("invalid number '{}'", word.1)))
+}
+
+fn numbers(
+    position: Position,
+    words: &[(Position, &str)],
+    count: usize,
+) -> Result<Vec<f64>, Error> {
+    if words.len() < count {
+        return Err(Error::new(position, &format!("expected {} numbers", count)));
+    }
+    words.iter().map(number).collect()
+}
+
+fn color(position: Position, words: &[(Position, &str)]) -> Result<Point, Error> {
+    let c = numbers(position, words, 3)?;
+    Ok(Point::new(c[0], c[1], c[2]))
+}
+
+fn parse_mtl(source: &str) -> Result<HashMap<String, BodyProps>, Error> {
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, Material)> = None;
+    for (number, line) in source.lines().enumerate() {
+        let words = words(number + 1, line);
+        let Some(&(position, keyword)) = words.first() else {
+            continue;
+        };
+        let arguments = &words[1..];
+        if keyword == "newmtl" {
+            let name = arguments
+                .first()
+                .ok_or_else(|| Error::new(position, "newmtl needs a name"))?;
+            if let Some((name, material)) = current.take() {
+                materials.insert(name, material.body_props());
+            }
+            current = Some((name.1.to_string(), Material::default()));
+            continue;
+        }
+        let Some((_, material)) = current.as_mut() else {
+            continue;
+        };
+        match keyword {
+            "Kd" => material.diffuse = color(position, arguments)?,
+            "Ks" => material.specular = color(position, arguments)?,
+            "Ns" => material.shininess = numbers(position, arguments, 1)?[0],
+            "Ni" => material.index_refraction = numbers(position, arguments, 1)?[0],
+            "d" => material.dissolve = numbers(position, arguments, 1)?[0],
+            "Tr" => material.dissolve = 1. - numbers(position, arguments, 1)?[0],
+            // Texture maps, illumination models and the like are not supported.
+            _ => {}
+        }
+    }
+    if let Some((name, material)) = current {
+        materials.insert(name, material.body_props());
+    }
+    Ok(materials)
+}
+
+// One corner of a face, as indices into the position, texture coordinate
+// and normal lists.
+#[derive(Clone, Copy, Debug)]
+struct Corner {
+    position: usize,
+    uv: Option<usize>,
+    normal: Option<usize>,
+}
+
+// Turns a one based, or negative relative, OBJ index into a list index.
+fn index(word: (Position, &str), text: &str, length: usize) -> Result<usize, Error> {
+    let i: i64 = text
+        .parse()
+        .map_err(|_| Error::new(word.0, &format!("invalid index '{}'", text)))?;
+    let resolved = if i < 0 { length as i64 + i } else { i - 1 };
+    if i == 0 || resolved < 0 || resolved >= length as i64 {
+        return Err(Error::new(word.0, &format!("index {} is out of range", i)));
+    }
+    Ok(resolved as usize)
+}
+
+struct Lists {
+    positions: Vec<Point>,
+    uvs: Vec<(f64, f64)>,
+    normals: Vec<Point>,
+}
+
+impl Lists {
+    fn corner(&self, word: (Position, &str)) -> Result<Corner, Error> {
+        let mut parts = word.1.split('/');
+        let mut optional = |length| match parts.next() {
+            None | Some("") => Ok(None),
+            Some(text) => index(word, text, length).map(Some),
+        };
+        let position = optional(self.positions.len())?
+            .ok_or_else(|| Error::new(word.0, "face corner without a vertex"))?;
+        let uv = optional(self.uvs.len())?;
+        let normal = optional(self.normals.len())?;
+        Ok(Corner {
+            position,
+            uv,
+            normal,
+        })
+    }
+
+    // A mesh of `triangles` with one vertex for each distinct combination
+    // of position, texture coordinate and normal. Vertex normals and texture
+    // coordinates are used when all corners have them.
+    fn mesh(&self, triangles: &[[Corner; 3]], body_props: BodyProps) -> TriangleMesh {
+        let corners = || triangles.iter().flatten();
+        let with_uvs = corners().all(|corner| corner.uv.is_some());
+        let with_normals = corners().all(|corner| corner.normal.is_some());
+
+        let mut vertices: HashMap<(usize, Option<usize>, Option<usize>), u32> = HashMap::new();
+        let mut positions = vec![];
+        let mut uvs = vec![];
+        let mut normals = vec![];
+        let mut vertex = |corner: &Corner| {
+            let uv = corner.uv.filter(|_| with_uvs);
+            let normal = corner.normal.filter(|_| with_normals);
+            *vertices
+                .entry((corner.position, uv, normal))
+                .or_insert_with(|| {
+                    positions.push(self.positions[corner.position]);
+                    uvs.extend(uv.map(|i| self.uvs[i]));
+                    normals.extend(normal.map(|i| self.normals[i]));
+                    positions.len() as u32 - 1
+                })
+        };
+        let indices = triangles
+            .iter()
+            .map(|triangle| triangle.each_ref().map(&mut vertex))
+            .collect();
+
+        let mut mesh = TriangleMesh::new(positions, indices, body_props);
+        if with_uvs {
+            mesh = mesh.with_uvs(uvs);
+        }
+        if with_normals {
+            mesh = mesh.with_normals(normals);
+        }
+        mesh
+    }
+}
+
+// The triangles of an OBJ file, grouped by material.
+struct Faces {
+    lists: Lists,
+    groups: Vec<(BodyProps, Vec<[Corner; 3]>)>,
+}
+
+fn faces(
+    source: &str,
+    directory: &Path,
+    object: Option<&str>,
+    default: BodyProps,
+) -> Result<Faces, Error> {
+    let mut lists = Lists {
+        positions: vec![],
+        uvs: vec![],
+        normals: vec![],
+    };
+    let mut materials = HashMap::new();
+    let mut groups = vec![(default, vec![])];
+    let mut group_of_material: HashMap<String, usize> = HashMap::new();
+    let mut group = 0;
+    let mut names: (Option<String>, Option<String>) = (None, None);
+
+    for (number, line) in source.lines().enumerate() {
+        let words = words(number + 1, line);
+        let Some(&(position, keyword)) = words.first() else {
+            continue;
+        };
+        let arguments = &words[1..];
+        match keyword {
+            "v" => lists.positions.push(color(position, arguments)?),
+            "vn" => lists.normals.push(color(position, arguments)?),
+            "vt" => {
+                let uv = numbers(position, arguments, 1)?;
+                lists.uvs.push((uv[0], uv.get(1).copied().unwrap_or(0.)));
+            }
+            "o" => names = (arguments.first().map(|w| w.1.to_string()), None),
+            "g" => names.1 = arguments.first().map(|w| w.1.to_string()),
+            "mtllib" => {
+                for word in arguments {
+                    let (file, source) = read(&directory.join(word.1))?;
+                    let library = parse_mtl(&source).map_err(|e| e.in_file(&file))?;
+                    materials.extend(library);
+                }
+            }
+            "usemtl" => {
+                let name = arguments
+                    .first()
+                    .ok_or_else(|| Error::new(position, "usemtl needs a name"))?;
+                let body_props = *materials
+                    .get(name.1)
+                    .ok_or_else(|| Error::new(name.0, &format!("unknown material '{}'", name.1)))?;
+                group = *group_of_material
+                    .entry(name.1.to_string())
+                    .or_insert_with(|| {
+                        groups.push((body_props, vec![]));
+                        groups.len() - 1
+                    });
+            }
+            "f" => {
+                let corners = arguments
+                    .iter()
+                    .map(|&word| lists.corner(word))
+                    .collect::<Result<Vec<Corner>, Error>>()?;
+                if corners.len() < 3 {
+                    return Err(Error::new(position, "a face needs at least three corners"));
+                }
+                let selected = object.is_none_or(|object| {
+                    names.0.as_deref() == Some(object) || names.1.as_deref() == Some(object)
+                });
+                if !selected {
+                    continue;
+                }
+                // Polygons are split into a fan around their first corner.
+                for i in 1..corners.len() - 1 {
+                    groups[group]
+                        .1
+                        .push([corners[0], corners[i], corners[i + 1]]);
+                }
+            }
+            // Smoothing groups, lines, points and free-form geometry.
+            _ => {}
+        }
+    }
+    groups.retain(|(_, triangles)| !triangles.is_empty());
+    if groups.is_empty() {
+        return Err(Error::without_position(&match object {
+            Some(object) => format!("no faces in object '{}'", object),
+            None => "no faces".to_string(),
+        }));
+    }
+    Ok(Faces { lists, groups })
+}
+
+// Reads the faces of a Wavefront OBJ file as one mesh for each material.
+// Material libraries are looked up in `directory`. Faces before any `usemtl`
+// get `default`, and when `object` is given only the faces in the object or
+// group of that name are kept.
+pub fn parse(
+    source: &str,
+    directory: &Path,
+    object: Option<&str>,
+    default: BodyProps,
+) -> Result<Vec<Box<dyn Body>>, Error> {
+    let Faces { lists, groups } = faces(source, directory, object, default)?;
+    Ok(groups
+        .iter()
+        .map(|(body_props, triangles)| {
+            Box::new(lists.mesh(triangles, *body_props)) as Box<dyn Body>
+        })
+        .collect())
+}
+
+pub fn load(
+    path: &Path,
+    object: Option<&str>,
+    default: BodyProps,
+) -> Result<Vec<Box<dyn Body>>, Error> {
+    let (file, source) = read(path)?;
+    let directory = path.parent().unwrap_or(Path::new("."));
+    parse(&source, directory, object, default).map_err(|e| e.in_file(&file))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{faces, parse, parse_mtl};
+    use crate::bodies::bodyprops::Material;
+    use crate::bodies::BodyProps;
+    use crate::scenefile::Position;
+    use std::path::Path;
+
+    const QUADS: &str = "
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vn 0 0 1
+o floor
+f 1/1/1 2/1/1 3/1/1 4/1/1
+o wall
+f -4//1 -3//1 -2//1
+";
+
+    fn triangles(object: Option<&str>) -> usize {
+        let faces = faces(QUADS, Path::new("."), object, BodyProps::null()).unwrap();
+        faces
+            .groups
+            .iter()
+            .map(|(_, triangles)| triangles.len())
+            .sum()
+    }
+
+    #[test]
+    fn test_faces() {
+        assert_eq!(triangles(None), 3);
+        assert_eq!(triangles(Some("floor")), 2);
+        assert_eq!(triangles(Some("wall")), 1);
+        let error = parse(QUADS, Path::new("."), Some("roof"), BodyProps::null()).unwrap_err();
+        assert_eq!(error.message, "no faces in object 'roof'");
+    }
+
+    #[test]
+    fn test_errors() {
+        let error_at = |source: &str| {
+            parse(source, Path::new("."), None, BodyProps::null())
+                .unwrap_err()
+                .position
+        };
+        let at = |line, column| Some(Position { line, column });
+        assert_eq!(error_at("v 0 0 0\nf 1 2 3"), at(2, 5));
+        assert_eq!(error_at("v 0 0 0\nv 1 0 0\nf 1 2"), at(3, 1));
+        assert_eq!(error_at("v 0 0 x"), at(1, 7));
+        assert_eq!(error_at("v 0 0 0\nusemtl red"), at(2, 8));
+    }
+
+    #[test]
+    fn test_materials() {
+        let materials = parse_mtl(
+            "newmtl matte\nKd 0.5 0.2 0.2\nKs 0.1 0.1 0.1\n\
+             newmtl chrome\nKd 0.1 0.1 0.1\nKs 0.9 0.9 0.9\nNs 48\n\
+             newmtl glass\nNi 1.33\nd 0.2\n",
+        )
+        .unwrap();
+        let material = |name: &str| materials[name].material();
+        assert!(matches!(material("matte"), Material::Lambertian));
+        assert!(matches!(material("chrome"), Material::Metal(f) if f == 0.2));
+        assert!(matches!(material("glass"), Material::Dielectric(i) if i == 1.33));
+    }
+}
@@ -0,0 +1,525 @@
+use crate::bodies::bodyprops::Material;
+use crate::bodies::{BodyProps, Texture, TriangleMesh};
+use crate::import::words;
+use crate::point::Point;
+use crate::scenefile::{Error, Position};
+use crate::tonemap::Transfer;
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Scalar {
+    Int8,
+    Uint8,
+    Int16,
+    Uint16,
+    Int32,
+    Uint32,
+    Float32,
+    Float64,
+}
+
+impl Scalar {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "char" | "int8" => Some(Scalar::Int8),
+            "uchar" | "uint8" => Some(Scalar::Uint8),
+            "short" | "int16" => Some(Scalar::Int16),
+            "ushort" | "uint16" => Some(Scalar::Uint16),
+            "int" | "int32" => Some(Scalar::Int32),
+            "uint" | "uint32" => Some(Scalar::Uint32),
+            "float" | "float32" => Some(Scalar::Float32),
+            "double" | "float64" => Some(Scalar::Float64),
+            _ => None,
+        }
+    }
+    fn size(&self) -> usize {
+        match self {
+            Scalar::Int8 | Scalar::Uint8 => 1,
+            Scalar::Int16 | Scalar::Uint16 => 2,
+            Scalar::Int32 | Scalar::Uint32 | Scalar::Float32 => 4,
+            Scalar::Float64 => 8,
+        }
+    }
+    // Colours stored as integers span the whole range of the type.
+    fn color(&self, x: f64) -> f64 {
+        match self {
+            Scalar::Uint8 => Transfer::Srgb.decode(x / 255.),
+            Scalar::Uint16 => Transfer::Srgb.decode(x / 65535.),
+            _ => x,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Kind {
+    Scalar(Scalar),
+    // The type of the length and of the items of a list.
+    List(Scalar, Scalar),
+}
+
+#[derive(Debug)]
+struct Property {
+    name: String,
+    kind: Kind,
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+fn parse_header(header: &str) -> Result<(Format, Vec<Element>), Error> {
+    let mut format = None;
+    let mut elements: Vec<Element> = vec![];
+    for (number, line) in header.lines().enumerate() {
+        let words = words(number + 1, line);
+        let Some(&(position, keyword)) = words.first() else {
+            continue;
+        };
+        let argument = |i: usize| {
+            words
+                .get(i)
+                .map(|w| w.1)
+                .ok_or_else(|| Error::new(position, &format!("incomplete {} line", keyword)))
+        };
+        let scalar = |i: usize| {
+            let name = argument(i)?;
+            Scalar::from_name(name)
+                .ok_or_else(|| Error::new(words[i].0, &format!("unknown type '{}'", name)))
+        };
+        match keyword {
+            "ply" if number == 0 => {}
+            _ if number == 0 => return Err(Error::new(position, "not a PLY file")),
+            "format" => {
+                format = Some(match argument(1)? {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    other => {
+                        let message = format!("unknown format '{}'", other);
+                        return Err(Error::new(words[1].0, &message));
+                    }
+                })
+            }
+            "element" => elements.push(Element {
+                name: argument(1)?.to_string(),
+                count: argument(2)?
+                    .parse()
+                    .map_err(|_| Error::new(words[2].0, "expected a count"))?,
+                properties: vec![],
+            }),
+            "property" => {
+                let (kind, name) = match argument(1)? {
+                    "list" => (Kind::List(scalar(2)?, scalar(3)?), argument(4)?),
+                    _ => (Kind::Scalar(scalar(1)?), argument(2)?),
+                };
+                elements
+                    .last_mut()
+                    .ok_or_else(|| Error::new(position, "property outside of an element"))?
+                    .properties
+                    .push(Property {
+                        name: name.to_string(),
+                        kind,
+                    });
+            }
+            "comment" | "obj_info" | "end_header" => {}
+            _ => {
+                let message = format!("unexpected '{}' in the header", keyword);
+                return Err(Error::new(position, &message));
+            }
+        }
+    }
+    let format = format.ok_or_else(|| Error::without_position("the header has no format"))?;
+    Ok((format, elements))
+}
+
+// Hands out the numbers of the body of the file one by one.
+trait Values {
+    fn next(&mut self, scalar: Scalar) -> Result<f64, Error>;
+    // An error about the number handed out last.
+    fn error(&self, message: &str) -> Error;
+
+    // A list length or vertex index, which has to be a whole number that is
+    // not negative.
+    fn index(&mut self, scalar: Scalar) -> Result<usize, Error> {
+        let value = self.next(scalar)?;
+        if value < 0. || value.fract() != 0. {
+            let message = format!("expected an index or length, got {}", value);
+            return Err(self.error(&message));
+        }
+        Ok(value as usize)
+    }
+}
+
+struct Ascii<'a> {
+    lines: std::iter::Enumerate<std::str::Lines<'a>>,
+    first_line: usize,
+    words: Vec<(Position, &'a str)>,
+    next: usize,
+}
+
+impl Values for Ascii<'_> {
+    fn next(&mut self, _scalar: Scalar) -> Result<f64, Error> {
+        while self.next == self.words.len() {
+            let (number, line) = self
+                .lines
+                .next()
+                .ok_or_else(|| Error::without_position("unexpected end of file"))?;
+            self.words = words(self.first_line + number, line);
+            self.next = 0;
+        }
+        let (position, word) = self.words[self.next];
+        self.next += 1;
+        word.parse()
+            .map_err(|_| Error::new(position, &format!("invalid number '{}'", word)))
+    }
+    fn error(&self, message: &str) -> Error {
+        Error::new(self.words[self.next - 1].0, message)
+    }
+}
+
+struct Binary<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    // Where the number handed out last starts, from the start of the body.
+    last: usize,
+    big_endian: bool,
+}
+
+impl Values for Binary<'_> {
+    fn next(&mut self, scalar: Scalar) -> Result<f64, Error> {
+        let size = scalar.size();
+        let mut bytes = [0u8; 8];
+        bytes[..size].copy_from_slice(
+            self.bytes
+                .get(self.offset..self.offset + size)
+                .ok_or_else(|| Error::without_position("unexpected end of file"))?,
+        );
+        self.last = self.offset;
+        self.offset += size;
+        if self.big_endian {
+            bytes[..size].reverse();
+        }
+        let [b0, b1, b2, b3, ..] = bytes;
+        Ok(match scalar {
+            Scalar::Int8 => b0 as i8 as f64,
+            Scalar::Uint8 => b0 as f64,
+            Scalar::Int16 => i16::from_le_bytes([b0, b1]) as f64,
+            Scalar::Uint16 => u16::from_le_bytes([b0, b1]) as f64,
+            Scalar::Int32 => i32::from_le_bytes([b0, b1, b2, b3]) as f64,
+            Scalar::Uint32 => u32::from_le_bytes([b0, b1, b2, b3]) as f64,
+            Scalar::Float32 => f32::from_le_bytes([b0, b1, b2, b3]) as f64,
+            Scalar::Float64 => f64::from_le_bytes(bytes),
+        })
+    }
+    fn error(&self, message: &str) -> Error {
+        let message = format!("{} at byte {} of the body", message, self.last);
+        Error::without_position(&message)
+    }
+}
+
+// The vertex properties that are read, by the names they commonly go by.
+const ATTRIBUTES: [&[&str]; 11] = [
+    &["x"],
+    &["y"],
+    &["z"],
+    &["nx"],
+    &["ny"],
+    &["nz"],
+    &["u", "s", "texture_u", "texture_s"],
+    &["v", "t", "texture_v", "texture_t"],
+    &["red", "diffuse_red"],
+    &["green", "diffuse_green"],
+    &["blue", "diffuse_blue"],
+];
+
+// Reads the vertices and faces of a PLY file into a mesh. Without a
+// `texture` meshes with vertex colours show them, and other meshes are grey.
+pub fn parse(
+    bytes: &[u8],
+    material: Material,
+    texture: Option<Texture>,
+) -> Result<TriangleMesh, Error> {
+    // The header ends with the line that starts with `end_header`, and the
+    // body may be binary, so the lines are split off the bytes one by one.
+    let mut start = 0;
+    let body = loop {
+        if start >= bytes.len() {
+            return Err(Error::without_position("not a PLY file"));
+        }
+        let end = bytes[start..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(bytes.len(), |i| start + i + 1);
+        let mut words = bytes[start..end]
+            .split(|b| b.is_ascii_whitespace())
+            .filter(|w| !w.is_empty());
+        if words.next() == Some(&b"end_header"[..]) {
+            break end;
+        }
+        start = end;
+    };
+    let header = std::str::from_utf8(&bytes[..body])
+        .map_err(|_| Error::without_position("the header is not text"))?;
+    let (format, elements) = parse_header(header)?;
+
+    let text;
+    let mut values: Box<dyn Values + '_> = match format {
+        Format::Ascii => {
+            text = std::str::from_utf8(&bytes[body..])
+                .map_err(|_| Error::without_position("the file is not text"))?;
+            Box::new(Ascii {
+                lines: text.lines().enumerate(),
+                first_line: header.lines().count() + 1,
+                words: vec![],
+                next: 0,
+            })
+        }
+        _ => Box::new(Binary {
+            bytes: &bytes[body..],
+            offset: 0,
+            last: 0,
+            big_endian: format == Format::BinaryBigEndian,
+        }),
+    };
+
+    let mut vertices: Vec<[f64; 11]> = vec![];
+    let mut present = [false; 11];
+    let mut color_type = Scalar::Float32;
+    let mut faces: Vec<Vec<usize>> = vec![];
+    for element in &elements {
+        // Which attribute each property holds, if any.
+        let slots: Vec<Option<usize>> = element
+            .properties
+            .iter()
+            .map(|p| {
+                ATTRIBUTES
+                    .iter()
+                    .position(|names| names.contains(&p.name.as_str()))
+            })
+            .collect();
+        let is_vertex = element.name == "vertex";
+        let is_face = element.name == "face";
+        if is_vertex {
+            for (property, slot) in element.properties.iter().zip(&slots) {
+                if let (Some(slot), Kind::Scalar(scalar)) = (slot, property.kind) {
+                    present[*slot] = true;
+                    if *slot >= 8 {
+                        color_type = scalar;
+                    }
+                }
+            }
+        }
+        for _ in 0..element.count {
+            let mut vertex = [0.; 11];
+            for (property, slot) in element.properties.iter().zip(&slots) {
+                match property.kind {
+                    Kind::Scalar(scalar) => {
+                        let value = values.next(scalar)?;
+                        if let (true, Some(slot)) = (is_vertex, slot) {
+                            vertex[*slot] = value;
+                        }
+                    }
+                    Kind::List(length, item) => {
+                        let length = values.index(length)?;
+                        let list =
+                            (0..length)
+                                .map(|_| values.index(item))
+                                .collect::<Result<Vec<usize>, Error>>()?;
+                        let indices = ["vertex_indices", "vertex_index"];
+                        if is_face && indices.contains(&property.name.as_str()) {
+                            faces.push(list);
+                        }
+                    }
+                }
+            }
+            if is_vertex {
+                vertices.push(vertex);
+            }
+        }
+    }
+
+    if !present[..3].iter().all(|&p| p) {
+        return Err(Error::without_position("vertices need x, y and z"));
+    }
+    let mut indices = vec![];
+    for (i, face) in faces.iter().enumerate() {
+        if face.len() < 3 {
+            let message = format!("face {} has fewer than three vertices", i);
+            return Err(Error::without_position(&message));
+        }
+        if let Some(index) = face.iter().find(|&&index| index >= vertices.len()) {
+            let message = format!("face {} has vertex index {} out of range", i, index);
+            return Err(Error::without_position(&message));
+        }
+        // Polygons are split into a fan around their first vertex.
+        for j in 1..face.len() - 1 {
+            indices.push([face[0], face[j], face[j + 1]].map(|i| i as u32));
+        }
+    }
+    if indices.is_empty() {
+        return Err(Error::without_position("no faces"));
+    }
+
+    let point = |v: &[f64; 11], i: usize| Point::new(v[i], v[i + 1], v[i + 2]);
+    let with_colors = present[8..].iter().all(|&p| p);
+    let texture = texture.unwrap_or(if with_colors {
+        Texture::VertexColor(Point::new(1., 1., 1.))
+    } else {
+        Texture::new_color(0.8, 0.8, 0.8)
+    });
+    let positions = vertices.iter().map(|v| point(v, 0)).collect();
+    let mut mesh = TriangleMesh::new(positions, indices, BodyProps::new(texture, material));
+    if present[3..6].iter().all(|&p| p) {
+        mesh = mesh.with_normals(vertices.iter().map(|v| point(v, 3)).collect());
+    }
+    if present[6..8].iter().all(|&p| p) {
+        mesh = mesh.with_uvs(vertices.iter().map(|v| (v[6], v[7])).collect());
+    }
+    if with_colors {
+        let color = |v: &[f64; 11]| {
+            let [r, g, b] = [v[8], v[9], v[10]].map(|c| color_type.color(c));
+            Point::new(r, g, b)
+        };
+        mesh = mesh.with_colors(vertices.iter().map(color).collect());
+    }
+    Ok(mesh)
+}
+
+pub fn load(
+    path: &Path,
+    material: Material,
+    texture: Option<Texture>,
+) -> Result<TriangleMesh, Error> {
+    let file = path.display().to_string();
+    let bytes =
+        std::fs::read(path).map_err(|e| Error::without_position(&e.to_string()).in_file(&file))?;
+    parse(&bytes, material, texture).map_err(|e| e.in_file(&file))
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse;
+    use crate::bodies::bodyprops::Material;
+    use crate::bodies::{Body, HitRecord, Texture, TriangleMesh};
+    use crate::point::Point;
+    use crate::raytracer::Ray;
+    use crate::scenefile::Position;
+
+    const HEADER: &str = "ply
+format {} 1.0
+comment a red and blue square
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+";
+    const VERTICES: [([f32; 3], [u8; 3]); 4] = [
+        ([0., 0., 0.], [255, 0, 0]),
+        ([1., 0., 0.], [255, 0, 0]),
+        ([1., 1., 0.], [0, 0, 255]),
+        ([0., 1., 0.], [0, 0, 255]),
+    ];
+
+    fn ascii() -> Vec<u8> {
+        let mut text = HEADER.replace("{}", "ascii");
+        for (p, c) in VERTICES {
+            text += &format!("{} {} {} {} {} {}\n", p[0], p[1], p[2], c[0], c[1], c[2]);
+        }
+        text += "4 0 1 2 3\n";
+        text.into_bytes()
+    }
+
+    fn binary() -> Vec<u8> {
+        let mut bytes = HEADER.replace("{}", "binary_little_endian").into_bytes();
+        for (p, c) in VERTICES {
+            bytes.extend(p.iter().flat_map(|x| x.to_le_bytes()));
+            bytes.extend(c);
+        }
+        bytes.push(4);
+        bytes.extend([0i32, 1, 2, 3].iter().flat_map(|i| i.to_le_bytes()));
+        bytes
+    }
+
+    fn color_at(mesh: &TriangleMesh, x: f64, y: f64) -> Point {
+        let ray = Ray::new(Point::new(x, y, 1.), Point::new(0., 0., -1.));
+        let mut rec = HitRecord::default();
+        assert!(mesh.hit(&ray, 0.001, f64::INFINITY, &mut rec));
+        match rec.body_props.texture() {
+            Texture::VertexColor(color) => color,
+            texture => panic!("unexpected texture {:?}", texture),
+        }
+    }
+
+    #[test]
+    fn test_vertex_colors() {
+        for bytes in [ascii(), binary()] {
+            let mesh = parse(&bytes, Material::Lambertian, None).unwrap();
+            let bottom = color_at(&mesh, 0.5, 0.2);
+            assert!((bottom - Point::new(0.8, 0., 0.2)).length() < 1e-9);
+            for x in [0.3, 0.7] {
+                let middle = color_at(&mesh, x, 0.5);
+                assert!((middle - Point::new(0.5, 0., 0.5)).length() < 1e-9);
+            }
+        }
+    }
+    #[test]
+    fn test_header_comment() {
+        // Only a line starting with end_header ends the header.
+        for bytes in [ascii(), binary()] {
+            let text = String::from_utf8_lossy(&bytes).into_owned();
+            let comment = "comment written before end_header\n";
+            let mut bytes = bytes.clone();
+            let at = text.find("element").unwrap();
+            bytes.splice(at..at, comment.bytes());
+            let mesh = parse(&bytes, Material::Lambertian, None).unwrap();
+            assert!((color_at(&mesh, 0.5, 0.2) - Point::new(0.8, 0., 0.2)).length() < 1e-9);
+        }
+    }
+    #[test]
+    fn test_errors() {
+        let error = |bytes: &[u8]| parse(bytes, Material::Lambertian, None).unwrap_err();
+        let ascii = String::from_utf8(ascii()).unwrap();
+        let at = |line, column| Some(Position { line, column });
+        assert_eq!(error(b"solid\nend_header\n").position, at(1, 1));
+        let bad_number = ascii.replace("1 1 0 0", "1 x 0 0");
+        assert_eq!(error(bad_number.as_bytes()).position, at(16, 3));
+        let bad_index = ascii.replace("4 0 1 2 3", "4 0 1 2 9");
+        assert_eq!(
+            error(bad_index.as_bytes()).message,
+            "face 0 has vertex index 9 out of range"
+        );
+        let negative = ascii.replace("4 0 1 2 3", "4 0 -1 2 3");
+        let error_at = |bytes: &[u8]| error(bytes).position;
+        assert_eq!(error_at(negative.as_bytes()), at(18, 5));
+        let fraction = ascii.replace("4 0 1 2 3", "2.5 0 1 2 3");
+        assert_eq!(error_at(fraction.as_bytes()), at(18, 1));
+        let mut negative = binary();
+        let last = negative.len() - 4;
+        negative[last..].copy_from_slice(&(-3i32).to_le_bytes());
+        assert_eq!(
+            error(&negative).message,
+            "expected an index or length, got -3 at byte 73 of the body"
+        );
+        let truncated = binary();
+        assert_eq!(
+            error(&truncated[..truncated.len() - 2]).message,
+            "unexpected end of file"
+        );
+    }
+}
@@ -1,13 +1,180 @@
-use std::fs::File;
-use std::io::Write;
 extern crate ordered_float;
+mod animation;
+mod aov;
 mod bodies;
+mod cli;
+mod denoise;
+mod import;
+mod matrix;
+mod noise;
+mod output;
 mod point;
+mod polynomial;
+mod random;
 mod raytracer;
+mod scene;
+mod scenefile;
 mod scenes;
-fn main() -> std::io::Result<()> {
-    let tracer = scenes::two_spheres();
-    let mut file = File::create("output.ppm")?;
-    file.write_all(&tracer.image().as_bytes().to_vec())?;
+mod sdf;
+mod tonemap;
+use cli::{Command, Options, UsageError};
+use raytracer::Tracer;
+use scene::{RenderSettings, Scene};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::process::ExitCode;
+
+// Loads the scene as it is during `frame`, with the settings of the command
+// line applied.
+fn load(options: &Options, frame: usize) -> Result<(Scene, RenderSettings), Box<dyn Error>> {
+    if let Some(seed) = options.seed {
+        random::seed(seed);
+    }
+    let mut scene = match &options.file {
+        Some(path) => scenefile::load(path.as_ref(), frame)?,
+        None => scenes::by_name(&options.scene).ok_or_else(|| {
+            UsageError(format!(
+                "unknown scene '{}', use --list to see the available scenes",
+                options.scene
+            ))
+        })?,
+    };
+    if scene.select_camera(options.camera).is_none() {
+        let name = options.file.as_ref().unwrap_or(&options.scene);
+        return Err(
+            UsageError(format!("scene '{}' has no camera {}", name, options.camera)).into(),
+        );
+    }
+
+    let mut settings = *scene.settings();
+    let aspect_ratio = settings.aspect_ratio();
+    match (options.width, options.height) {
+        (Some(width), Some(height)) => (settings.width, settings.height) = (width, height),
+        (Some(width), None) => {
+            settings.width = width;
+            settings.height = ((width as f64 / aspect_ratio) as usize).max(2);
+        }
+        (None, Some(height)) => {
+            settings.width = ((height as f64 * aspect_ratio) as usize).max(2);
+            settings.height = height;
+        }
+        (None, None) => {}
+    }
+    settings.samples_per_pixel = options
+        .samples_per_pixel
+        .unwrap_or(settings.samples_per_pixel);
+    settings.max_depth = options.max_depth.unwrap_or(settings.max_depth);
+    settings.seed = options.seed.or(settings.seed);
+    Ok((scene, settings))
+}
+
+fn render_image(
+    options: &Options,
+    scene: &Scene,
+    settings: &RenderSettings,
+    output: &str,
+    aovs: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    let mut tracer = Tracer::new();
+    tracer.set_tone_mapper(options.tone_mapper);
+    tracer.set_compression(options.compression);
+    if aovs.is_some() {
+        tracer.enable_aovs();
+    }
+    if let Some(denoiser) = options.denoiser {
+        tracer.set_denoiser(denoiser);
+    }
+    tracer.render(scene, settings);
+    tracer.save(output)?;
+    if let Some(path) = aovs {
+        tracer.save_aovs(path)?;
+    }
+    Ok(())
+}
+
+// Renders each frame to its own numbered image. An image only appears once
+// its frame is done, so resuming starts again at the first one missing.
+fn render_frames(options: &Options, (first, last): (usize, usize)) -> Result<(), Box<dyn Error>> {
+    let first = if options.resume {
+        (first..=last).find(|&frame| !Path::new(&output::numbered(&options.output, frame)).exists())
+    } else {
+        Some(first)
+    };
+    let Some(first) = first else {
+        return Ok(());
+    };
+    for frame in first..=last {
+        let (scene, settings) = load(options, frame)?;
+        let path = output::numbered(&options.output, frame);
+        let aovs = options
+            .aovs
+            .as_ref()
+            .map(|path| output::numbered(path, frame));
+        let partial = output::partial(&path);
+        render_image(options, &scene, &settings, &partial, aovs.as_deref())?;
+        fs::rename(&partial, &path)?;
+    }
     Ok(())
 }
+
+fn render(options: &Options) -> Result<(), Box<dyn Error>> {
+    if let Some(threads) = options.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()?;
+    }
+    // Fail on an unsupported output before spending time on the render.
+    if options.export.is_none() {
+        for path in [Some(&options.output), options.aovs.as_ref()]
+            .into_iter()
+            .flatten()
+        {
+            output::ImageFormat::from_path(path.as_ref()).map_err(|e| UsageError(e.to_string()))?;
+        }
+    }
+    if let Some(frames) = options.frames {
+        return render_frames(options, frames);
+    }
+
+    let (scene, settings) = load(options, 0)?;
+    if let Some(path) = &options.export {
+        return Ok(scenefile::save(path.as_ref(), &scene, &settings)?);
+    }
+    render_image(
+        options,
+        &scene,
+        &settings,
+        &options.output,
+        options.aovs.as_deref(),
+    )
+}
+
+fn main() -> ExitCode {
+    let command = match cli::parse(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(error) => {
+            eprintln!("error: {}\n\n{}", error, cli::USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    match command {
+        Command::Help => print!("{}", cli::USAGE),
+        Command::List => {
+            for (name, _) in scenes::SCENES {
+                println!("{}", name);
+            }
+        }
+        Command::Render(options) => {
+            if let Err(error) = render(&options) {
+                eprintln!("error: {}", error);
+                return if error.is::<UsageError>() {
+                    ExitCode::from(2)
+                } else {
+                    ExitCode::FAILURE
+                };
+            }
+        }
+    }
+    ExitCode::SUCCESS
+}
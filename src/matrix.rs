@@ -0,0 +1,356 @@
+use crate::point::Point;
+use std::ops;
+
+// An affine transform. The last row is kept so that transforms compose with
+// plain matrix products, but it is always (0, 0, 0, 1).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Matrix4 {
+    rows: [[f64; 4]; 4],
+}
+
+impl Matrix4 {
+    pub fn new(rows: [[f64; 4]; 4]) -> Self {
+        Matrix4 { rows }
+    }
+    pub fn identity() -> Self {
+        Matrix4::scaling(Point::new(1., 1., 1.))
+    }
+    pub fn translation(offset: Point) -> Self {
+        Matrix4::new([
+            [1., 0., 0., offset.x],
+            [0., 1., 0., offset.y],
+            [0., 0., 1., offset.z],
+            [0., 0., 0., 1.],
+        ])
+    }
+    pub fn scaling(factors: Point) -> Self {
+        Matrix4::new([
+            [factors.x, 0., 0., 0.],
+            [0., factors.y, 0., 0.],
+            [0., 0., factors.z, 0.],
+            [0., 0., 0., 1.],
+        ])
+    }
+    // A counterclockwise rotation by `degrees` around `axis`, looking down
+    // the axis towards the origin.
+    pub fn rotation(axis: Point, degrees: f64) -> Self {
+        let Point { x, y, z } = axis.unit_vector();
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let t = 1. - cos;
+        Matrix4::new([
+            [
+                t * x * x + cos,
+                t * x * y - sin * z,
+                t * x * z + sin * y,
+                0.,
+            ],
+            [
+                t * x * y + sin * z,
+                t * y * y + cos,
+                t * y * z - sin * x,
+                0.,
+            ],
+            [
+                t * x * z - sin * y,
+                t * y * z + sin * x,
+                t * z * z + cos,
+                0.,
+            ],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    pub fn rows(&self) -> [[f64; 4]; 4] {
+        self.rows
+    }
+    pub fn transpose(&self) -> Self {
+        let mut rows = [[0.; 4]; 4];
+        for (i, row) in rows.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.rows[j][i];
+            }
+        }
+        Matrix4::new(rows)
+    }
+    // Gauss-Jordan elimination with partial pivoting, `None` if the matrix
+    // squashes space flat.
+    pub fn inverse(&self) -> Option<Self> {
+        let mut a = self.rows;
+        let mut inverse = Matrix4::identity().rows;
+        for column in 0..4 {
+            let pivot = (column..4)
+                .max_by(|&i, &j| a[i][column].abs().total_cmp(&a[j][column].abs()))
+                .unwrap();
+            if a[pivot][column].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(column, pivot);
+            inverse.swap(column, pivot);
+            let scale = a[column][column];
+            for j in 0..4 {
+                a[column][j] /= scale;
+                inverse[column][j] /= scale;
+            }
+            for i in (0..4).filter(|&i| i != column) {
+                let factor = a[i][column];
+                for j in 0..4 {
+                    a[i][j] -= factor * a[column][j];
+                    inverse[i][j] -= factor * inverse[column][j];
+                }
+            }
+        }
+        Some(Matrix4::new(inverse))
+    }
+
+    pub fn determinant(&self) -> f64 {
+        let m = self.rows;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+    pub fn transform_point(&self, p: Point) -> Point {
+        self.transform_vector(p) + Point::new(self.rows[0][3], self.rows[1][3], self.rows[2][3])
+    }
+    // Directions are not moved by the translation part.
+    pub fn transform_vector(&self, v: Point) -> Point {
+        let row = |r: [f64; 4]| r[0] * v.x + r[1] * v.y + r[2] * v.z;
+        Point::new(row(self.rows[0]), row(self.rows[1]), row(self.rows[2]))
+    }
+}
+
+// A rotation as a unit quaternion (w, x, y, z).
+#[derive(Debug, Clone, Copy)]
+struct Quaternion {
+    w: f64,
+    v: Point,
+}
+
+impl Quaternion {
+    fn from_matrix(m: &Matrix4) -> Self {
+        let m = m.rows;
+        let trace = m[0][0] + m[1][1] + m[2][2];
+        // Start from the largest component, so nothing is divided by almost
+        // nothing.
+        let (w, x, y, z) = if trace > 0. {
+            let s = 2. * (trace + 1.).sqrt();
+            let (x, y, z) = (m[2][1] - m[1][2], m[0][2] - m[2][0], m[1][0] - m[0][1]);
+            (s / 4., x / s, y / s, z / s)
+        } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+            let s = 2. * (1. + m[0][0] - m[1][1] - m[2][2]).sqrt();
+            let (w, y, z) = (m[2][1] - m[1][2], m[0][1] + m[1][0], m[0][2] + m[2][0]);
+            (w / s, s / 4., y / s, z / s)
+        } else if m[1][1] > m[2][2] {
+            let s = 2. * (1. + m[1][1] - m[0][0] - m[2][2]).sqrt();
+            let (w, x, z) = (m[0][2] - m[2][0], m[0][1] + m[1][0], m[1][2] + m[2][1]);
+            (w / s, x / s, s / 4., z / s)
+        } else {
+            let s = 2. * (1. + m[2][2] - m[0][0] - m[1][1]).sqrt();
+            let (w, x, y) = (m[1][0] - m[0][1], m[0][2] + m[2][0], m[1][2] + m[2][1]);
+            (w / s, x / s, y / s, s / 4.)
+        };
+        Quaternion {
+            w,
+            v: Point::new(x, y, z),
+        }
+    }
+    fn to_matrix(self) -> Matrix4 {
+        let (w, Point { x, y, z }) = (self.w, self.v);
+        Matrix4::new([
+            [
+                1. - 2. * (y * y + z * z),
+                2. * (x * y - w * z),
+                2. * (x * z + w * y),
+                0.,
+            ],
+            [
+                2. * (x * y + w * z),
+                1. - 2. * (x * x + z * z),
+                2. * (y * z - w * x),
+                0.,
+            ],
+            [
+                2. * (x * z - w * y),
+                2. * (y * z + w * x),
+                1. - 2. * (x * x + y * y),
+                0.,
+            ],
+            [0., 0., 0., 1.],
+        ])
+    }
+    fn dot(&self, other: &Quaternion) -> f64 {
+        self.w * other.w + self.v.dot(other.v)
+    }
+    // Turns at a steady rate the shorter way round.
+    fn slerp(&self, other: &Quaternion, s: f64) -> Self {
+        let (cos, other) = if self.dot(other) < 0. {
+            (
+                -self.dot(other),
+                Quaternion {
+                    w: -other.w,
+                    v: -other.v,
+                },
+            )
+        } else {
+            (self.dot(other), *other)
+        };
+        let (a, b) = if cos > 1. - 1e-9 {
+            (1. - s, s)
+        } else {
+            let angle = cos.acos();
+            let sin = angle.sin();
+            (((1. - s) * angle).sin() / sin, (s * angle).sin() / sin)
+        };
+        let (w, v) = (a * self.w + b * other.w, self.v * a + other.v * b);
+        let length = (w * w + v.length_squared()).sqrt();
+        Quaternion {
+            w: w / length,
+            v: v * (1. / length),
+        }
+    }
+}
+
+// An affine transform split into a translation, a rotation and a stretch,
+// which are blended one by one so that turns stay turns.
+#[derive(Debug, Clone, Copy)]
+pub struct Decomposed {
+    translation: Point,
+    rotation: Quaternion,
+    stretch: Matrix4,
+}
+
+impl Decomposed {
+    pub fn new(m: &Matrix4) -> Self {
+        let rows = m.rows;
+        let translation = Point::new(rows[0][3], rows[1][3], rows[2][3]);
+        let linear = Matrix4::translation(-translation) * *m;
+        // The polar decomposition: averaging a matrix with its inverse
+        // transpose converges to the nearest rotation.
+        let mut rotation = linear;
+        for _ in 0..100 {
+            let Some(inverse) = rotation.inverse() else {
+                break;
+            };
+            let inverse_transpose = inverse.transpose().rows;
+            let mut next = rotation.rows;
+            for (row, other) in next.iter_mut().zip(inverse_transpose) {
+                for (cell, other) in row.iter_mut().zip(other) {
+                    *cell = (*cell + other) / 2.;
+                }
+            }
+            let next = Matrix4::new(next);
+            let change = (0..3)
+                .flat_map(|i| (0..3).map(move |j| (i, j)))
+                .map(|(i, j)| (next.rows[i][j] - rotation.rows[i][j]).abs())
+                .fold(0., f64::max);
+            rotation = next;
+            if change < 1e-12 {
+                break;
+            }
+        }
+        // A mirror is left to the stretch.
+        if rotation.determinant() < 0. {
+            rotation = rotation * Matrix4::scaling(Point::new(-1., -1., -1.));
+        }
+        Decomposed {
+            translation,
+            rotation: Quaternion::from_matrix(&rotation),
+            stretch: rotation.transpose() * linear,
+        }
+    }
+    // The transform a fraction `s` of the way from this one to `other`.
+    pub fn lerp(&self, other: &Decomposed, s: f64) -> Matrix4 {
+        let mut stretch = self.stretch.rows;
+        for (row, other) in stretch.iter_mut().zip(other.stretch.rows) {
+            for (cell, other) in row.iter_mut().zip(other) {
+                *cell += (other - *cell) * s;
+            }
+        }
+        let translation = self.translation + (other.translation - self.translation) * s;
+        Matrix4::translation(translation)
+            * self.rotation.slerp(&other.rotation, s).to_matrix()
+            * Matrix4::new(stretch)
+    }
+    // The angle in radians of the turn from this transform to `other`.
+    pub fn turn(&self, other: &Decomposed) -> f64 {
+        2. * self.rotation.dot(&other.rotation).abs().min(1.).acos()
+    }
+}
+
+impl ops::Mul<Matrix4> for Matrix4 {
+    type Output = Matrix4;
+
+    fn mul(self, other: Matrix4) -> Matrix4 {
+        let mut rows = [[0.; 4]; 4];
+        for (i, row) in rows.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.rows[i][k] * other.rows[k][j]).sum();
+            }
+        }
+        Matrix4::new(rows)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Decomposed, Matrix4};
+    use crate::point::Point;
+
+    fn assert_close(a: Point, b: Point) {
+        assert!((a - b).length() < 1e-9, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn test_transforms() {
+        let m = Matrix4::translation(Point::new(1., 2., 3.))
+            * Matrix4::rotation(Point::new(0., 0., 1.), 90.)
+            * Matrix4::scaling(Point::new(2., 2., 2.));
+        assert_close(
+            m.transform_point(Point::new(1., 0., 0.)),
+            Point::new(1., 4., 3.),
+        );
+        assert_close(
+            m.transform_vector(Point::new(1., 0., 0.)),
+            Point::new(0., 2., 0.),
+        );
+    }
+    #[test]
+    fn test_inverse() {
+        let m = Matrix4::translation(Point::new(-3., 0.5, 7.))
+            * Matrix4::rotation(Point::new(1., 2., -1.), 33.)
+            * Matrix4::scaling(Point::new(0.5, 4., 1.));
+        let product = (m * m.inverse().unwrap()).rows();
+        let identity = Matrix4::identity().rows();
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((product[i][j] - identity[i][j]).abs() < 1e-9);
+            }
+        }
+        assert!(Matrix4::scaling(Point::new(1., 0., 1.)).inverse().is_none());
+    }
+    #[test]
+    fn test_decomposed() {
+        let start = Matrix4::translation(Point::new(1., 0., 0.))
+            * Matrix4::scaling(Point::new(1., 2., -1.));
+        let end = Matrix4::translation(Point::new(3., 0., 0.))
+            * Matrix4::rotation(Point::new(0., 1., 0.), 180.)
+            * Matrix4::scaling(Point::new(1., 2., -1.));
+        let (a, b) = (Decomposed::new(&start), Decomposed::new(&end));
+        for (s, m) in [(0., start), (1., end)] {
+            let blended = a.lerp(&b, s).rows().into_iter().flatten();
+            for (x, y) in blended.zip(m.rows().into_iter().flatten()) {
+                assert!((x - y).abs() < 1e-9);
+            }
+        }
+        // Half a turn stays solid and goes through a quarter turn on the
+        // way, where blending the matrices would squash it flat.
+        assert!((a.turn(&b) - std::f64::consts::PI).abs() < 1e-9);
+        let halfway = a.lerp(&b, 0.5);
+        assert!((halfway.determinant().abs() - 2.).abs() < 1e-9);
+        let x = halfway.transform_vector(Point::new(1., 0., 0.));
+        assert!((x.length() - 1.).abs() < 1e-9 && x.x.abs() < 1e-9 && x.y.abs() < 1e-9);
+        assert_close(
+            halfway.transform_point(Point::new(0., 0., 0.)),
+            Point::new(2., 0., 0.),
+        );
+    }
+}
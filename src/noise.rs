@@ -0,0 +1,156 @@
+use crate::point::Point;
+
+// Gradient noise on the plane or in space, summed over `octaves` that each
+// have twice the frequency and half the weight of the one before. The same
+// seed always gives the same noise.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Noise {
+    pub seed: u64,
+    pub frequency: f64,
+    pub octaves: usize,
+}
+
+impl Noise {
+    pub fn new(seed: u64, frequency: f64, octaves: usize) -> Self {
+        Noise {
+            seed,
+            frequency,
+            octaves,
+        }
+    }
+
+    // Between 0 and 1, and around 0.5 on average.
+    pub fn value(&self, x: f64, y: f64) -> f64 {
+        // Gradient noise stays within about -0.7 and 0.7.
+        self.sum(0.7, |seed, f| gradient_noise(seed, x * f, y * f))
+    }
+    // The same in space, as for the density of smoke.
+    pub fn value_at(&self, p: Point) -> f64 {
+        self.sum(1., |seed, f| gradient_noise_3d(seed, p * f))
+    }
+
+    fn sum(&self, reach: f64, noise: impl Fn(u64, f64) -> f64) -> f64 {
+        let mut sum = 0.;
+        let mut weight = 1.;
+        let mut total = 0.;
+        let mut frequency = self.frequency;
+        for octave in 0..self.octaves {
+            let seed = self.seed.wrapping_add(octave as u64);
+            sum += weight * noise(seed, frequency);
+            total += weight;
+            weight *= 0.5;
+            frequency *= 2.;
+        }
+        if total == 0. {
+            return 0.5;
+        }
+        (0.5 + 0.5 / reach * sum / total).clamp(0., 1.)
+    }
+}
+
+// A well mixed hash of a lattice point.
+fn hash(seed: u64, x: i64, y: i64, z: i64) -> u64 {
+    let mut h = seed
+        ^ (x as u64).wrapping_mul(0x9e3779b97f4a7c15)
+        ^ (y as u64).wrapping_mul(0xc2b2ae3d27d4eb4f)
+        ^ (z as u64).wrapping_mul(0x165667b19e3779f9);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51afd7ed558ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ceb9fe1a85ec53);
+    h ^ (h >> 33)
+}
+
+fn gradient_noise(seed: u64, x: f64, y: f64) -> f64 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    // The slope at each corner, dotted with the offset from that corner.
+    let corner = |dx: f64, dy: f64| {
+        let angle = hash(seed, x0 as i64 + dx as i64, y0 as i64 + dy as i64, 0) as f64
+            / u64::MAX as f64
+            * std::f64::consts::TAU;
+        angle.cos() * (fx - dx) + angle.sin() * (fy - dy)
+    };
+    let fade = |t: f64| t * t * t * (t * (t * 6. - 15.) + 10.);
+    let (u, v) = (fade(fx), fade(fy));
+    let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+    lerp(
+        lerp(corner(0., 0.), corner(1., 0.), u),
+        lerp(corner(0., 1.), corner(1., 1.), u),
+        v,
+    )
+}
+
+// The edges of a cube, as slopes to pick from in space.
+const GRADIENTS: [(f64, f64, f64); 12] = [
+    (1., 1., 0.),
+    (-1., 1., 0.),
+    (1., -1., 0.),
+    (-1., -1., 0.),
+    (1., 0., 1.),
+    (-1., 0., 1.),
+    (1., 0., -1.),
+    (-1., 0., -1.),
+    (0., 1., 1.),
+    (0., -1., 1.),
+    (0., 1., -1.),
+    (0., -1., -1.),
+];
+
+fn gradient_noise_3d(seed: u64, p: Point) -> f64 {
+    let base = Point::new(p.x.floor(), p.y.floor(), p.z.floor());
+    let f = p - base;
+    let corner = |dx: f64, dy: f64, dz: f64| {
+        let (x, y, z) = (base.x + dx, base.y + dy, base.z + dz);
+        let (gx, gy, gz) = GRADIENTS[(hash(seed, x as i64, y as i64, z as i64) % 12) as usize];
+        gx * (f.x - dx) + gy * (f.y - dy) + gz * (f.z - dz)
+    };
+    let fade = |t: f64| t * t * t * (t * (t * 6. - 15.) + 10.);
+    let (u, v, w) = (fade(f.x), fade(f.y), fade(f.z));
+    let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+    let layer = |dz: f64| {
+        lerp(
+            lerp(corner(0., 0., dz), corner(1., 0., dz), u),
+            lerp(corner(0., 1., dz), corner(1., 1., dz), u),
+            v,
+        )
+    };
+    lerp(layer(0.), layer(1.), w)
+}
+
+#[cfg(test)]
+mod test {
+    use super::Noise;
+    use crate::point::Point;
+
+    #[test]
+    fn test_noise() {
+        let noise = Noise::new(7, 0.5, 4);
+        let values: Vec<f64> = (0..1000)
+            .map(|i| noise.value(i as f64 * 0.37, i as f64 * 0.11))
+            .collect();
+        assert!(values.iter().all(|v| (0. ..=1.).contains(v)));
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        assert!((mean - 0.5).abs() < 0.1);
+        // Close points have close values, and the noise repeats exactly.
+        assert!((noise.value(3., 4.) - noise.value(3.001, 4.)).abs() < 0.01);
+        assert_eq!(noise.value(3., 4.), Noise::new(7, 0.5, 4).value(3., 4.));
+        assert_ne!(noise.value(3., 4.), Noise::new(8, 0.5, 4).value(3., 4.));
+
+        let values: Vec<f64> = (0..1000)
+            .map(|i| {
+                noise.value_at(Point::new(
+                    i as f64 * 0.37,
+                    i as f64 * 0.11,
+                    i as f64 * 0.23,
+                ))
+            })
+            .collect();
+        assert!(values.iter().all(|v| (0. ..=1.).contains(v)));
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        assert!((mean - 0.5).abs() < 0.1);
+        let p = Point::new(3., 4., 5.);
+        let nearby = p + Point::new(0., 0., 0.001);
+        assert!((noise.value_at(p) - noise.value_at(nearby)).abs() < 0.01);
+    }
+}
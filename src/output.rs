@@ -0,0 +1,155 @@
+pub mod deflate;
+pub mod exr;
+pub mod hdr;
+pub mod png;
+pub mod ppm;
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImageFormat {
+    Ppm,
+    Png,
+    Hdr,
+    Exr,
+}
+
+impl ImageFormat {
+    pub fn from_path(path: &Path) -> io::Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("ppm") => Ok(ImageFormat::Ppm),
+            Some("png") => Ok(ImageFormat::Png),
+            Some("hdr") => Ok(ImageFormat::Hdr),
+            Some("exr") => Ok(ImageFormat::Exr),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unsupported image format: {}", path.display()),
+            )),
+        }
+    }
+
+    pub fn is_high_dynamic_range(&self) -> bool {
+        matches!(self, ImageFormat::Hdr | ImageFormat::Exr)
+    }
+}
+
+fn unsupported(format: ImageFormat) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("{:?} can not store this image", format),
+    )
+}
+
+// Writes 8 bit RGB rows, top row first, to `path` in the format matching its
+// extension.
+pub fn write_image<I, R>(path: &Path, width: usize, height: usize, rows: I) -> io::Result<()>
+where
+    I: IntoIterator<Item = R>,
+    R: AsRef<[u8]>,
+{
+    let format = ImageFormat::from_path(path)?;
+    let mut file = BufWriter::new(File::create(path)?);
+    match format {
+        ImageFormat::Ppm => ppm::write(&mut file, width, height, rows)?,
+        ImageFormat::Png => png::write(&mut file, width, height, rows)?,
+        _ => return Err(unsupported(format)),
+    }
+    file.flush()
+}
+
+pub fn write_exr(
+    path: &Path,
+    width: usize,
+    height: usize,
+    channels: &[exr::Channel],
+    compression: exr::Compression,
+) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    exr::write(&mut file, width, height, channels, compression)?;
+    file.flush()
+}
+
+// Writes linear floating point RGB pixels, top row first, to a high dynamic
+// range image. OpenEXR files are stored with `compression`.
+pub fn write_float_image(
+    path: &Path,
+    width: usize,
+    height: usize,
+    pixels: &[[f32; 3]],
+    compression: exr::Compression,
+) -> io::Result<()> {
+    match ImageFormat::from_path(path)? {
+        ImageFormat::Hdr => {
+            let mut file = BufWriter::new(File::create(path)?);
+            hdr::write(&mut file, width, height, pixels.chunks(width))?;
+            file.flush()
+        }
+        ImageFormat::Exr => {
+            let channels: Vec<exr::Channel> = ["R", "G", "B"]
+                .iter()
+                .enumerate()
+                .map(|(c, name)| exr::Channel {
+                    name: name.to_string(),
+                    data: pixels.iter().map(|p| p[c]).collect(),
+                })
+                .collect();
+            write_exr(path, width, height, &channels, compression)
+        }
+        format => Err(unsupported(format)),
+    }
+}
+
+// The path of one frame of an animation: a run of `#` in `path` is replaced
+// by the frame number padded with zeros to its length, and without one the
+// number goes before the extension.
+pub fn numbered(path: &str, frame: usize) -> String {
+    if let Some(start) = path.rfind('#') {
+        let start = path[..start].trim_end_matches('#').len();
+        let end = start
+            + path[start..]
+                .find(|c| c != '#')
+                .unwrap_or(path.len() - start);
+        let width = end - start;
+        return format!("{}{:0width$}{}", &path[..start], frame, &path[end..]);
+    }
+    match Path::new(path).extension().and_then(|e| e.to_str()) {
+        Some(extension) => {
+            let stem = &path[..path.len() - extension.len() - 1];
+            format!("{}_{:04}.{}", stem, frame, extension)
+        }
+        None => format!("{}_{:04}", path, frame),
+    }
+}
+
+// Where an image is written before it is moved to `path`, so that a frame
+// that was cut short never looks finished.
+pub fn partial(path: &str) -> String {
+    match Path::new(path).extension().and_then(|e| e.to_str()) {
+        Some(extension) => {
+            let stem = &path[..path.len() - extension.len() - 1];
+            format!("{}.part.{}", stem, extension)
+        }
+        None => format!("{}.part", path),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{numbered, partial};
+
+    #[test]
+    fn test_numbered() {
+        assert_eq!(numbered("out/frame_###.png", 7), "out/frame_007.png");
+        assert_eq!(numbered("#.ppm", 12), "12.ppm");
+        assert_eq!(numbered("f##_##.png", 1234), "f##_1234.png");
+        assert_eq!(numbered("out.v2/spin.exr", 3), "out.v2/spin_0003.exr");
+        assert_eq!(numbered("spin", 3), "spin_0003");
+        assert_eq!(partial("spin_0003.png"), "spin_0003.part.png");
+    }
+}
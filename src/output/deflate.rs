@@ -0,0 +1,357 @@
+use std::io::{self, Write};
+
+const WINDOW_SIZE: usize = 32 * 1024;
+const BLOCK_SIZE: usize = 64 * 1024;
+const HASH_BITS: usize = 15;
+const MAX_CHAIN: usize = 64;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const NO_POSITION: usize = usize::MAX;
+
+const LENGTH_BASE: [usize; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u32; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [usize; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u32; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+struct BitWriter {
+    buffer: Vec<u8>,
+    acc: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        BitWriter {
+            buffer: vec![],
+            acc: 0,
+            count: 0,
+        }
+    }
+    fn write_bits(&mut self, value: u32, bits: u32) {
+        self.acc |= (value as u64) << self.count;
+        self.count += bits;
+        while self.count >= 8 {
+            self.buffer.push(self.acc as u8);
+            self.acc >>= 8;
+            self.count -= 8;
+        }
+    }
+    // Huffman codes are packed starting with their most significant bit.
+    fn write_code(&mut self, code: u32, bits: u32) {
+        self.write_bits(code.reverse_bits() >> (32 - bits), bits);
+    }
+    fn align(&mut self) {
+        if self.count > 0 {
+            self.write_bits(0, 8 - self.count);
+        }
+    }
+}
+
+struct HashChains {
+    head: Vec<usize>,
+    prev: Vec<usize>,
+}
+
+impl HashChains {
+    fn new(size: usize) -> Self {
+        HashChains {
+            head: vec![NO_POSITION; 1 << HASH_BITS],
+            prev: vec![NO_POSITION; size],
+        }
+    }
+    fn hash(window: &[u8], i: usize) -> usize {
+        (((window[i] as usize) << 10) ^ ((window[i + 1] as usize) << 5) ^ (window[i + 2] as usize))
+            & ((1 << HASH_BITS) - 1)
+    }
+    fn insert(&mut self, window: &[u8], i: usize) {
+        if i + MIN_MATCH <= window.len() {
+            let h = Self::hash(window, i);
+            self.prev[i] = self.head[h];
+            self.head[h] = i;
+        }
+    }
+    fn longest_match(&self, window: &[u8], i: usize) -> Option<(usize, usize)> {
+        let max_length = MAX_MATCH.min(window.len() - i);
+        if max_length < MIN_MATCH {
+            return None;
+        }
+        let mut best: Option<(usize, usize)> = None;
+        let mut candidate = self.head[Self::hash(window, i)];
+        for _ in 0..MAX_CHAIN {
+            if candidate == NO_POSITION || i - candidate > WINDOW_SIZE {
+                break;
+            }
+            let length = window[candidate..]
+                .iter()
+                .zip(&window[i..i + max_length])
+                .take_while(|(a, b)| a == b)
+                .count();
+            if length >= MIN_MATCH && best.is_none_or(|(l, _)| length > l) {
+                best = Some((length, i - candidate));
+                if length == max_length {
+                    break;
+                }
+            }
+            candidate = self.prev[candidate];
+        }
+        best
+    }
+}
+
+pub struct Adler32 {
+    a: u32,
+    b: u32,
+}
+
+impl Adler32 {
+    pub fn new() -> Self {
+        Adler32 { a: 1, b: 0 }
+    }
+    pub fn update(&mut self, data: &[u8]) {
+        for chunk in data.chunks(5552) {
+            for byte in chunk {
+                self.a += *byte as u32;
+                self.b += self.a;
+            }
+            self.a %= 65521;
+            self.b %= 65521;
+        }
+    }
+    pub fn finish(&self) -> u32 {
+        (self.b << 16) | self.a
+    }
+}
+
+// Streaming zlib (RFC 1950) encoder producing deflate blocks with the fixed
+// Huffman code and LZ77 matches found through hash chains.
+pub struct ZlibEncoder<W: Write> {
+    inner: W,
+    bits: BitWriter,
+    window: Vec<u8>,
+    pending: usize,
+    adler: Adler32,
+}
+
+impl<W: Write> ZlibEncoder<W> {
+    pub fn new(mut inner: W) -> io::Result<Self> {
+        inner.write_all(&[0x78, 0x9c])?;
+        Ok(ZlibEncoder {
+            inner,
+            bits: BitWriter::new(),
+            window: Vec::with_capacity(WINDOW_SIZE + BLOCK_SIZE),
+            pending: 0,
+            adler: Adler32::new(),
+        })
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.compress_block(true)?;
+        self.bits.align();
+        self.bits
+            .buffer
+            .extend_from_slice(&self.adler.finish().to_be_bytes());
+        self.inner.write_all(&self.bits.buffer)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    fn write_literal(&mut self, symbol: usize) {
+        match symbol {
+            0..=143 => self.bits.write_code(0x30 + symbol as u32, 8),
+            144..=255 => self.bits.write_code(0x190 + (symbol - 144) as u32, 9),
+            256..=279 => self.bits.write_code((symbol - 256) as u32, 7),
+            _ => self.bits.write_code(0xc0 + (symbol - 280) as u32, 8),
+        }
+    }
+
+    fn write_match(&mut self, length: usize, distance: usize) {
+        let code = LENGTH_BASE.iter().rposition(|&b| b <= length).unwrap();
+        self.write_literal(257 + code);
+        self.bits
+            .write_bits((length - LENGTH_BASE[code]) as u32, LENGTH_EXTRA[code]);
+        let code = DISTANCE_BASE.iter().rposition(|&b| b <= distance).unwrap();
+        self.bits.write_code(code as u32, 5);
+        self.bits.write_bits(
+            (distance - DISTANCE_BASE[code]) as u32,
+            DISTANCE_EXTRA[code],
+        );
+    }
+
+    fn compress_block(&mut self, last: bool) -> io::Result<()> {
+        let mut chains = HashChains::new(self.window.len());
+        for i in 0..self.pending {
+            chains.insert(&self.window, i);
+        }
+
+        self.bits.write_bits(last as u32, 1);
+        self.bits.write_bits(1, 2);
+        let mut i = self.pending;
+        while i < self.window.len() {
+            match chains.longest_match(&self.window, i) {
+                Some((length, distance)) => {
+                    self.write_match(length, distance);
+                    for j in i..i + length {
+                        chains.insert(&self.window, j);
+                    }
+                    i += length;
+                }
+                None => {
+                    self.write_literal(self.window[i] as usize);
+                    chains.insert(&self.window, i);
+                    i += 1;
+                }
+            }
+        }
+        self.write_literal(256);
+
+        let keep_from = self.window.len().saturating_sub(WINDOW_SIZE);
+        self.window.drain(..keep_from);
+        self.pending = self.window.len();
+        self.inner.write_all(&self.bits.buffer)?;
+        self.bits.buffer.clear();
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for ZlibEncoder<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.adler.update(data);
+        self.window.extend_from_slice(data);
+        if self.window.len() - self.pending >= BLOCK_SIZE {
+            self.compress_block(false)?;
+        }
+        Ok(data.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::{
+        Adler32, ZlibEncoder, BLOCK_SIZE, DISTANCE_BASE, DISTANCE_EXTRA, LENGTH_BASE, LENGTH_EXTRA,
+    };
+    use std::io::Write;
+
+    struct BitReader<'a> {
+        data: &'a [u8],
+        position: usize,
+    }
+
+    impl BitReader<'_> {
+        fn bits(&mut self, count: u32) -> u32 {
+            let mut value = 0;
+            for i in 0..count {
+                let bit = (self.data[self.position / 8] >> (self.position % 8)) & 1;
+                value |= (bit as u32) << i;
+                self.position += 1;
+            }
+            value
+        }
+        // Huffman codes start with their most significant bit.
+        fn code(&mut self, count: u32) -> u32 {
+            (0..count).fold(0, |code, _| (code << 1) | self.bits(1))
+        }
+        fn literal(&mut self) -> usize {
+            let code = self.code(7);
+            if code < 0x18 {
+                return 256 + code as usize;
+            }
+            let code = (code << 1) | self.bits(1);
+            match code {
+                0x30..=0xbf => code as usize - 0x30,
+                0xc0..=0xc7 => 280 + code as usize - 0xc0,
+                _ => 144 + ((code << 1) | self.bits(1)) as usize - 0x190,
+            }
+        }
+        fn align(&mut self) {
+            self.position = self.position.div_ceil(8) * 8;
+        }
+    }
+
+    // Decodes a zlib stream of stored and fixed Huffman blocks, which is all
+    // the encoder writes, and checks its Adler-32.
+    pub fn inflate(data: &[u8]) -> Vec<u8> {
+        assert_eq!(data[0] & 0x0f, 8);
+        assert_eq!(u16::from_be_bytes([data[0], data[1]]) % 31, 0);
+        let mut reader = BitReader { data, position: 16 };
+        let mut out: Vec<u8> = vec![];
+        loop {
+            let last = reader.bits(1) == 1;
+            match reader.bits(2) {
+                0 => {
+                    reader.align();
+                    let length = reader.bits(16);
+                    assert_eq!(reader.bits(16), !length & 0xffff);
+                    for _ in 0..length {
+                        out.push(reader.bits(8) as u8);
+                    }
+                }
+                1 => loop {
+                    let symbol = reader.literal();
+                    if symbol < 256 {
+                        out.push(symbol as u8);
+                        continue;
+                    }
+                    if symbol == 256 {
+                        break;
+                    }
+                    let code = symbol - 257;
+                    let length = LENGTH_BASE[code] + reader.bits(LENGTH_EXTRA[code]) as usize;
+                    let code = reader.code(5) as usize;
+                    let distance = DISTANCE_BASE[code] + reader.bits(DISTANCE_EXTRA[code]) as usize;
+                    for _ in 0..length {
+                        out.push(out[out.len() - distance]);
+                    }
+                },
+                kind => panic!("unexpected block type {}", kind),
+            }
+            if last {
+                break;
+            }
+        }
+        reader.align();
+        let end = reader.position / 8;
+        let mut adler = Adler32::new();
+        adler.update(&out);
+        assert_eq!(data[end..], adler.finish().to_be_bytes());
+        out
+    }
+
+    #[test]
+    fn test_round_trip() {
+        // Repeats near and far, and more than two blocks of it.
+        let data: Vec<u8> = (0..5 * BLOCK_SIZE / 2)
+            .map(|i| {
+                if i % 1000 < 600 {
+                    (i * 7 % 251) as u8
+                } else {
+                    (i / 3000) as u8
+                }
+            })
+            .collect();
+        let mut encoder = ZlibEncoder::new(vec![]).unwrap();
+        for chunk in data.chunks(10000) {
+            encoder.write_all(chunk).unwrap();
+        }
+        let compressed = encoder.finish().unwrap();
+        assert!(compressed.len() < data.len() / 2);
+        assert_eq!(inflate(&compressed), data);
+        assert_eq!(
+            inflate(&ZlibEncoder::new(vec![]).unwrap().finish().unwrap()),
+            b""
+        );
+    }
+}
@@ -0,0 +1,293 @@
+use crate::output::deflate::ZlibEncoder;
+use std::io::{self, Write};
+
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+const FLOAT: i32 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Compression {
+    None,
+    Zip,
+}
+
+impl Compression {
+    fn id(&self) -> u8 {
+        match self {
+            Compression::None => 0,
+            Compression::Zip => 3,
+        }
+    }
+    fn lines_per_block(&self) -> usize {
+        match self {
+            Compression::None => 1,
+            Compression::Zip => 16,
+        }
+    }
+}
+
+// A full resolution 32 bit float channel stored row by row, top row first.
+pub struct Channel {
+    pub name: String,
+    pub data: Vec<f32>,
+}
+
+fn attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+fn header(width: usize, height: usize, channels: &[&Channel], compression: Compression) -> Vec<u8> {
+    let mut header = vec![];
+    header.extend_from_slice(&MAGIC);
+    header.extend_from_slice(&[2, 0, 0, 0]);
+
+    let mut list = vec![];
+    for channel in channels {
+        list.extend_from_slice(channel.name.as_bytes());
+        list.push(0);
+        list.extend_from_slice(&FLOAT.to_le_bytes());
+        list.extend_from_slice(&[0, 0, 0, 0]);
+        list.extend_from_slice(&1i32.to_le_bytes());
+        list.extend_from_slice(&1i32.to_le_bytes());
+    }
+    list.push(0);
+    attribute(&mut header, "channels", "chlist", &list);
+    attribute(
+        &mut header,
+        "compression",
+        "compression",
+        &[compression.id()],
+    );
+
+    let mut window = vec![];
+    for v in [0, 0, width as i32 - 1, height as i32 - 1] {
+        window.extend_from_slice(&v.to_le_bytes());
+    }
+    attribute(&mut header, "dataWindow", "box2i", &window);
+    attribute(&mut header, "displayWindow", "box2i", &window);
+    attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    let mut center = vec![];
+    center.extend_from_slice(&0f32.to_le_bytes());
+    center.extend_from_slice(&0f32.to_le_bytes());
+    attribute(&mut header, "screenWindowCenter", "v2f", &center);
+    attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    header.push(0);
+    header
+}
+
+// The ZIP compressor splits even and odd bytes and delta encodes the result
+// before deflating, as specified by OpenEXR.
+fn zip(raw: &[u8]) -> io::Result<Vec<u8>> {
+    let half = raw.len().div_ceil(2);
+    let mut reordered = vec![0u8; raw.len()];
+    for (i, byte) in raw.iter().enumerate() {
+        let target = if i % 2 == 0 { i / 2 } else { half + i / 2 };
+        reordered[target] = *byte;
+    }
+    let mut previous = reordered.first().copied().unwrap_or(0);
+    for byte in reordered.iter_mut().skip(1) {
+        let current = *byte;
+        *byte = current.wrapping_sub(previous).wrapping_add(128);
+        previous = current;
+    }
+    let mut encoder = ZlibEncoder::new(vec![])?;
+    encoder.write_all(&reordered)?;
+    encoder.finish()
+}
+
+pub fn write<W: Write>(
+    out: &mut W,
+    width: usize,
+    height: usize,
+    channels: &[Channel],
+    compression: Compression,
+) -> io::Result<()> {
+    let mut sorted: Vec<&Channel> = channels.iter().collect();
+    sorted.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let lines = compression.lines_per_block();
+    let mut chunks = vec![];
+    for y0 in (0..height).step_by(lines) {
+        let mut raw = vec![];
+        for y in y0..(y0 + lines).min(height) {
+            for channel in &sorted {
+                for value in &channel.data[y * width..(y + 1) * width] {
+                    raw.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+        }
+        let data = match compression {
+            Compression::None => raw,
+            Compression::Zip => {
+                let compressed = zip(&raw)?;
+                if compressed.len() < raw.len() {
+                    compressed
+                } else {
+                    raw
+                }
+            }
+        };
+        chunks.push((y0 as i32, data));
+    }
+
+    let header = header(width, height, &sorted, compression);
+    out.write_all(&header)?;
+    let mut offset = (header.len() + 8 * chunks.len()) as u64;
+    for (_, data) in &chunks {
+        out.write_all(&offset.to_le_bytes())?;
+        offset += 8 + data.len() as u64;
+    }
+    for (y, data) in &chunks {
+        out.write_all(&y.to_le_bytes())?;
+        out.write_all(&(data.len() as i32).to_le_bytes())?;
+        out.write_all(data)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{write, Channel, Compression, MAGIC};
+    use crate::output::deflate::test::inflate;
+    use std::collections::HashMap;
+
+    fn read_i32(data: &[u8], at: usize) -> i32 {
+        i32::from_le_bytes(data[at..at + 4].try_into().unwrap())
+    }
+
+    fn read_string(data: &[u8], at: &mut usize) -> String {
+        let end = *at + data[*at..].iter().position(|&b| b == 0).unwrap();
+        let string = String::from_utf8(data[*at..end].to_vec()).unwrap();
+        *at = end + 1;
+        string
+    }
+
+    // Undoes the delta encoding and byte split of a ZIP block.
+    fn unzip(data: &[u8]) -> Vec<u8> {
+        let mut reordered = inflate(data);
+        for i in 1..reordered.len() {
+            reordered[i] = reordered[i - 1]
+                .wrapping_add(reordered[i])
+                .wrapping_sub(128);
+        }
+        let half = reordered.len().div_ceil(2);
+        (0..reordered.len())
+            .map(|i| {
+                if i % 2 == 0 {
+                    reordered[i / 2]
+                } else {
+                    reordered[half + i / 2]
+                }
+            })
+            .collect()
+    }
+
+    // Reads back the header attributes and the channels of an image.
+    fn decode(data: &[u8]) -> (HashMap<String, Vec<u8>>, Vec<Channel>) {
+        assert_eq!(data[..4], MAGIC);
+        assert_eq!(data[4..8], [2, 0, 0, 0]);
+        let mut at = 8;
+        let mut attributes = HashMap::new();
+        loop {
+            let name = read_string(data, &mut at);
+            if name.is_empty() {
+                break;
+            }
+            read_string(data, &mut at);
+            let size = read_i32(data, at) as usize;
+            attributes.insert(name, data[at + 4..at + 4 + size].to_vec());
+            at += 4 + size;
+        }
+
+        let list = &attributes["channels"];
+        let mut channels = vec![];
+        let mut i = 0;
+        while list[i] != 0 {
+            let name = read_string(list, &mut i);
+            assert_eq!(read_i32(list, i), 2);
+            i += 16;
+            channels.push(Channel { name, data: vec![] });
+        }
+        let window = &attributes["dataWindow"];
+        let width = read_i32(window, 8) as usize + 1;
+        let height = read_i32(window, 12) as usize + 1;
+        let lines = match attributes["compression"][..] {
+            [0] => 1,
+            [3] => 16,
+            _ => panic!("unknown compression"),
+        };
+
+        for block in 0..height.div_ceil(lines) {
+            let offset = u64::from_le_bytes(data[at + 8 * block..][..8].try_into().unwrap());
+            let offset = offset as usize;
+            let y = read_i32(data, offset) as usize;
+            assert_eq!(y, block * lines);
+            let count = lines.min(height - y);
+            let size = read_i32(data, offset + 4) as usize;
+            let stored = &data[offset + 8..offset + 8 + size];
+            // ZIP blocks that would not shrink are stored as they are.
+            let raw = if size < count * width * channels.len() * 4 {
+                unzip(stored)
+            } else {
+                stored.to_vec()
+            };
+            let mut values = raw
+                .chunks(4)
+                .map(|b| f32::from_le_bytes(b.try_into().unwrap()));
+            for _ in 0..count {
+                for channel in channels.iter_mut() {
+                    channel.data.extend(values.by_ref().take(width));
+                }
+            }
+            assert!(values.next().is_none());
+        }
+        (attributes, channels)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let (width, height) = (5, 20);
+        let channels: Vec<Channel> = ["R", "G", "B", "A"]
+            .iter()
+            .enumerate()
+            .map(|(c, name)| Channel {
+                name: name.to_string(),
+                data: (0..width * height)
+                    .map(|i| (i / width) as f32 * 0.5 + c as f32)
+                    .collect(),
+            })
+            .collect();
+        let mut sizes = vec![];
+        for (compression, id) in [(Compression::None, 0), (Compression::Zip, 3)] {
+            let mut exr = vec![];
+            write(&mut exr, width, height, &channels, compression).unwrap();
+            sizes.push(exr.len());
+            let (attributes, decoded) = decode(&exr);
+            assert_eq!(attributes["compression"], [id]);
+            assert_eq!(attributes["dataWindow"], attributes["displayWindow"]);
+            // Channels are stored sorted by name.
+            let names: Vec<&str> = decoded.iter().map(|c| c.name.as_str()).collect();
+            assert_eq!(names, ["A", "B", "G", "R"]);
+            for channel in &decoded {
+                let original = channels.iter().find(|c| c.name == channel.name).unwrap();
+                assert_eq!(channel.data, original.data);
+            }
+        }
+        assert!(sizes[1] < sizes[0]);
+    }
+}
@@ -0,0 +1,197 @@
+use std::io::{self, Write};
+
+const MIN_RUN: usize = 4;
+const MAX_RUN: usize = 127;
+
+pub fn to_rgbe(rgb: [f32; 3]) -> [u8; 4] {
+    // Not a number is stored as black, and what is too bright for the
+    // largest exponent saturates.
+    let rgb = rgb.map(|c| if c.is_nan() { 0. } else { c.max(0.) });
+    let v = rgb[0].max(rgb[1]).max(rgb[2]).min(f32::MAX);
+    if v < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+    let mut exponent = v.log2().floor() as i32 + 1;
+    // Guard against log2 rounding so that the mantissa stays below one.
+    if v / 2f32.powi(exponent) >= 1. {
+        exponent += 1;
+    }
+    let exponent = exponent.min(127);
+    let scale = 256. / 2f32.powi(exponent);
+    [
+        (rgb[0] * scale) as u8,
+        (rgb[1] * scale) as u8,
+        (rgb[2] * scale) as u8,
+        (exponent + 128) as u8,
+    ]
+}
+
+// Run length encodes a single component of a scanline.
+fn write_component<W: Write>(out: &mut W, data: &[u8]) -> io::Result<()> {
+    let mut i = 0;
+    while i < data.len() {
+        let mut run_start = i;
+        let mut run_length = 0;
+        while run_start < data.len() {
+            run_length = data[run_start..]
+                .iter()
+                .take(MAX_RUN)
+                .take_while(|&&b| b == data[run_start])
+                .count();
+            if run_length >= MIN_RUN {
+                break;
+            }
+            run_start += run_length;
+            run_length = 0;
+        }
+        while i < run_start {
+            let count = (run_start - i).min(128);
+            out.write_all(&[count as u8])?;
+            out.write_all(&data[i..i + count])?;
+            i += count;
+        }
+        if run_length >= MIN_RUN {
+            out.write_all(&[128 + run_length as u8, data[run_start]])?;
+            i += run_length;
+        }
+    }
+    Ok(())
+}
+
+// Writes a Radiance RGBE image using the adaptive run length encoding of
+// scanlines whenever the width allows it.
+pub fn write<W, I, R>(out: &mut W, width: usize, height: usize, rows: I) -> io::Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = R>,
+    R: AsRef<[[f32; 3]]>,
+{
+    write!(
+        out,
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        height, width
+    )?;
+    let encoded = (8..0x8000).contains(&width);
+    let mut components = vec![vec![0u8; width]; 4];
+    for row in rows {
+        let row = row.as_ref();
+        if !encoded {
+            for rgb in row {
+                out.write_all(&to_rgbe(*rgb))?;
+            }
+            continue;
+        }
+        for (x, rgb) in row.iter().enumerate() {
+            for (c, byte) in to_rgbe(*rgb).iter().enumerate() {
+                components[c][x] = *byte;
+            }
+        }
+        out.write_all(&[2, 2, (width >> 8) as u8, (width & 0xff) as u8])?;
+        for component in &components {
+            write_component(out, component)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{to_rgbe, write};
+
+    // Reads back the size and RGBE pixels of an image, top row first.
+    fn decode(data: &[u8]) -> (usize, usize, Vec<[u8; 4]>) {
+        let header_end = data.windows(2).position(|w| w == b"\n\n").unwrap() + 2;
+        assert!(data.starts_with(b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n"));
+        let line_end = header_end + data[header_end..].iter().position(|&b| b == b'\n').unwrap();
+        let size = std::str::from_utf8(&data[header_end..line_end]).unwrap();
+        let words: Vec<&str> = size.split(' ').collect();
+        assert_eq!((words[0], words[2]), ("-Y", "+X"));
+        let (height, width): (usize, usize) =
+            (words[1].parse().unwrap(), words[3].parse().unwrap());
+
+        let mut at = line_end + 1;
+        let mut pixels = vec![];
+        for _ in 0..height {
+            if !(8..0x8000).contains(&width) {
+                for _ in 0..width {
+                    pixels.push(data[at..at + 4].try_into().unwrap());
+                    at += 4;
+                }
+                continue;
+            }
+            assert_eq!(data[at..at + 4], [2, 2, (width >> 8) as u8, width as u8]);
+            at += 4;
+            let mut row = vec![[0u8; 4]; width];
+            for c in 0..4 {
+                let mut x = 0;
+                while x < width {
+                    let count = data[at] as usize;
+                    if count > 128 {
+                        for pixel in &mut row[x..x + count - 128] {
+                            pixel[c] = data[at + 1];
+                        }
+                        x += count - 128;
+                        at += 2;
+                    } else {
+                        for (i, pixel) in row[x..x + count].iter_mut().enumerate() {
+                            pixel[c] = data[at + 1 + i];
+                        }
+                        x += count;
+                        at += 1 + count;
+                    }
+                }
+            }
+            pixels.extend(row);
+        }
+        assert_eq!(at, data.len());
+        (width, height, pixels)
+    }
+
+    fn to_float(rgbe: [u8; 4]) -> [f32; 3] {
+        if rgbe[3] == 0 {
+            return [0.; 3];
+        }
+        let scale = 2f32.powi(rgbe[3] as i32 - 136);
+        [0, 1, 2].map(|c| (rgbe[c] as f32 + 0.5) * scale)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        // Runs, single values, and a last row that is one run too long for
+        // a single count byte.
+        for width in [4, 12, 300] {
+            let rows: Vec<Vec<[f32; 3]>> = (0..4)
+                .map(|y| {
+                    (0..width)
+                        .map(|x| {
+                            let kind = if y == 3 { 0 } else { (x / 5 + y) % 3 };
+                            match kind {
+                                0 => [0.5, 0.25, 2.],
+                                1 => [x as f32 * 0.1, 1000., 0.],
+                                _ => [0.; 3],
+                            }
+                        })
+                        .collect()
+                })
+                .collect();
+            let mut hdr = vec![];
+            write(&mut hdr, width, 4, &rows).unwrap();
+            let (w, h, pixels) = decode(&hdr);
+            assert_eq!((w, h), (width, 4));
+            for (rgb, rgbe) in rows.iter().flatten().zip(pixels) {
+                assert_eq!(to_rgbe(*rgb), rgbe);
+                let brightest = rgb[0].max(rgb[1]).max(rgb[2]);
+                for (a, b) in rgb.iter().zip(to_float(rgbe)) {
+                    assert!((a - b).abs() <= brightest / 128.);
+                }
+            }
+        }
+    }
+    #[test]
+    fn test_not_finite() {
+        assert_eq!(to_rgbe([f32::NAN, 0.5, -1.]), to_rgbe([0., 0.5, 0.]));
+        assert_eq!(to_rgbe([f32::NAN; 3]), [0; 4]);
+        assert_eq!(to_rgbe([f32::INFINITY, 0., 1.]), [255, 0, 0, 255]);
+        assert_eq!(to_rgbe([f32::MAX, f32::MAX / 4., 0.])[3], 255);
+    }
+}
@@ -0,0 +1,259 @@
+use crate::output::deflate::ZlibEncoder;
+use std::io::{self, Write};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+const IDAT_SIZE: usize = 64 * 1024;
+const BYTES_PER_PIXEL: usize = 3;
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 == 1 {
+                0xedb88320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+}
+
+pub fn crc32(parts: &[&[u8]]) -> u32 {
+    let mut crc = 0xffffffffu32;
+    for part in parts {
+        for byte in *part {
+            crc = CRC_TABLE[((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8);
+        }
+    }
+    crc ^ 0xffffffff
+}
+
+fn write_chunk<W: Write>(out: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    out.write_all(&crc32(&[kind, data]).to_be_bytes())
+}
+
+// Collects compressed bytes and emits them as IDAT chunks.
+struct ChunkWriter<'a, W: Write> {
+    out: &'a mut W,
+    buffer: Vec<u8>,
+}
+
+impl<'a, W: Write> Write for ChunkWriter<'a, W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(data);
+        if self.buffer.len() >= IDAT_SIZE {
+            self.flush()?;
+        }
+        Ok(data.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.buffer.is_empty() {
+            write_chunk(self.out, b"IDAT", &self.buffer)?;
+            self.buffer.clear();
+        }
+        Ok(())
+    }
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+fn filter_row(filter: u8, row: &[u8], previous: &[u8], out: &mut Vec<u8>) {
+    out.clear();
+    out.push(filter);
+    for i in 0..row.len() {
+        let a = if i >= BYTES_PER_PIXEL {
+            row[i - BYTES_PER_PIXEL]
+        } else {
+            0
+        };
+        let b = previous[i];
+        let c = if i >= BYTES_PER_PIXEL {
+            previous[i - BYTES_PER_PIXEL]
+        } else {
+            0
+        };
+        let predicted = match filter {
+            0 => 0,
+            1 => a,
+            2 => b,
+            3 => ((a as u16 + b as u16) / 2) as u8,
+            _ => paeth(a, b, c),
+        };
+        out.push(row[i].wrapping_sub(predicted));
+    }
+}
+
+// Picks the filter with the smallest sum of absolute residuals, the usual
+// heuristic from the PNG specification.
+fn best_filter(row: &[u8], previous: &[u8], scratch: &mut Vec<u8>, best: &mut Vec<u8>) {
+    let mut best_score = u64::MAX;
+    for filter in 0..5 {
+        filter_row(filter, row, previous, scratch);
+        let score = scratch[1..]
+            .iter()
+            .map(|&v| (v as i8).unsigned_abs() as u64)
+            .sum();
+        if score < best_score {
+            best_score = score;
+            std::mem::swap(scratch, best);
+        }
+    }
+}
+
+pub fn write<W, I, R>(out: &mut W, width: usize, height: usize, rows: I) -> io::Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = R>,
+    R: AsRef<[u8]>,
+{
+    out.write_all(&SIGNATURE)?;
+    let mut header = vec![];
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // 8 bit depth, truecolor, deflate, adaptive filtering, no interlace
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+    write_chunk(out, b"IHDR", &header)?;
+
+    let chunks = ChunkWriter {
+        out,
+        buffer: Vec::with_capacity(IDAT_SIZE),
+    };
+    let mut encoder = ZlibEncoder::new(chunks)?;
+    let mut previous = vec![0u8; width * BYTES_PER_PIXEL];
+    let mut scratch = vec![];
+    let mut filtered = vec![];
+    for row in rows {
+        let row = row.as_ref();
+        best_filter(row, &previous, &mut scratch, &mut filtered);
+        encoder.write_all(&filtered)?;
+        previous.copy_from_slice(row);
+    }
+    let chunks = encoder.finish()?;
+    write_chunk(chunks.out, b"IEND", &[])
+}
+
+#[cfg(test)]
+mod test {
+    use super::{crc32, paeth, write, BYTES_PER_PIXEL, IDAT_SIZE, SIGNATURE};
+    use crate::output::deflate::test::inflate;
+    use crate::output::deflate::Adler32;
+
+    // Reverses the filter of each row, returning the rows and the filters.
+    fn unfilter(data: &[u8], width: usize) -> (Vec<Vec<u8>>, Vec<u8>) {
+        let stride = width * BYTES_PER_PIXEL;
+        let mut rows: Vec<Vec<u8>> = vec![];
+        let mut filters = vec![];
+        for line in data.chunks(stride + 1) {
+            let previous = rows.last().cloned().unwrap_or(vec![0; stride]);
+            let mut row = vec![0u8; stride];
+            for i in 0..stride {
+                let a = if i >= BYTES_PER_PIXEL {
+                    row[i - BYTES_PER_PIXEL]
+                } else {
+                    0
+                };
+                let b = previous[i];
+                let c = if i >= BYTES_PER_PIXEL {
+                    previous[i - BYTES_PER_PIXEL]
+                } else {
+                    0
+                };
+                let predicted = match line[0] {
+                    0 => 0,
+                    1 => a,
+                    2 => b,
+                    3 => ((a as u16 + b as u16) / 2) as u8,
+                    4 => paeth(a, b, c),
+                    filter => panic!("unknown filter {}", filter),
+                };
+                row[i] = line[1 + i].wrapping_add(predicted);
+            }
+            filters.push(line[0]);
+            rows.push(row);
+        }
+        (rows, filters)
+    }
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(crc32(&[b"IEND"]), 0xae426082);
+        let mut adler = Adler32::new();
+        adler.update(b"Wikipedia");
+        assert_eq!(adler.finish(), 0x11e60398);
+    }
+    #[test]
+    fn test_round_trip() {
+        // Large enough for several deflate blocks and IDAT chunks, with
+        // gradients, stripes and noise so that different filters win.
+        let (width, height) = (300, 200);
+        let mut seed = 1u32;
+        let rows: Vec<Vec<u8>> = (0..height)
+            .map(|y| {
+                (0..width * BYTES_PER_PIXEL)
+                    .map(|i| {
+                        seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+                        match y / 40 {
+                            0 => (i / 3) as u8,
+                            1 => y as u8,
+                            2 | 4 => (seed >> 24) as u8,
+                            _ => ((i / 3 + y) % 7 * 30) as u8,
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        let mut png = vec![];
+        write(&mut png, width, height, &rows).unwrap();
+
+        assert_eq!(png[..8], SIGNATURE);
+        let mut at = 8;
+        let mut chunks = vec![];
+        while at < png.len() {
+            let length = u32::from_be_bytes(png[at..at + 4].try_into().unwrap()) as usize;
+            let (kind, data) = (&png[at + 4..at + 8], &png[at + 8..at + 8 + length]);
+            let crc =
+                u32::from_be_bytes(png[at + 8 + length..at + 12 + length].try_into().unwrap());
+            assert_eq!(crc, crc32(&[kind, data]));
+            chunks.push((kind, data));
+            at += 12 + length;
+        }
+        assert_eq!(chunks[0].0, b"IHDR");
+        assert_eq!(chunks.last().unwrap().0, b"IEND");
+        let idat: Vec<u8> = chunks
+            .iter()
+            .filter(|(kind, _)| kind == b"IDAT")
+            .flat_map(|(_, data)| data.iter().copied())
+            .collect();
+        assert!(idat.len() > IDAT_SIZE);
+
+        let (decoded, mut filters) = unfilter(&inflate(&idat), width);
+        assert_eq!(decoded, rows);
+        filters.sort();
+        filters.dedup();
+        assert!(filters.len() >= 3, "only filters {:?}", filters);
+    }
+}
@@ -0,0 +1,14 @@
+use std::io::{self, Write};
+
+pub fn write<W, I, R>(out: &mut W, width: usize, height: usize, rows: I) -> io::Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = R>,
+    R: AsRef<[u8]>,
+{
+    write!(out, "P6\n{} {}\n255\n", width, height)?;
+    for row in rows {
+        out.write_all(row.as_ref())?;
+    }
+    Ok(())
+}
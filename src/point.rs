@@ -1,3 +1,4 @@
+use crate::random;
 use rand::Rng;
 use std::ops;
 #[derive(Debug, Clone, Copy)]
@@ -20,7 +21,7 @@ impl Point {
     }
 
     pub fn random() -> Self {
-        let mut rng = rand::thread_rng();
+        let mut rng = random::rng();
         Point {
             x: rng.gen(),
             y: rng.gen(),
@@ -28,7 +29,7 @@ impl Point {
         }
     }
     pub fn random_in_unit_sphere() -> Self {
-        let mut rng = rand::thread_rng();
+        let mut rng = random::rng();
         loop {
             let p = Point {
                 x: rng.gen_range(0f64..2.) - 1.,
@@ -41,7 +42,7 @@ impl Point {
         }
     }
     pub fn random_in_unit_disk() -> Self {
-        let mut rng = rand::thread_rng();
+        let mut rng = random::rng();
         loop {
             let p = Point {
                 x: rng.gen_range(0f64..2.) - 1.,
@@ -59,9 +60,9 @@ impl Point {
     pub fn random_in_hemisphere(normal: Point) -> Self {
         let in_unit = Self::random_in_unit_sphere();
         if in_unit.dot(normal) > 0. {
-            return in_unit;
+            in_unit
         } else {
-            return -in_unit;
+            -in_unit
         }
     }
 
@@ -200,6 +201,8 @@ impl ops::Div<f64> for Point {
 #[cfg(test)]
 mod test {
     use super::Point;
+    use crate::bodies::{Body, BodyProps, HitRecord, Triangle};
+    use crate::raytracer::Ray;
 
     #[test]
     fn test_additon() {
@@ -215,4 +218,81 @@ mod test {
         assert_eq!(scaled.y, 6.);
         assert_eq!(scaled.z, 9.);
     }
+
+    fn triangle() -> Triangle {
+        Triangle::new(
+            Point::new(0., 0., 0.),
+            Point::new(1., 0., 0.),
+            Point::new(0., 1., 0.),
+            BodyProps::null(),
+        )
+    }
+
+    fn hit(triangle: &Triangle, x: f64, y: f64) -> Option<HitRecord> {
+        let ray = Ray::new(Point::new(x, y, 1.), Point::new(0., 0., -1.));
+        let mut rec = HitRecord::default();
+        if triangle.hit(&ray, 0.001, f64::INFINITY, &mut rec) {
+            Some(rec)
+        } else {
+            None
+        }
+    }
+
+    #[test]
+    fn test_triangle_hit() {
+        let rec = hit(&triangle(), 0.25, 0.5).unwrap();
+        assert_eq!(rec.t, 1.);
+        assert_eq!((rec.u, rec.v), (0.25, 0.5));
+        assert!(rec.front_face);
+        assert_eq!(rec.normal.z, 1.);
+        assert!(hit(&triangle(), 0.6, 0.6).is_none());
+        assert!(hit(&triangle(), -0.1, 0.5).is_none());
+
+        // A triangle of a finely tessellated mesh, seen along a short ray.
+        let tiny = Triangle::new(
+            Point::new(0., 0., 0.),
+            Point::new(1e-5, 0., 0.),
+            Point::new(0., 1e-5, 0.),
+            BodyProps::null(),
+        );
+        let ray = Ray::new(Point::new(2e-6, 2e-6, 1.), Point::new(0., 0., -1e-3));
+        let mut rec = HitRecord::default();
+        assert!(tiny.hit(&ray, 0.001, f64::INFINITY, &mut rec));
+        assert!((rec.t - 1000.).abs() < 1e-9);
+    }
+    #[test]
+    fn test_triangle_uvs() {
+        let triangle = triangle().with_uvs([(0.5, 0.5), (1., 0.5), (0.5, 1.)]);
+        let rec = hit(&triangle, 0.5, 0.).unwrap();
+        assert_eq!((rec.u, rec.v), (0.75, 0.5));
+    }
+    #[test]
+    fn test_triangle_normals() {
+        let up = Point::new(0., 0., 1.);
+        let tilted = Point::new(1., 0., 1.);
+        let triangle = triangle().with_normals([up, tilted, up]);
+        let rec = hit(&triangle, 0.5, 0.).unwrap();
+        let expected = (up + tilted.unit_vector()).unit_vector();
+        assert!((rec.normal - expected).length() < 1e-12);
+        // Seen from behind the shading normal is turned towards the ray.
+        let ray = Ray::new(Point::new(0.5, 0., -1.), Point::new(0., 0., 1.));
+        let mut rec = HitRecord::default();
+        assert!(triangle.hit(&ray, 0.001, f64::INFINITY, &mut rec));
+        assert!(!rec.front_face);
+        assert!((rec.normal + expected).length() < 1e-12);
+    }
+    #[test]
+    fn test_triangle_flat_bounding_box() {
+        let flat = Triangle::new(
+            Point::new(0., 2., 0.),
+            Point::new(1., 2., 0.),
+            Point::new(0., 2., 1.),
+            BodyProps::null(),
+        );
+        let bbox = flat.bounding_box();
+        assert!(bbox.max.y > bbox.min.y);
+        assert_eq!((bbox.min.x, bbox.max.x), (0., 1.));
+        let ray = Ray::new(Point::new(0.2, 5., 0.2), Point::new(0., -1., 0.));
+        assert!(bbox.hit(&ray, 0.001, f64::INFINITY));
+    }
 }
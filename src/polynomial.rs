@@ -0,0 +1,219 @@
+// The real roots of a x^2 + b x + c in ascending order, a double root is
+// given twice. When `a` vanishes the single root of b x + c is given twice.
+pub fn solve_quadratic(a: f64, b: f64, c: f64) -> Option<[f64; 2]> {
+    if a.abs() < 1e-12 {
+        if b.abs() < 1e-12 {
+            return None;
+        }
+        return Some([-c / b, -c / b]);
+    }
+    let discriminant = b * b - 4. * a * c;
+    if discriminant < 0. {
+        return None;
+    }
+    // Avoids subtracting nearly equal numbers for the smaller root.
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    if q == 0. {
+        return Some([0., 0.]);
+    }
+    let (x0, x1) = (q / a, c / q);
+    Some([x0.min(x1), x0.max(x1)])
+}
+
+// Up to four real roots in ascending order.
+#[derive(Clone, Copy, Debug)]
+pub struct Roots {
+    values: [f64; 4],
+    len: usize,
+}
+
+impl Roots {
+    fn new() -> Self {
+        Roots {
+            values: [0.; 4],
+            len: 0,
+        }
+    }
+    fn push(&mut self, x: f64) {
+        self.values[self.len] = x;
+        self.len += 1;
+    }
+    pub fn as_slice(&self) -> &[f64] {
+        &self.values[..self.len]
+    }
+}
+
+pub fn evaluate(coefficients: &[f64], x: f64) -> f64 {
+    coefficients.iter().fold(0., |sum, c| sum * x + c)
+}
+
+// The real roots of the polynomial with `coefficients`, highest power first,
+// of a degree up to four. The roots of the derivative split the line into
+// pieces where the polynomial only rises or falls, and each piece that
+// changes sign holds exactly one root that can not be missed. Where the
+// polynomial touches zero without crossing it, the root is the end of such a
+// piece.
+pub fn solve(coefficients: &[f64]) -> Roots {
+    // Rays that make no sense, such as through a body squashed flat, give
+    // coefficients that are not numbers and have no roots.
+    if coefficients.iter().any(|c| !c.is_finite()) {
+        return Roots::new();
+    }
+    let largest = coefficients.iter().fold(0., |m: f64, c| m.max(c.abs()));
+    let start = coefficients
+        .iter()
+        .position(|c| c.abs() > 1e-12 * largest)
+        .unwrap_or(coefficients.len());
+    let c = &coefficients[start..];
+    let mut roots = Roots::new();
+    match c.len() {
+        0 | 1 => {}
+        2 => roots.push(-c[1] / c[0]),
+        3 => {
+            if let Some([x0, x1]) = solve_quadratic(c[0], c[1], c[2]) {
+                roots.push(x0);
+                if x1 != x0 {
+                    roots.push(x1);
+                }
+            }
+        }
+        _ => {
+            let degree = c.len() - 1;
+            let mut derivative = [0.; 4];
+            for (i, d) in derivative[..degree].iter_mut().enumerate() {
+                *d = c[i] * (degree - i) as f64;
+            }
+            // No root is further out than this.
+            let bound = 1. + c[1..].iter().fold(0., |m: f64, x| m.max((x / c[0]).abs()));
+            let touches = |x: f64| evaluate(c, x).abs() <= 1e-12 * largest;
+            let mut lo = -bound;
+            let critical = solve(&derivative[..degree]);
+            for &hi in critical.as_slice().iter().chain([bound].iter()) {
+                let hi = hi.clamp(lo, bound);
+                if touches(hi) {
+                    roots.push(hi);
+                } else if !touches(lo) && evaluate(c, lo) * evaluate(c, hi) < 0. {
+                    roots.push(refine(c, lo, hi));
+                }
+                lo = hi;
+            }
+        }
+    }
+    roots
+}
+
+pub fn solve_quartic(a: f64, b: f64, c: f64, d: f64, e: f64) -> Roots {
+    solve(&[a, b, c, d, e])
+}
+
+// Narrows down the root between `lo` and `hi`, where the polynomial has
+// opposite signs. Newton steps are taken while they stay inside the bracket,
+// which shrinks with every step, and bisection steps otherwise.
+fn refine(coefficients: &[f64], mut lo: f64, mut hi: f64) -> f64 {
+    let rising = evaluate(coefficients, hi) > 0.;
+    let mut x = 0.5 * (lo + hi);
+    for _ in 0..100 {
+        let (value, slope) = coefficients.iter().fold((0., 0.), |(value, slope), c| {
+            (value * x + c, slope * x + value)
+        });
+        if value == 0. {
+            return x;
+        }
+        if (value > 0.) == rising {
+            hi = x;
+        } else {
+            lo = x;
+        }
+        let newton = x - value / slope;
+        let next = if newton > lo && newton < hi {
+            newton
+        } else {
+            0.5 * (lo + hi)
+        };
+        if (next - x).abs() <= 1e-15 * (1. + x.abs()) {
+            return next;
+        }
+        x = next;
+    }
+    x
+}
+
+#[cfg(test)]
+mod test {
+    use super::{evaluate, solve, solve_quadratic, solve_quartic};
+    use crate::random;
+    use rand::Rng;
+
+    // Every sign change on a fine grid, narrowed down by bisection.
+    fn brute_force(coefficients: &[f64], lo: f64, hi: f64) -> Vec<f64> {
+        let steps = 200000;
+        let x = |i: usize| lo + (hi - lo) * i as f64 / steps as f64;
+        let mut roots = vec![];
+        for i in 0..steps {
+            let (mut a, mut b) = (x(i), x(i + 1));
+            if evaluate(coefficients, a) * evaluate(coefficients, b) >= 0. {
+                continue;
+            }
+            for _ in 0..60 {
+                let mid = 0.5 * (a + b);
+                if evaluate(coefficients, a) * evaluate(coefficients, mid) <= 0. {
+                    b = mid;
+                } else {
+                    a = mid;
+                }
+            }
+            roots.push(0.5 * (a + b));
+        }
+        roots
+    }
+
+    #[test]
+    fn test_quadratic() {
+        assert_eq!(solve_quadratic(2., -2., -4.), Some([-1., 2.]));
+        assert_eq!(solve_quadratic(1., 0., 1.), None);
+        assert_eq!(solve_quadratic(0., 2., -1.), Some([0.5, 0.5]));
+        // Far apart roots keep their precision.
+        let [small, large] = solve_quadratic(1., -1e8, 1.).unwrap();
+        assert!((small - 1e-8).abs() < 1e-20 && (large - 1e8).abs() < 1e-4);
+    }
+    #[test]
+    fn test_quartic() {
+        // (x + 3)(x + 0.5)(x - 1)(x - 1.001)
+        let roots = solve(&[1., 1.499, -4.5025, 0.502, 1.5015]);
+        let expected = [-3., -0.5, 1., 1.001];
+        assert_eq!(roots.as_slice().len(), 4);
+        for (root, expected) in roots.as_slice().iter().zip(expected) {
+            assert!((root - expected).abs() < 1e-9, "{} != {}", root, expected);
+        }
+        // (x^2 + 1)^2 has no real roots, and x^4 - x^2 a double one at zero.
+        assert!(solve_quartic(1., 0., 2., 0., 1.).as_slice().is_empty());
+        let roots = solve_quartic(1., 0., -1., 0., 0.);
+        assert_eq!(roots.as_slice().len(), 3);
+        assert!(roots.as_slice()[1].abs() < 1e-6);
+        // A vanishing leading coefficient leaves a cubic.
+        assert_eq!(solve_quartic(0., 1., 0., -1., 0.).as_slice().len(), 3);
+        // Coefficients that are not numbers have no roots.
+        assert!(solve_quartic(1., f64::NAN, 0., 0., -1.)
+            .as_slice()
+            .is_empty());
+        assert!(solve(&[f64::INFINITY, 0., -1.]).as_slice().is_empty());
+    }
+    #[test]
+    fn test_against_brute_force() {
+        random::seed(17);
+        let mut rng = random::rng();
+        for _ in 0..50 {
+            let coefficients: Vec<f64> = (0..5).map(|_| rng.gen_range(-5.0..5.0)).collect();
+            let bound = 1.
+                + coefficients[1..]
+                    .iter()
+                    .fold(0., |m: f64, c| m.max((c / coefficients[0]).abs()));
+            let expected = brute_force(&coefficients, -bound, bound);
+            let roots = solve(&coefficients);
+            assert_eq!(roots.as_slice().len(), expected.len(), "{:?}", coefficients);
+            for (root, expected) in roots.as_slice().iter().zip(expected) {
+                assert!((root - expected).abs() < 1e-8, "{:?}", coefficients);
+            }
+        }
+    }
+}
@@ -1,13 +1,24 @@
 use std::f64::consts::PI;
 
-use crate::bodies::{Body, HitRecord, BVH};
+use crate::aov::{self, Aov};
+use crate::bodies::{Body, HitRecord};
+use crate::denoise::Denoiser;
+use crate::output;
+use crate::output::exr::Compression;
 use crate::point::Point;
+use crate::random;
+use crate::scene::{RenderSettings, Scene};
+use crate::tonemap::ToneMapper;
 use indicatif::{ProgressBar, ProgressStyle};
 use rand::Rng;
 use rayon::prelude::*;
+use std::io;
+use std::path::Path;
 pub struct Ray {
     pub origin: Point,
     pub direction: Point,
+    // When the ray is sent, for bodies that move while the shutter is open.
+    pub time: f64,
 }
 
 #[derive(Clone, Debug)]
@@ -18,36 +29,31 @@ pub struct Pixel {
 }
 
 impl Pixel {
-    pub fn default() -> Self {
-        Pixel {
-            red: 0,
-            green: 0,
-            blue: 0,
-        }
-    }
-    pub fn transform_to_color(value: f64, samples: usize) -> u8 {
-        let v = match (value / samples as f64).sqrt() {
-            v if v < 0. => 0.,
-            v if v > 0.999 => 0.999,
-            v => v,
-        };
-        (v * 256.) as u8
+    pub fn transform_to_color(value: f64) -> u8 {
+        (value.clamp(0., 0.999) * 256.) as u8
     }
-    pub fn from_point(point: Point, samples: usize) -> Self {
+    pub fn from_point(point: Point) -> Self {
         Pixel {
-            red: Self::transform_to_color(point.x, samples),
-            green: Self::transform_to_color(point.y, samples),
-            blue: Self::transform_to_color(point.z, samples),
+            red: Self::transform_to_color(point.x),
+            green: Self::transform_to_color(point.y),
+            blue: Self::transform_to_color(point.z),
         }
     }
 
-    pub fn to_ppm(&self) -> String {
-        format!("{:3} {:3} {:3}", self.red, self.green, self.blue)
+    pub fn to_rgb(&self) -> [u8; 3] {
+        [self.red, self.green, self.blue]
     }
 }
 impl Ray {
     pub fn new(origin: Point, direction: Point) -> Self {
-        Ray { origin, direction }
+        Ray {
+            origin,
+            direction,
+            time: 0.,
+        }
+    }
+    pub fn with_time(self, time: f64) -> Self {
+        Ray { time, ..self }
     }
 
     pub fn at(&self, distance: f64) -> Point {
@@ -55,7 +61,14 @@ impl Ray {
     }
 }
 
+#[derive(Clone, Debug)]
 pub struct Camera {
+    look_from: Point,
+    look_at: Point,
+    vup: Point,
+    vfov: f64,
+    aperture: f64,
+    focus_dist: f64,
     origin: Point,
     horizontal: Point,
     vertical: Point,
@@ -63,6 +76,7 @@ pub struct Camera {
     u: Point,
     v: Point,
     lens_radius: f64,
+    shutter: (f64, f64),
 }
 
 fn degrees_to_radians(degrees: f64) -> f64 {
@@ -94,6 +108,12 @@ impl Camera {
         let lower_left_corner = origin - horizontal / 2. - vertical / 2. - focus_dist * w;
         let lens_radius = aperture / 2.;
         Camera {
+            look_from,
+            look_at,
+            vup,
+            vfov,
+            aperture,
+            focus_dist,
             origin,
             horizontal,
             vertical,
@@ -101,66 +121,139 @@ impl Camera {
             u,
             v,
             lens_radius,
+            shutter: (0., 0.),
         }
     }
+    // Rays are sent at times between `open` and `close`, which blurs bodies
+    // that move in the meantime.
+    pub fn with_shutter(self, open: f64, close: f64) -> Self {
+        Camera {
+            shutter: (open, close),
+            ..self
+        }
+    }
+    pub fn look_from(&self) -> Point {
+        self.look_from
+    }
+    pub fn look_at(&self) -> Point {
+        self.look_at
+    }
+    pub fn vup(&self) -> Point {
+        self.vup
+    }
+    pub fn vfov(&self) -> f64 {
+        self.vfov
+    }
+    pub fn aperture(&self) -> f64 {
+        self.aperture
+    }
+    pub fn focus_dist(&self) -> f64 {
+        self.focus_dist
+    }
+    pub fn shutter(&self) -> (f64, f64) {
+        self.shutter
+    }
+    pub fn with_aspect_ratio(&self, aspect_ratio: f64) -> Self {
+        Camera::new(
+            self.look_from,
+            self.look_at,
+            self.vup,
+            self.vfov,
+            aspect_ratio,
+            self.aperture,
+            self.focus_dist,
+        )
+        .with_shutter(self.shutter.0, self.shutter.1)
+    }
+    // Distance of `p` from the camera along the viewing direction.
+    pub fn depth(&self, p: Point) -> f64 {
+        (self.origin - p).dot(self.u.cross(self.v))
+    }
     pub fn new_ray(&self, u: f64, v: f64) -> Ray {
         let rd = self.lens_radius * Point::random_unit_vector();
         let offset = self.u * rd.x + self.v * rd.y;
+        let (open, close) = self.shutter;
         Ray {
             origin: self.origin + offset,
             direction: self.lower_left_corner + u * self.horizontal + v * self.vertical
                 - self.origin
                 - offset,
+            time: open + (close - open) * random::rng().gen::<f64>(),
         }
     }
 }
 
 pub struct Tracer {
-    pixels: Vec<Vec<Pixel>>,
+    radiance: Vec<Vec<Point>>,
+    aovs: Option<Vec<Vec<Aov>>>,
     width: usize,
     height: usize,
-    camera: Camera,
-    samples_per_pixel: usize,
-    max_depth: usize,
+    tone_mapper: ToneMapper,
+    compression: Compression,
+    denoiser: Option<Denoiser>,
 }
 
 impl Tracer {
-    pub fn new(
-        width: usize,
-        height: usize,
-        camera: Camera,
-        samples_per_pixel: usize,
-        max_depth: usize,
-    ) -> Self {
-        let pixels: Vec<Vec<Pixel>> = vec![vec![Pixel::default(); width]; height];
-
+    pub fn new() -> Self {
         Tracer {
-            pixels,
-            width,
-            height,
-            camera,
-            samples_per_pixel,
-            max_depth,
+            radiance: vec![],
+            aovs: None,
+            width: 0,
+            height: 0,
+            tone_mapper: ToneMapper::default(),
+            compression: Compression::Zip,
+            denoiser: None,
         }
     }
-    pub fn ray_color(&self, ray: Ray, depth: usize, world: &BVH) -> Point {
-        if depth <= 0 {
-            return Point::default();
-        }
-
+    // Collect first hit AOVs in the next render.
+    pub fn enable_aovs(&mut self) {
+        self.aovs = Some(vec![]);
+    }
+    // Denoise the radiance buffer after the next render, this implies AOVs.
+    pub fn set_denoiser(&mut self, denoiser: Denoiser) {
+        self.denoiser = Some(denoiser);
+        self.enable_aovs();
+    }
+    pub fn set_tone_mapper(&mut self, tone_mapper: ToneMapper) {
+        self.tone_mapper = tone_mapper;
+    }
+    // How OpenEXR images are stored by `save` and `save_aovs`.
+    pub fn set_compression(&mut self, compression: Compression) {
+        self.compression = compression;
+    }
+    // Media scatter like any other body: each draws how far the ray gets
+    // through it, and the nearest of those points and of the surfaces is the
+    // hit. So overlapping media add up, surfaces inside a medium are reached
+    // only by the light that gets through, and a ray scattered inside a
+    // medium goes on from there with its phase function. For a camera ray
+    // the first hit is recorded in the `Aov`.
+    pub fn ray_color(
+        &self,
+        ray: Ray,
+        depth: usize,
+        scene: &Scene,
+        first_hit: Option<(&Camera, &mut Aov)>,
+    ) -> Point {
         let mut record = HitRecord::default();
-        if world.hit(&ray, 0.001, f64::INFINITY, &mut record) {
-            match record.body_props.scatter(&ray, &record) {
-                None => return Point::default(),
+        if depth > 0 && scene.world().hit(&ray, 0.001, f64::INFINITY, &mut record) {
+            if let Some((camera, aov)) = first_hit {
+                aov.add_hit(camera.depth(record.p), &record);
+            }
+            return match record.body_props.scatter(&ray, &record) {
+                None => Point::default(),
                 Some((attenuation, scattered)) => {
-                    return attenuation * self.ray_color(scattered, depth - 1, world)
+                    attenuation * self.ray_color(scattered, depth - 1, scene, None)
                 }
-            }
+            };
+        }
+        let background = match depth {
+            0 => Point::default(),
+            _ => scene.background().color(&ray),
+        };
+        if let Some((_, aov)) = first_hit {
+            aov.add_miss(background);
         }
-        //background
-        let unit_d = ray.direction;
-        let t = 0.5 * (unit_d.y + 1.);
-        return (1. - t) * Point::new(1., 1., 1.) + t * Point::new(0.5, 0.7, 1.0);
+        background
     }
 
     pub fn progress_bar(&self) -> ProgressBar {
@@ -171,45 +264,165 @@ impl Tracer {
             )
             .unwrap()
             .progress_chars("▰▰▱")
-            .tick_strings(&vec!["🙈 🙉 🙊 ", "🙉 🙊 🙈 ", "🙊 🙈 🙉 "]),
+            .tick_strings(&["🙈 🙉 🙊 ", "🙉 🙊 🙈 ", "🙊 🙈 🙉 "]),
         );
         bar
     }
-    pub fn render(&mut self, world: &BVH) {
+    pub fn render(&mut self, scene: &Scene, settings: &RenderSettings) {
+        self.width = settings.width;
+        self.height = settings.height;
+        let camera = scene.camera().with_aspect_ratio(settings.aspect_ratio());
         let bar = self.progress_bar();
-        self.pixels = (0..self.height)
+        let rows: Vec<Vec<(Point, Aov)>> = (0..self.height)
             .into_par_iter()
             .map(|j| {
                 (0..self.width)
                     .map(|i| {
-                        let mut rng = rand::thread_rng();
+                        if let Some(seed) = settings.seed {
+                            random::seed_pixel(seed, j * self.width + i);
+                        }
+                        let mut rng = random::rng();
                         bar.inc(1);
                         let mut color = Point::default();
-                        for _ in 0..self.samples_per_pixel {
+                        let mut aov = Aov::default();
+                        for _ in 0..settings.samples_per_pixel {
                             let u = (i as f64 + rng.gen::<f64>()) / (self.width - 1) as f64;
                             let v = (j as f64 + rng.gen::<f64>()) / (self.height - 1) as f64;
-                            let ray = self.camera.new_ray(u, v);
-                            color = color + self.ray_color(ray, self.max_depth, world);
+                            let ray = camera.new_ray(u, v);
+                            color = color
+                                + self.ray_color(
+                                    ray,
+                                    settings.max_depth,
+                                    scene,
+                                    Some((&camera, &mut aov)),
+                                );
                         }
 
-                        Pixel::from_point(color, self.samples_per_pixel)
+                        (color / settings.samples_per_pixel as f64, aov.average())
                     })
-                    .collect::<Vec<Pixel>>()
+                    .collect()
             })
             .collect();
 
-        bar.finish()
+        self.radiance = rows
+            .iter()
+            .map(|row| row.iter().map(|(color, _)| *color).collect())
+            .collect();
+        if self.aovs.is_some() {
+            self.aovs = Some(
+                rows.iter()
+                    .map(|row| row.iter().map(|(_, aov)| *aov).collect())
+                    .collect(),
+            );
+        }
+        bar.finish();
+
+        if let (Some(denoiser), Some(aovs)) = (&self.denoiser, &self.aovs) {
+            self.radiance = denoiser.denoise(&self.radiance, aovs);
+        }
     }
 
-    pub fn image(&self) -> String {
-        let mut output = format!("P3\n{} {}\n255\n", self.width, self.height).to_owned();
-        for row in self.pixels.iter().rev() {
-            for pixel in row {
-                output.push_str(&pixel.to_ppm());
-                output.push_str(" ");
-            }
-            output.push_str("\n");
+    pub fn save_aovs<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        match &self.aovs {
+            Some(aovs) if !aovs.is_empty() => aov::save(
+                path.as_ref(),
+                &self.radiance,
+                aovs,
+                &self.tone_mapper,
+                self.compression,
+            ),
+            _ => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "AOVs were not collected during rendering",
+            )),
         }
-        output
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        if output::ImageFormat::from_path(path)?.is_high_dynamic_range() {
+            let pixels: Vec<[f32; 3]> = self
+                .radiance
+                .iter()
+                .rev()
+                .flat_map(|row| row.iter().map(|c| [c.x as f32, c.y as f32, c.z as f32]))
+                .collect();
+            return output::write_float_image(
+                path,
+                self.width,
+                self.height,
+                &pixels,
+                self.compression,
+            );
+        }
+        let rows = self.radiance.iter().rev().map(|row| {
+            row.iter()
+                .flat_map(|color| self.tone_mapper.map(*color).to_rgb())
+                .collect::<Vec<u8>>()
+        });
+        output::write_image(path, self.width, self.height, rows)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Camera, Ray, Tracer};
+    use crate::bodies::bodyprops::{Material, Phase};
+    use crate::bodies::medium::Density;
+    use crate::bodies::{Body, BodyProps, Cube, Medium, Texture, BVH};
+    use crate::point::Point;
+    use crate::random;
+    use crate::scene::{Background, RenderSettings, Scene};
+    use std::sync::Arc;
+
+    // The share of `count` rays along x through the unit cube that reach the
+    // white background, with everything in `bodies` black.
+    fn passing(bodies: Vec<Box<dyn Body>>, count: usize) -> f64 {
+        let camera = Camera::new(
+            Point::new(0., 0., 5.),
+            Point::new(0., 0., 0.),
+            Point::new(0., 1., 0.),
+            40.,
+            1.,
+            0.,
+            5.,
+        );
+        let scene = Scene::new(BVH::new(bodies), camera, RenderSettings::new(2, 2, 1, 8))
+            .with_background(Background::Solid(Point::new(1., 1., 1.)));
+        let tracer = Tracer::new();
+        let light: f64 = (0..count)
+            .map(|_| {
+                let ray = Ray::new(Point::new(-1., 0.5, 0.5), Point::new(1., 0., 0.));
+                tracer.ray_color(ray, 8, &scene, None).x
+            })
+            .sum();
+        light / count as f64
+    }
+
+    #[test]
+    fn test_media() {
+        random::seed(5);
+        let black = Texture::new_color(0., 0., 0.);
+        let fog_props = BodyProps::new(black, Material::Volume(Phase::Isotropic));
+        let cube = || -> Arc<dyn Body> {
+            Arc::new(Cube::new(
+                Point::new(0., 0., 0.),
+                Point::new(1., 1., 1.),
+                fog_props,
+            ))
+        };
+        let fog = |density: f64| -> Box<dyn Body> {
+            Box::new(Medium::new(cube(), Density::Constant(density), fog_props))
+        };
+        // Overlapping media are as dense as both together.
+        let light = passing(vec![fog(0.3), fog(0.4)], 20000);
+        assert!((light - (-0.7f64).exp()).abs() < 0.01);
+        // A wall inside the fog stops what gets through.
+        let wall = Cube::new(
+            Point::new(0.5, 0., 0.),
+            Point::new(0.6, 1., 1.),
+            BodyProps::new(black, Material::Lambertian),
+        );
+        assert_eq!(passing(vec![fog(0.3), Box::new(wall)], 1000), 0.);
     }
 }
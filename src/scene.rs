@@ -0,0 +1,105 @@
+use crate::bodies::BVH;
+use crate::point::Point;
+use crate::raytracer::{Camera, Ray};
+
+#[derive(Clone, Copy, Debug)]
+pub struct RenderSettings {
+    pub width: usize,
+    pub height: usize,
+    pub samples_per_pixel: usize,
+    pub max_depth: usize,
+    pub seed: Option<u64>,
+    // Frames per second of an animation.
+    pub fps: f64,
+}
+
+impl RenderSettings {
+    pub fn new(width: usize, height: usize, samples_per_pixel: usize, max_depth: usize) -> Self {
+        RenderSettings {
+            width,
+            height,
+            samples_per_pixel,
+            max_depth,
+            seed: None,
+            fps: 24.,
+        }
+    }
+    pub fn aspect_ratio(&self) -> f64 {
+        self.width as f64 / self.height as f64
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Background {
+    Solid(Point),
+    // Blends from the first colour for rays pointing down to the second for
+    // rays pointing up.
+    Gradient(Point, Point),
+}
+
+impl Background {
+    pub fn sky() -> Self {
+        Background::Gradient(Point::new(1., 1., 1.), Point::new(0.5, 0.7, 1.0))
+    }
+    pub fn color(&self, ray: &Ray) -> Point {
+        match self {
+            Background::Solid(color) => *color,
+            Background::Gradient(bottom, top) => {
+                let t = 0.5 * (ray.direction.y + 1.);
+                (1. - t) * *bottom + t * *top
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Scene {
+    world: BVH,
+    cameras: Vec<Camera>,
+    camera: usize,
+    background: Background,
+    settings: RenderSettings,
+}
+
+impl Scene {
+    pub fn new(world: BVH, camera: Camera, settings: RenderSettings) -> Self {
+        Scene {
+            world,
+            cameras: vec![camera],
+            camera: 0,
+            background: Background::sky(),
+            settings,
+        }
+    }
+    pub fn with_background(self, background: Background) -> Self {
+        Scene { background, ..self }
+    }
+
+    pub fn world(&self) -> &BVH {
+        &self.world
+    }
+    pub fn background(&self) -> &Background {
+        &self.background
+    }
+    pub fn settings(&self) -> &RenderSettings {
+        &self.settings
+    }
+    pub fn camera(&self) -> &Camera {
+        &self.cameras[self.camera]
+    }
+    pub fn cameras(&self) -> &[Camera] {
+        &self.cameras
+    }
+
+    // Adds a camera and returns its index for `select_camera`.
+    pub fn add_camera(&mut self, camera: Camera) -> usize {
+        self.cameras.push(camera);
+        self.cameras.len() - 1
+    }
+    pub fn select_camera(&mut self, index: usize) -> Option<&Camera> {
+        if index < self.cameras.len() {
+            self.camera = index;
+        }
+        self.cameras.get(index)
+    }
+}
@@ -0,0 +1,265 @@
+pub mod builder;
+pub mod lexer;
+pub mod parser;
+pub mod writer;
+
+use crate::scene::{RenderSettings, Scene};
+use std::fmt;
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug)]
+pub struct Error {
+    pub file: String,
+    pub position: Option<Position>,
+    pub message: String,
+}
+
+impl Error {
+    pub fn new(position: Position, message: &str) -> Self {
+        Error {
+            file: String::new(),
+            position: Some(position),
+            message: message.to_string(),
+        }
+    }
+    pub fn without_position(message: &str) -> Self {
+        Error {
+            file: String::new(),
+            position: None,
+            message: message.to_string(),
+        }
+    }
+    // Errors from files that the scene file refers to keep their own name.
+    pub fn in_file(self, file: &str) -> Self {
+        if !self.file.is_empty() {
+            return self;
+        }
+        Error {
+            file: file.to_string(),
+            ..self
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.position {
+            Some(p) => write!(f, "{}:{}:{}: {}", self.file, p.line, p.column, self.message),
+            None => write!(f, "{}: {}", self.file, self.message),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum ValueKind {
+    Number(f64),
+    Text(String),
+    Word(String),
+    Tuple(Vec<Value>),
+    Call(String, Vec<Value>),
+}
+
+#[derive(Clone, Debug)]
+pub struct Value {
+    pub kind: ValueKind,
+    pub position: Position,
+}
+
+#[derive(Clone, Debug)]
+pub struct Field {
+    pub name: String,
+    pub position: Position,
+    pub value: Value,
+}
+
+// A block such as `sphere { center = (0, 1, 0) radius = 1 }`.
+#[derive(Clone, Debug)]
+pub struct Item {
+    pub kind: String,
+    pub position: Position,
+    pub fields: Vec<Field>,
+}
+
+// Builds a scene from the text of a scene file as it is during `frame`.
+// `file` is only used in error messages and relative paths are resolved
+// against `directory`.
+pub fn parse(source: &str, file: &str, directory: &Path, frame: usize) -> Result<Scene, Error> {
+    lexer::tokenize(source)
+        .and_then(parser::parse)
+        .and_then(|items| builder::build(&items, directory, frame))
+        .map_err(|e| e.in_file(file))
+}
+
+pub fn save(path: &Path, scene: &Scene, settings: &RenderSettings) -> Result<(), Error> {
+    let file = path.display().to_string();
+    let text = writer::write(scene, settings).map_err(|e| e.in_file(&file))?;
+    std::fs::write(path, text).map_err(|e| Error::without_position(&e.to_string()).in_file(&file))
+}
+
+pub fn load(path: &Path, frame: usize) -> Result<Scene, Error> {
+    let file = path.display().to_string();
+    let source = std::fs::read_to_string(path)
+        .map_err(|e| Error::without_position(&e.to_string()).in_file(&file))?;
+    let directory = path.parent().unwrap_or(Path::new("."));
+    parse(&source, &file, directory, frame)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse, writer, Position};
+    use crate::point::Point;
+    use std::path::Path;
+
+    const SCENE: &str = "
+settings { width = 40 height = 20 samples = 1 }
+camera { look_from = (0, 0, 5) look_at = (0, 0, 0) }
+sphere { center = (0, 0, 0) radius = 1 material = metal(0.1) texture = checkered((1, 1, 1), (0, 0, 0)) }
+cube { min = (-1, -1, -1) max = (1e0, 1, 1) material = lambertian }
+cube { min = (0, 0, 0) max = (1, 1, 1) rotate = (0, 90, 0) translate = (0, 2, 0) material = lambertian }
+cube { min = (0, 0, 0) max = (1, 1, 1) translate = (3, 0, 0) end_translate = (3, 1, 0) material = lambertian }
+sphere { center = (0, 3, 0) end_center = (1, 3, 0) motion = (0, 0.5) radius = 0.5 material = lambertian }
+";
+
+    fn error_at(source: &str) -> Option<Position> {
+        parse(source, "test.scene", Path::new("."), 0)
+            .unwrap_err()
+            .position
+    }
+
+    #[test]
+    fn test_parse_scene() {
+        let scene = parse(SCENE, "test.scene", Path::new("."), 0).unwrap();
+        assert_eq!(scene.settings().width, 40);
+        assert_eq!(scene.settings().height, 20);
+        assert_eq!(scene.settings().max_depth, 50);
+    }
+    #[test]
+    fn test_error_positions() {
+        let at = |line, column| Some(Position { line, column });
+        assert_eq!(error_at("sphere {\n  radius = 1 @"), at(2, 14));
+        assert_eq!(error_at("sphere { radius = }"), at(1, 19));
+        assert_eq!(error_at("cone { }"), at(1, 1));
+        assert_eq!(
+            error_at("cylinder { base = (0, 1, 0) top = (0, 1, 0) radius = 1 }"),
+            at(1, 35)
+        );
+        assert_eq!(
+            error_at("disk { center = (0, 0, 0) normal = (0, 0, 0) radius = 1 }"),
+            at(1, 36)
+        );
+        assert_eq!(
+            error_at("quad { corner = (0, 0, 0) u = (1, 0, 0) v = (2, 0, 0) }"),
+            at(1, 45)
+        );
+        assert_eq!(
+            error_at("sphere { center = (0, 0, 0) radius = -1 }"),
+            at(1, 38)
+        );
+        assert_eq!(
+            error_at("torus { center = (0, 0, 0) axis = (0, 0, 0) }"),
+            at(1, 35)
+        );
+        assert_eq!(error_at("settings { samples = 0 }"), at(1, 22));
+        assert_eq!(error_at("settings {\n  width = 1 }"), at(2, 11));
+        assert_eq!(error_at("settings { fps = 0 }"), at(1, 18));
+        assert_eq!(error_at("union { bodies = (\"a\", \"b\") }"), at(1, 19));
+        assert_eq!(
+            error_at(
+                "sphere { name = \"a\" center = (0, 0, 0) radius = 1 material = ether }\n\
+                      medium { boundary = \"a\" density = grid((2, 2, 2), (1, 2)) }"
+            ),
+            at(2, 51)
+        );
+        assert_eq!(
+            error_at("settings { }\n sphere { radius = 1 scale = (1, 0, 1) }"),
+            at(2, 2)
+        );
+        assert_eq!(
+            error_at("camera { look_from = (0, 0, 1) look_at = (0, 0) }"),
+            at(1, 42)
+        );
+        let error = parse(
+            "sphere { center = (0, 0, 0) radius = 1 }",
+            "a.scene",
+            Path::new("."),
+            0,
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "a.scene:1:1: sphere is missing field 'material'"
+        );
+    }
+    #[test]
+    fn test_keys() {
+        let keyed = "
+settings { fps = 2 }
+camera { look_from = catmull_rom((0, (0, 0, 5)), (1, (0, 0, 7))) look_at = (0, 0, 0) vfov = linear((0, 20), (2, 40)) }
+sphere { center = (0, 0, 0) radius = 1 translate = linear((0, (0, 0, 0)), (1, (2, 0, 0))) material = lambertian }
+";
+        // Frame 1 runs from 0.5 to 1 seconds.
+        let scene = parse(keyed, "test.scene", Path::new("."), 1).unwrap();
+        let camera = scene.camera();
+        assert!((camera.look_from() - Point::new(0., 0., 6.)).length() < 1e-12);
+        assert_eq!(camera.vfov(), 25.);
+        assert_eq!(camera.shutter(), (0.5, 0.5));
+        let text = writer::write(&scene, scene.settings()).unwrap();
+        assert!(text.contains("matrix = ((1, 0, 0, 1), (0, 1, 0, 0), (0, 0, 1, 0))"));
+        assert!(text.contains("end_matrix = ((1, 0, 0, 2), (0, 1, 0, 0), (0, 0, 1, 0))"));
+        assert!(text.contains("motion = (0.5, 1)"));
+
+        let at = |line, column| Some(Position { line, column });
+        assert_eq!(
+            error_at("sphere { center = (0, 0, 0) radius = linear((1, 1), (0, 2)) material = lambertian }"),
+            at(1, 54)
+        );
+        assert_eq!(
+            error_at("sphere { center = linear() radius = 1 }"),
+            at(1, 19)
+        );
+        assert_eq!(
+            error_at("sphere { radius = 1 rotate = linear((0, (0, 0, 0))) end_scale = 2 }"),
+            at(1, 1)
+        );
+    }
+    #[test]
+    fn test_write_round_trip() {
+        let scene = parse(SCENE, "test.scene", Path::new("."), 0).unwrap();
+        let text = writer::write(&scene, scene.settings()).unwrap();
+        let reparsed = parse(&text, "test.scene", Path::new("."), 0).unwrap();
+        // The world is written in BVH order, which depends on how it was built.
+        let blocks = |text: &str| {
+            let mut blocks: Vec<String> =
+                text.split("\n\n").map(|b| b.trim().to_string()).collect();
+            blocks.sort();
+            blocks
+        };
+        let rewritten = writer::write(&reparsed, reparsed.settings()).unwrap();
+        assert_eq!(blocks(&rewritten), blocks(&text));
+        assert!(text.contains("texture = checkered((1, 1, 1), (0, 0, 0))"));
+        assert!(text.contains("matrix = (("));
+        assert!(text.contains("end_matrix = ((1, 0, 0, 3), (0, 1, 0, 1), (0, 0, 1, 0))"));
+        assert!(text.contains("motion = (0, 0.5)"));
+
+        // Parts are named by where they are written, so a body made of parts
+        // is only written the same way when nothing comes before it.
+        let medium = "
+camera { look_from = (0, 0, 5) look_at = (0, 0, 0) }
+sphere { name = \"smoke\" center = (3, 0, 0) radius = 1 material = lambertian }
+medium { boundary = \"smoke\" density = grid((2, 2, 2), (0, 1, 0, 1, 0, 1, 0, 1)) material = henyey_greenstein(0.5) }
+";
+        let scene = parse(medium, "test.scene", Path::new("."), 0).unwrap();
+        let text = writer::write(&scene, scene.settings()).unwrap();
+        let reparsed = parse(&text, "test.scene", Path::new("."), 0).unwrap();
+        assert_eq!(writer::write(&reparsed, reparsed.settings()).unwrap(), text);
+        assert!(text.contains("density = grid((2, 2, 2), (0, 1, 0, 1, 0, 1, 0, 1))"));
+    }
+}
@@ -0,0 +1,1068 @@
+use crate::animation::{Interpolation, Track};
+use crate::bodies::bodyprops::{Material, Phase};
+use crate::bodies::collision::AABB;
+use crate::bodies::cone::Caps;
+use crate::bodies::csg::Operation;
+use crate::bodies::heightfield::{Grid, Source};
+use crate::bodies::medium::Density;
+use crate::bodies::motion::Motion;
+use crate::bodies::quad::Shape;
+use crate::bodies::rect::Axis;
+use crate::bodies::{
+    Body, BodyProps, Capsule, Cone, Csg, Cube, Cylinder, Disk, DistanceField, Heightfield, Medium,
+    Quad, Rect, Sphere, Texture, Torus, Transformed, Triangle, TriangleMesh, BVH,
+};
+use crate::import::{netpbm, Model};
+use crate::matrix::Matrix4;
+use crate::noise::Noise;
+use crate::point::Point;
+use crate::raytracer::Camera;
+use crate::scene::{Background, RenderSettings, Scene};
+use crate::scenefile::{Error, Item, Position, Value, ValueKind};
+use crate::sdf::{self, Sdf};
+use std::collections::HashMap;
+use std::ops::{Add, Mul, Sub};
+use std::path::Path;
+use std::sync::Arc;
+
+impl Value {
+    fn error(&self, message: &str) -> Error {
+        Error::new(self.position, message)
+    }
+
+    pub fn number(&self) -> Result<f64, Error> {
+        match self.kind {
+            ValueKind::Number(n) => Ok(n),
+            _ => Err(self.error("expected a number")),
+        }
+    }
+    pub fn count(&self) -> Result<usize, Error> {
+        let n = self.number()?;
+        if n < 0. || n.fract() != 0. {
+            return Err(self.error("expected a whole number"));
+        }
+        Ok(n as usize)
+    }
+    pub fn point(&self) -> Result<Point, Error> {
+        match &self.kind {
+            ValueKind::Tuple(values) if values.len() == 3 => Ok(Point::new(
+                values[0].number()?,
+                values[1].number()?,
+                values[2].number()?,
+            )),
+            _ => Err(self.error("expected a vector such as (0, 1, 0)")),
+        }
+    }
+    // The values of a tuple such as `((0, 0), (1, 0), (0, 1))`.
+    pub fn tuple(&self, length: usize) -> Result<&[Value], Error> {
+        match &self.kind {
+            ValueKind::Tuple(values) if values.len() == length => Ok(values),
+            _ => Err(self.error(&format!("expected a tuple of {} values", length))),
+        }
+    }
+    pub fn elements(&self) -> Result<&[Value], Error> {
+        match &self.kind {
+            ValueKind::Tuple(values) => Ok(values),
+            _ => Err(self.error("expected a tuple")),
+        }
+    }
+    pub fn uv(&self) -> Result<(f64, f64), Error> {
+        let uv = self.tuple(2)?;
+        Ok((uv[0].number()?, uv[1].number()?))
+    }
+    pub fn text(&self) -> Result<&str, Error> {
+        match &self.kind {
+            ValueKind::Text(text) => Ok(text),
+            _ => Err(self.error("expected a string")),
+        }
+    }
+    pub fn word(&self) -> Result<&str, Error> {
+        match &self.kind {
+            ValueKind::Word(word) => Ok(word),
+            _ => Err(self.error("expected a name")),
+        }
+    }
+
+    // The name and arguments of `name(...)`, or of a bare `name`.
+    fn call(&self, what: &str) -> Result<(&str, &[Value]), Error> {
+        match &self.kind {
+            ValueKind::Word(name) => Ok((name, &[])),
+            ValueKind::Call(name, arguments) => Ok((name, arguments)),
+            _ => Err(self.error(&format!("expected a {}", what))),
+        }
+    }
+}
+
+fn arguments<'a>(
+    value: &Value,
+    arguments: &'a [Value],
+    count: usize,
+) -> Result<&'a [Value], Error> {
+    if arguments.len() != count {
+        return Err(value.error(&format!("expected {} arguments", count)));
+    }
+    Ok(arguments)
+}
+
+pub fn material(value: &Value) -> Result<Material, Error> {
+    let (name, args) = value.call("material")?;
+    match name {
+        "lambertian" => arguments(value, args, 0).map(|_| Material::Lambertian),
+        "ether" => arguments(value, args, 0).map(|_| Material::Ether),
+        "metal" => Ok(Material::Metal(arguments(value, args, 1)?[0].number()?)),
+        "dielectric" => Ok(Material::Dielectric(
+            arguments(value, args, 1)?[0].number()?,
+        )),
+        "isotropic" => arguments(value, args, 0).map(|_| Material::Volume(Phase::Isotropic)),
+        "henyey_greenstein" => {
+            let g = &arguments(value, args, 1)?[0];
+            match g.number()? {
+                g if g > -1. && g < 1. => Ok(Material::Volume(Phase::HenyeyGreenstein(g))),
+                _ => Err(g.error("expected a number between -1 and 1")),
+            }
+        }
+        _ => Err(value.error(&format!(
+            "unknown material '{}', expected lambertian, metal, dielectric, ether, \
+             isotropic or henyey_greenstein",
+            name
+        ))),
+    }
+}
+
+fn color(value: &Value, args: &[Value]) -> Result<Point, Error> {
+    let args = arguments(value, args, 3)?;
+    Ok(Point::new(
+        args[0].number()?,
+        args[1].number()?,
+        args[2].number()?,
+    ))
+}
+
+pub fn texture(value: &Value) -> Result<Texture, Error> {
+    if let ValueKind::Tuple(_) = value.kind {
+        return Ok(Texture::SolidColor(value.point()?));
+    }
+    let (name, args) = value.call("texture")?;
+    match name {
+        "solid" => Ok(Texture::SolidColor(color(value, args)?)),
+        "noise" => Ok(Texture::Noise(color(value, args)?)),
+        "vertex_color" if args.is_empty() => Ok(Texture::VertexColor(Point::new(1., 1., 1.))),
+        "vertex_color" => Ok(Texture::VertexColor(color(value, args)?)),
+        "checkered" => {
+            let args = arguments(value, args, 2)?;
+            Ok(Texture::Checkered(args[0].point()?, args[1].point()?))
+        }
+        _ => Err(value.error(&format!(
+            "unknown texture '{}', expected solid, noise, checkered or vertex_color",
+            name
+        ))),
+    }
+}
+
+// A value that changes over time, given by keys such as
+// `catmull_rom((0, (0, 1, 5)), (2, (5, 1, 0)))`, at `time`. Other values are
+// the same at any time.
+fn keyed<T>(
+    value: &Value,
+    time: f64,
+    parse: impl Fn(&Value) -> Result<T, Error>,
+) -> Result<T, Error>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f64, Output = T>,
+{
+    let (interpolation, keys) = match &value.kind {
+        ValueKind::Call(name, keys) if name == "linear" => (Interpolation::Linear, keys),
+        ValueKind::Call(name, keys) if name == "catmull_rom" => (Interpolation::CatmullRom, keys),
+        _ => return parse(value),
+    };
+    let mut track: Vec<(f64, T)> = vec![];
+    for key in keys {
+        let pair = key.tuple(2)?;
+        let key_time = pair[0].number()?;
+        if track.last().is_some_and(|(t, _)| key_time <= *t) {
+            return Err(pair[0].error("keys have to be in order of time"));
+        }
+        track.push((key_time, parse(&pair[1])?));
+    }
+    if track.is_empty() {
+        return Err(value.error("expected at least one key"));
+    }
+    Ok(Track::new(interpolation, track).at(time))
+}
+
+fn is_keyed(value: &Value) -> bool {
+    matches!(&value.kind, ValueKind::Call(name, _) if name == "linear" || name == "catmull_rom")
+}
+
+// Hands out the fields of an item and reports the ones nobody asked for.
+pub struct Fields<'a> {
+    item: &'a Item,
+    used: Vec<bool>,
+    // When the frame being built starts and ends. Keyed values are taken at
+    // its start.
+    frame: (f64, f64),
+}
+
+impl<'a> Fields<'a> {
+    pub fn new(item: &'a Item) -> Result<Self, Error> {
+        for (i, field) in item.fields.iter().enumerate() {
+            if item.fields[..i].iter().any(|f| f.name == field.name) {
+                return Err(Error::new(
+                    field.position,
+                    &format!("duplicate field '{}'", field.name),
+                ));
+            }
+        }
+        Ok(Fields {
+            item,
+            used: vec![false; item.fields.len()],
+            frame: (0., 0.),
+        })
+    }
+    pub fn with_frame(self, start: f64, end: f64) -> Self {
+        Fields {
+            frame: (start, end),
+            ..self
+        }
+    }
+    pub fn position(&self) -> Position {
+        self.item.position
+    }
+
+    pub fn optional(&mut self, name: &str) -> Option<&'a Value> {
+        let index = self.item.fields.iter().position(|f| f.name == name)?;
+        self.used[index] = true;
+        Some(&self.item.fields[index].value)
+    }
+    pub fn required(&mut self, name: &str) -> Result<&'a Value, Error> {
+        self.optional(name).ok_or_else(|| {
+            Error::new(
+                self.item.position,
+                &format!("{} is missing field '{}'", self.item.kind, name),
+            )
+        })
+    }
+
+    pub fn number(&mut self, name: &str) -> Result<f64, Error> {
+        keyed(self.required(name)?, self.frame.0, Value::number)
+    }
+    pub fn number_or(&mut self, name: &str, default: f64) -> Result<f64, Error> {
+        let time = self.frame.0;
+        self.optional(name)
+            .map_or(Ok(default), |v| keyed(v, time, Value::number))
+    }
+    pub fn point(&mut self, name: &str) -> Result<Point, Error> {
+        keyed(self.required(name)?, self.frame.0, Value::point)
+    }
+    pub fn point_or(&mut self, name: &str, default: Point) -> Result<Point, Error> {
+        let time = self.frame.0;
+        self.optional(name)
+            .map_or(Ok(default), |v| keyed(v, time, Value::point))
+    }
+    // A direction such as a normal or an axis, which can not be zero.
+    pub fn direction(&mut self, name: &str) -> Result<Point, Error> {
+        let direction = self.point(name)?;
+        if direction.length_squared() == 0. {
+            return Err(self.error_at(name, &format!("{} must not be zero", name)));
+        }
+        Ok(direction)
+    }
+    pub fn direction_or(&mut self, name: &str, default: Point) -> Result<Point, Error> {
+        let direction = self.point_or(name, default)?;
+        if direction.length_squared() == 0. {
+            return Err(self.error_at(name, &format!("{} must not be zero", name)));
+        }
+        Ok(direction)
+    }
+    // An error at the value of `name`, or at the item if it has none.
+    pub fn error_at(&mut self, name: &str, message: &str) -> Error {
+        match self.optional(name) {
+            Some(value) => value.error(message),
+            None => Error::new(self.position(), message),
+        }
+    }
+    pub fn body_props(&mut self) -> Result<BodyProps, Error> {
+        let material = material(self.required("material")?)?;
+        let texture = self
+            .optional("texture")
+            .map_or(Ok(Texture::new_color(1., 1., 1.)), texture)?;
+        Ok(BodyProps::new(texture, material))
+    }
+
+    // The item without its name and the fields that place it.
+    fn unplaced(&self) -> Item {
+        let mut item = self.item.clone();
+        item.fields.retain(|f| {
+            let name = f.name.trim_start_matches("end_");
+            f.name != "name" && !["matrix", "scale", "rotate", "translate"].contains(&name)
+        });
+        item
+    }
+    fn use_all(&mut self) {
+        self.used.fill(true);
+    }
+
+    pub fn finish(self) -> Result<(), Error> {
+        match self.used.iter().position(|used| !used) {
+            Some(i) => {
+                let field = &self.item.fields[i];
+                Err(Error::new(
+                    field.position,
+                    &format!("unknown field '{}' for {}", field.name, self.item.kind),
+                ))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+// The top of a cylinder, cone or capsule, which can not be its base.
+fn top(fields: &mut Fields) -> Result<Point, Error> {
+    let (base, top) = (fields.point("base")?, fields.point("top")?);
+    if (top - base).length_squared() == 0. {
+        return Err(fields.error_at("top", "top must not be the same as base"));
+    }
+    Ok(top)
+}
+
+fn settings(fields: &mut Fields) -> Result<RenderSettings, Error> {
+    let default = RenderSettings::new(400, 225, 100, 50);
+    // Images are at least 2 pixels wide and high, and the colour of a pixel
+    // is the average of at least one sample.
+    let count = |fields: &mut Fields, name, default, least| match fields.optional(name) {
+        None => Ok(default),
+        Some(value) => match value.count()? {
+            n if n < least => Err(value.error(&format!("{} must be at least {}", name, least))),
+            n => Ok(n),
+        },
+    };
+    let mut settings = RenderSettings::new(
+        count(fields, "width", default.width, 2)?,
+        count(fields, "height", default.height, 2)?,
+        count(fields, "samples", default.samples_per_pixel, 1)?,
+        count(fields, "max_depth", default.max_depth, 0)?,
+    );
+    if let Some(value) = fields.optional("fps") {
+        settings.fps = value.number()?;
+        if settings.fps <= 0. {
+            return Err(value.error("fps must be more than 0"));
+        }
+    }
+    settings.seed = fields
+        .optional("seed")
+        .map(|v| v.count())
+        .transpose()?
+        .map(|s| s as u64);
+    Ok(settings)
+}
+
+fn camera(fields: &mut Fields, aspect_ratio: f64) -> Result<Camera, Error> {
+    let look_from = fields.point("look_from")?;
+    let look_at = fields.point("look_at")?;
+    let (open, close) = fields
+        .optional("shutter")
+        .map_or(Ok((0., 0.)), |v| v.uv())?;
+    let camera = Camera::new(
+        look_from,
+        look_at,
+        fields.point_or("vup", Point::new(0., 1., 0.))?,
+        fields.number_or("vfov", 20.)?,
+        aspect_ratio,
+        fields.number_or("aperture", 0.)?,
+        fields.number_or("focus_dist", (look_from - look_at).length())?,
+    );
+    // The shutter opens and closes relative to the start of the frame.
+    let start = fields.frame.0;
+    Ok(camera.with_shutter(start + open, start + close))
+}
+
+fn background(fields: &mut Fields) -> Result<Background, Error> {
+    if let Some(color) = fields.optional("color") {
+        return Ok(Background::Solid(color.point()?));
+    }
+    match Background::sky() {
+        Background::Gradient(bottom, top) => Ok(Background::Gradient(
+            fields.point_or("bottom", bottom)?,
+            fields.point_or("top", top)?,
+        )),
+        sky => Ok(sky),
+    }
+}
+
+pub fn matrix(value: &Value) -> Result<Matrix4, Error> {
+    let mut rows = Matrix4::identity().rows();
+    for (row, values) in rows.iter_mut().zip(value.tuple(3)?) {
+        for (cell, value) in row.iter_mut().zip(values.tuple(4)?) {
+            *cell = value.number()?;
+        }
+    }
+    Ok(Matrix4::new(rows))
+}
+
+// Any body can be placed by a `matrix`, followed by `scale`, `rotate` (in
+// degrees around x, then y, then z) and `translate`. Where a moving body
+// ends up is given by the same fields starting with `end_`, and those that
+// are left out stay as they are.
+fn transform(fields: &mut Fields, end: bool, time: f64) -> Result<Option<Matrix4>, Error> {
+    fn field<'a>(fields: &mut Fields<'a>, end: bool, name: &str) -> Option<&'a Value> {
+        if end {
+            fields
+                .optional(&format!("end_{}", name))
+                .or_else(|| fields.optional(name))
+        } else {
+            fields.optional(name)
+        }
+    }
+    let matrix = field(fields, end, "matrix").map(matrix).transpose()?;
+    let scale = |value: &Value| match value.kind {
+        ValueKind::Number(n) => Ok(Point::new(n, n, n)),
+        _ => value.point(),
+    };
+    let scale = field(fields, end, "scale")
+        .map(|v| keyed(v, time, scale))
+        .transpose()?;
+    let rotate = field(fields, end, "rotate")
+        .map(|v| keyed(v, time, Value::point))
+        .transpose()?;
+    let translate = field(fields, end, "translate")
+        .map(|v| keyed(v, time, Value::point))
+        .transpose()?;
+    if matrix.is_none() && scale.is_none() && rotate.is_none() && translate.is_none() {
+        return Ok(None);
+    }
+
+    let mut to_world = matrix.unwrap_or(Matrix4::identity());
+    if let Some(scale) = scale {
+        to_world = Matrix4::scaling(scale) * to_world;
+    }
+    if let Some(rotate) = rotate {
+        to_world = Matrix4::rotation(Point::new(1., 0., 0.), rotate.x) * to_world;
+        to_world = Matrix4::rotation(Point::new(0., 1., 0.), rotate.y) * to_world;
+        to_world = Matrix4::rotation(Point::new(0., 0., 1.), rotate.z) * to_world;
+    }
+    if let Some(translate) = translate {
+        to_world = Matrix4::translation(translate) * to_world;
+    }
+    if to_world.inverse().is_none() {
+        return Err(Error::new(
+            fields.position(),
+            "the transform flattens the body",
+        ));
+    }
+    Ok(Some(to_world))
+}
+
+// From when to when a body moves, by default from 0 to 1.
+fn motion(fields: &mut Fields) -> Result<Motion, Error> {
+    match fields.optional("motion") {
+        Some(value) => {
+            let (start, end) = value.uv()?;
+            Ok(Motion::new(start, end))
+        }
+        None => Ok(Motion::new(0., 1.)),
+    }
+}
+
+// Bodies that only differ in where they are placed share one copy, so a
+// model can be placed many times while it is loaded only once.
+fn placed(
+    kind: &str,
+    fields: &mut Fields,
+    directory: &Path,
+    shared: &mut HashMap<String, Arc<dyn Body>>,
+    parts: &HashMap<String, Arc<dyn Body>>,
+) -> Result<Box<dyn Body>, Error> {
+    let moving = ["matrix", "scale", "rotate", "translate"]
+        .iter()
+        .any(|name| fields.optional(&format!("end_{}", name)).is_some());
+    // A body with keyed placement moves over each frame.
+    let keyed = ["scale", "rotate", "translate"]
+        .iter()
+        .any(|name| fields.optional(name).is_some_and(is_keyed));
+    if moving && keyed {
+        return Err(Error::new(
+            fields.position(),
+            "a body moves either by keys or by end_ fields",
+        ));
+    }
+    let (start, end) = fields.frame;
+    let to_world = match (transform(fields, false, start)?, moving) {
+        (Some(to_world), _) => to_world,
+        (None, true) => Matrix4::identity(),
+        (None, false) => return body(kind, fields, directory, parts),
+    };
+    let key = fields.unplaced().to_string();
+    let body = match shared.get(&key) {
+        Some(body) => {
+            fields.use_all();
+            body.clone()
+        }
+        None => {
+            let body: Arc<dyn Body> = Arc::from(body(kind, fields, directory, parts)?);
+            shared.insert(key, body.clone());
+            body
+        }
+    };
+    let placed = Transformed::new(body, to_world);
+    if keyed && end > start {
+        let end_to_world = transform(fields, false, end)?.unwrap_or(to_world);
+        return Ok(Box::new(
+            placed.with_motion(end_to_world, Motion::new(start, end)),
+        ));
+    }
+    if !moving {
+        return Ok(Box::new(placed));
+    }
+    let end_to_world = transform(fields, true, start)?.unwrap_or(Matrix4::identity());
+    Ok(Box::new(placed.with_motion(end_to_world, motion(fields)?)))
+}
+
+fn axis(value: &Value) -> Result<Axis, Error> {
+    match value.word()? {
+        "xy" => Ok(Axis::XY),
+        "xz" => Ok(Axis::XZ),
+        "yz" => Ok(Axis::YZ),
+        _ => Err(value.error("expected xy, xz or yz")),
+    }
+}
+
+// A shape such as `smooth_union(0.2, sphere(1), translate((1, 0, 0), box((1, 1, 1))))`.
+fn sdf(value: &Value) -> Result<Box<dyn Sdf>, Error> {
+    let (name, args) = value.call("shape")?;
+    let shape: Box<dyn Sdf> = match name {
+        "sphere" => Box::new(sdf::Sphere {
+            radius: arguments(value, args, 1)?[0].number()?,
+        }),
+        "box" => Box::new(sdf::Cuboid {
+            half_size: arguments(value, args, 1)?[0].point()?,
+        }),
+        "torus" => {
+            let args = arguments(value, args, 2)?;
+            Box::new(sdf::Torus {
+                major_radius: args[0].number()?,
+                minor_radius: args[1].number()?,
+            })
+        }
+        "cylinder" => {
+            let args = arguments(value, args, 2)?;
+            Box::new(sdf::Cylinder {
+                radius: args[0].number()?,
+                half_height: args[1].number()?,
+            })
+        }
+        "translate" => {
+            let args = arguments(value, args, 2)?;
+            Box::new(sdf::Translate {
+                offset: args[0].point()?,
+                shape: sdf(&args[1])?,
+            })
+        }
+        "union" => {
+            let args = arguments(value, args, 2)?;
+            Box::new(sdf::Union {
+                a: sdf(&args[0])?,
+                b: sdf(&args[1])?,
+            })
+        }
+        "smooth_union" => {
+            let args = arguments(value, args, 3)?;
+            Box::new(sdf::SmoothUnion {
+                radius: args[0].number()?,
+                a: sdf(&args[1])?,
+                b: sdf(&args[2])?,
+            })
+        }
+        "repeat" => {
+            let args = arguments(value, args, 2)?;
+            Box::new(sdf::Repeat {
+                period: args[0].point()?,
+                shape: sdf(&args[1])?,
+            })
+        }
+        "twist" => {
+            let args = arguments(value, args, 3)?;
+            Box::new(sdf::Twist {
+                degrees: args[0].number()?,
+                reach: args[1].number()?,
+                shape: sdf(&args[2])?,
+            })
+        }
+        "round" => {
+            let args = arguments(value, args, 2)?;
+            Box::new(sdf::Round {
+                radius: args[0].number()?,
+                shape: sdf(&args[1])?,
+            })
+        }
+        "mandelbulb" => {
+            let args = arguments(value, args, 2)?;
+            Box::new(sdf::Mandelbulb {
+                power: args[0].number()?,
+                iterations: args[1].count()?,
+            })
+        }
+        "menger" => Box::new(sdf::MengerSponge {
+            iterations: arguments(value, args, 1)?[0].count()?,
+        }),
+        _ => {
+            return Err(value.error(&format!(
+                "unknown shape '{}', expected sphere, box, torus, cylinder, translate, union, \
+                 smooth_union, repeat, twist, round, mandelbulb or menger",
+                name
+            )))
+        }
+    };
+    Ok(shape)
+}
+
+fn caps(value: &Value) -> Result<Caps, Error> {
+    match value.word()? {
+        "both" => Ok(Caps::Both),
+        "base" => Ok(Caps::Base),
+        "top" => Ok(Caps::Top),
+        "none" => Ok(Caps::None),
+        _ => Err(value.error("expected both, base, top or none")),
+    }
+}
+
+fn shape(value: &Value) -> Result<Shape, Error> {
+    match value.word()? {
+        "parallelogram" => Ok(Shape::Parallelogram),
+        "triangle" => Ok(Shape::Triangle),
+        _ => Err(value.error("expected parallelogram or triangle")),
+    }
+}
+
+// Vertex attributes need one value for every position.
+fn per_vertex(value: &Value, count: usize) -> Result<&[Value], Error> {
+    let values = value.elements()?;
+    if values.len() == count {
+        Ok(values)
+    } else {
+        Err(value.error(&format!("expected {} values", count)))
+    }
+}
+
+fn mesh(fields: &mut Fields) -> Result<TriangleMesh, Error> {
+    let positions = fields.required("positions")?;
+    let positions = positions
+        .elements()?
+        .iter()
+        .map(|p| p.point())
+        .collect::<Result<Vec<Point>, Error>>()?;
+    let value = fields.required("indices")?;
+    let mut indices = vec![];
+    for triangle in value.elements()? {
+        let corners = triangle.tuple(3)?;
+        let mut triangle = [0; 3];
+        for (index, corner) in triangle.iter_mut().zip(corners) {
+            let i = corner.count()?;
+            if i >= positions.len() {
+                return Err(corner.error("index is out of range"));
+            }
+            *index = i as u32;
+        }
+        indices.push(triangle);
+    }
+    if indices.is_empty() {
+        return Err(value.error("a mesh needs at least one triangle"));
+    }
+    let count = positions.len();
+    let normals = fields
+        .optional("normals")
+        .map(|v| per_vertex(v, count))
+        .transpose()?;
+    let uvs = fields
+        .optional("uvs")
+        .map(|v| per_vertex(v, count))
+        .transpose()?;
+    let colors = fields
+        .optional("colors")
+        .map(|v| per_vertex(v, count))
+        .transpose()?;
+
+    let mut mesh = TriangleMesh::new(positions, indices, fields.body_props()?);
+    if let Some(normals) = normals {
+        let normals = normals
+            .iter()
+            .map(|n| n.point())
+            .collect::<Result<_, _>>()?;
+        mesh = mesh.with_normals(normals);
+    }
+    if let Some(colors) = colors {
+        let colors = colors.iter().map(|c| c.point()).collect::<Result<_, _>>()?;
+        mesh = mesh.with_colors(colors);
+    }
+    if let Some(uvs) = uvs {
+        mesh = mesh.with_uvs(uvs.iter().map(|uv| uv.uv()).collect::<Result<_, _>>()?);
+    }
+    Ok(mesh)
+}
+
+// A body made of named bodies, `bodies = ("a", "b", "c")` combines the first
+// two and then the result with the third.
+fn csg(
+    operation: Operation,
+    fields: &mut Fields,
+    parts: &HashMap<String, Arc<dyn Body>>,
+) -> Result<Csg, Error> {
+    let value = fields.required("bodies")?;
+    let part = |value: &Value| {
+        let name = value.text()?;
+        parts
+            .get(name)
+            .cloned()
+            .ok_or_else(|| value.error(&format!("unknown body '{}'", name)))
+    };
+    let names = value.elements()?;
+    if names.len() < 2 {
+        return Err(value.error("expected at least two bodies"));
+    }
+    let mut body = Csg::new(operation, part(&names[0])?, part(&names[1])?);
+    for name in &names[2..] {
+        body = Csg::new(operation, Arc::new(body), part(name)?);
+    }
+    Ok(body)
+}
+
+// A number for a constant density, `noise(seed, frequency, octaves, density)`
+// or `grid((x, y, z), (values, ...))` with the number of samples along each
+// axis.
+fn density(value: &Value) -> Result<Density, Error> {
+    let nonnegative = |v: &Value| -> Result<f64, Error> {
+        match v.number()? {
+            n if n < 0. => Err(v.error("a density can not be negative")),
+            n => Ok(n),
+        }
+    };
+    if let ValueKind::Number(_) = value.kind {
+        return Ok(Density::Constant(nonnegative(value)?));
+    }
+    let (name, args) = value.call("density")?;
+    match name {
+        "noise" => {
+            let args = arguments(value, args, 4)?;
+            let noise = Noise::new(args[0].count()? as u64, args[1].number()?, args[2].count()?);
+            Ok(Density::Noise(noise, nonnegative(&args[3])?))
+        }
+        "grid" => {
+            let args = arguments(value, args, 2)?;
+            let sizes = args[0].tuple(3)?;
+            let mut resolution = [0; 3];
+            for (n, size) in resolution.iter_mut().zip(sizes) {
+                *n = size.count()?;
+                if *n < 2 {
+                    return Err(size.error("expected at least 2 samples"));
+                }
+            }
+            let values = args[1]
+                .elements()?
+                .iter()
+                .map(nonnegative)
+                .collect::<Result<Vec<_>, _>>()?;
+            if values.len() != resolution.iter().product() {
+                return Err(args[1].error(&format!(
+                    "expected {} values",
+                    resolution.iter().product::<usize>()
+                )));
+            }
+            Ok(Density::Grid { resolution, values })
+        }
+        _ => Err(value.error(&format!(
+            "unknown density '{}', expected a number, noise or grid",
+            name
+        ))),
+    }
+}
+
+// Heights from a grayscale image or from noise over the unit square.
+fn heightfield(fields: &mut Fields, directory: &Path) -> Result<Heightfield, Error> {
+    let (grid, source) = match (fields.optional("image"), fields.optional("noise")) {
+        (Some(image), None) => {
+            let path = directory.join(image.text()?);
+            let grid = netpbm::load(&path)?;
+            let path = path.canonicalize().unwrap_or(path);
+            (grid, Source::Image(path))
+        }
+        (None, Some(noise)) => {
+            let values = noise.tuple(3)?;
+            let noise = Noise::new(
+                values[0].count()? as u64,
+                values[1].number()?,
+                values[2].count()?,
+            );
+            let resolution = match fields.optional("resolution") {
+                Some(value) if value.count()? < 2 => {
+                    return Err(value.error("expected at least 2 samples"))
+                }
+                Some(value) => value.count()?,
+                None => 256,
+            };
+            let grid = Grid::from_noise(&noise, resolution);
+            (grid, Source::Noise(noise, resolution))
+        }
+        _ => {
+            return Err(Error::new(
+                fields.position(),
+                "heightfield needs either an image or noise",
+            ))
+        }
+    };
+    let field = Heightfield::new(
+        grid,
+        fields.point("corner")?,
+        fields.point("size")?,
+        fields.body_props()?,
+    );
+    Ok(field.with_source(source))
+}
+
+pub fn body(
+    kind: &str,
+    fields: &mut Fields,
+    directory: &Path,
+    parts: &HashMap<String, Arc<dyn Body>>,
+) -> Result<Box<dyn Body>, Error> {
+    let body: Box<dyn Body> = match kind {
+        "sphere" => {
+            let center = fields.point("center")?;
+            let radius = fields.number("radius")?;
+            if radius <= 0. {
+                return Err(fields.error_at("radius", "radius must be more than 0"));
+            }
+            let sphere = Sphere::new(center, radius, fields.body_props()?);
+            match fields.optional("end_center") {
+                Some(end_center) => {
+                    Box::new(sphere.with_motion(end_center.point()?, motion(fields)?))
+                }
+                None => Box::new(sphere),
+            }
+        }
+        "rect" => Box::new(Rect::new(
+            fields.number("a0")?,
+            fields.number("a1")?,
+            fields.number("b0")?,
+            fields.number("b1")?,
+            fields.number("k")?,
+            axis(fields.required("axis")?)?,
+            fields.body_props()?,
+        )),
+        "cube" => match fields.optional("corner") {
+            Some(corner) => {
+                let edges = fields.required("edges")?.tuple(3)?;
+                let edges = [edges[0].point()?, edges[1].point()?, edges[2].point()?];
+                Box::new(Cube::from_edges(
+                    corner.point()?,
+                    edges,
+                    fields.body_props()?,
+                ))
+            }
+            None => Box::new(Cube::new(
+                fields.point("min")?,
+                fields.point("max")?,
+                fields.body_props()?,
+            )),
+        },
+        "quad" => {
+            let (corner, u, v) = (
+                fields.point("corner")?,
+                fields.point("u")?,
+                fields.point("v")?,
+            );
+            if u.cross(v).length_squared() == 0. {
+                return Err(fields.error_at("v", "u and v must span a plane"));
+            }
+            let shape = fields.optional("shape").map(shape).transpose()?;
+            match shape.unwrap_or(Shape::Parallelogram) {
+                Shape::Parallelogram => Box::new(Quad::new(corner, u, v, fields.body_props()?)),
+                Shape::Triangle => Box::new(Quad::triangle(corner, u, v, fields.body_props()?)),
+            }
+        }
+        "cylinder" => Box::new(
+            Cylinder::new(
+                fields.point("base")?,
+                top(fields)?,
+                fields.number("radius")?,
+                fields.body_props()?,
+            )
+            .with_caps(fields.optional("caps").map_or(Ok(Caps::Both), caps)?),
+        ),
+        "cone" => Box::new(
+            Cone::new(
+                fields.point("base")?,
+                top(fields)?,
+                fields.number("base_radius")?,
+                fields.number_or("top_radius", 0.)?,
+                fields.body_props()?,
+            )
+            .with_caps(fields.optional("caps").map_or(Ok(Caps::Both), caps)?),
+        ),
+        "capsule" => Box::new(Capsule::new(
+            fields.point("base")?,
+            top(fields)?,
+            fields.number("radius")?,
+            fields.body_props()?,
+        )),
+        "torus" => Box::new(Torus::new(
+            fields.point("center")?,
+            fields.direction_or("axis", Point::new(0., 1., 0.))?,
+            fields.number("major_radius")?,
+            fields.number("minor_radius")?,
+            fields.body_props()?,
+        )),
+        "union" => Box::new(csg(Operation::Union, fields, parts)?),
+        "intersection" => Box::new(csg(Operation::Intersection, fields, parts)?),
+        "difference" => Box::new(csg(Operation::Difference, fields, parts)?),
+        "sdf" => {
+            let shape = sdf(fields.required("shape")?)?;
+            let bounds = AABB::new(fields.point("min")?, fields.point("max")?);
+            let mut field = DistanceField::new(shape, bounds, fields.body_props()?);
+            if let Some(steps) = fields.optional("steps") {
+                field = field.with_steps(steps.count()?);
+            }
+            if let Some(epsilon) = fields.optional("epsilon") {
+                field = field.with_epsilon(epsilon.number()?);
+            }
+            Box::new(field)
+        }
+        "heightfield" => Box::new(heightfield(fields, directory)?),
+        "medium" => {
+            let value = fields.required("boundary")?;
+            let name = value.text()?;
+            let boundary = parts
+                .get(name)
+                .cloned()
+                .ok_or_else(|| value.error(&format!("unknown body '{}'", name)))?;
+            let density = density(fields.required("density")?)?;
+            // Media scatter the same way in every direction unless told otherwise.
+            let material = fields
+                .optional("material")
+                .map_or(Ok(Material::Volume(Phase::Isotropic)), material)?;
+            let texture = fields
+                .optional("texture")
+                .map_or(Ok(Texture::new_color(1., 1., 1.)), texture)?;
+            Box::new(Medium::new(
+                boundary,
+                density,
+                BodyProps::new(texture, material),
+            ))
+        }
+        "disk" => Box::new(Disk::new(
+            fields.point("center")?,
+            fields.direction("normal")?,
+            fields.number("radius")?,
+            fields.body_props()?,
+        )),
+        "triangle" => {
+            let mut triangle = Triangle::new(
+                fields.point("a")?,
+                fields.point("b")?,
+                fields.point("c")?,
+                fields.body_props()?,
+            );
+            if let Some(normals) = fields.optional("normals") {
+                let n = normals.tuple(3)?;
+                triangle = triangle.with_normals([n[0].point()?, n[1].point()?, n[2].point()?]);
+            }
+            if let Some(uvs) = fields.optional("uvs") {
+                let uvs = uvs.tuple(3)?;
+                triangle = triangle.with_uvs([uvs[0].uv()?, uvs[1].uv()?, uvs[2].uv()?]);
+            }
+            Box::new(triangle)
+        }
+        "mesh" => Box::new(mesh(fields)?),
+        "obj" => {
+            let path = directory.join(fields.required("path")?.text()?);
+            let object = fields.optional("object").map(|v| v.text()).transpose()?;
+            // The material and texture are for faces without an MTL material.
+            let material = fields
+                .optional("material")
+                .map_or(Ok(Material::Lambertian), material)?;
+            let texture = fields
+                .optional("texture")
+                .map_or(Ok(Texture::new_color(0.8, 0.8, 0.8)), texture)?;
+            let body_props = BodyProps::new(texture, material);
+            Box::new(Model::load_obj(&path, object, body_props)?)
+        }
+        "ply" => {
+            let path = directory.join(fields.required("path")?.text()?);
+            let material = fields
+                .optional("material")
+                .map_or(Ok(Material::Lambertian), material)?;
+            let texture = fields.optional("texture").map(texture).transpose()?;
+            Box::new(Model::load_ply(&path, material, texture)?)
+        }
+        _ => {
+            return Err(Error::new(
+                fields.position(),
+                &format!("unknown item '{}'", kind),
+            ))
+        }
+    };
+    Ok(body)
+}
+
+// Builds the scene as it is during `frame`, which starts at `frame / fps`.
+pub fn build(items: &[Item], directory: &Path, frame: usize) -> Result<Scene, Error> {
+    let mut render_settings = None;
+    for item in items.iter().filter(|item| item.kind == "settings") {
+        if render_settings.is_some() {
+            return Err(Error::new(item.position, "duplicate settings"));
+        }
+        let mut fields = Fields::new(item)?;
+        render_settings = Some(settings(&mut fields)?);
+        fields.finish()?;
+    }
+    let settings = render_settings.unwrap_or(RenderSettings::new(400, 225, 100, 50));
+    let start = frame as f64 / settings.fps;
+    let end = (frame + 1) as f64 / settings.fps;
+
+    let mut cameras = vec![];
+    let mut sky = None;
+    let mut bodies = vec![];
+    let mut shared = HashMap::new();
+    // Bodies with a name are not placed in the world, but can be used to
+    // make up other bodies.
+    let mut parts = HashMap::new();
+    for item in items {
+        let mut fields = Fields::new(item)?.with_frame(start, end);
+        match item.kind.as_str() {
+            "settings" => continue,
+            "camera" => cameras.push(camera(&mut fields, settings.aspect_ratio())?),
+            "background" => {
+                if sky.is_some() {
+                    return Err(Error::new(item.position, "duplicate background"));
+                }
+                sky = Some(background(&mut fields)?);
+            }
+            kind => {
+                let name = fields.optional("name");
+                let body = placed(kind, &mut fields, directory, &mut shared, &parts)?;
+                match name {
+                    Some(name) => {
+                        if parts.contains_key(name.text()?) {
+                            return Err(name.error("duplicate body name"));
+                        }
+                        parts.insert(name.text()?.to_string(), Arc::from(body));
+                    }
+                    None => bodies.push(body),
+                }
+            }
+        }
+        fields.finish()?;
+    }
+
+    if bodies.is_empty() {
+        return Err(Error::without_position("the scene has no bodies"));
+    }
+    let mut cameras = cameras.into_iter();
+    let camera = cameras
+        .next()
+        .ok_or_else(|| Error::without_position("the scene has no camera"))?;
+    let mut scene = Scene::new(BVH::new(bodies), camera, settings)
+        .with_background(sky.unwrap_or(Background::sky()));
+    for camera in cameras {
+        scene.add_camera(camera);
+    }
+    Ok(scene)
+}
@@ -0,0 +1,126 @@
+use crate::scenefile::{Error, Position};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+    Ident(String),
+    Number(f64),
+    Text(String),
+    LeftBrace,
+    RightBrace,
+    LeftParen,
+    RightParen,
+    Comma,
+    Equals,
+    End,
+}
+
+impl Token {
+    pub fn describe(&self) -> String {
+        match self {
+            Token::Ident(name) => format!("'{}'", name),
+            Token::Number(n) => format!("number {}", n),
+            Token::Text(_) => "string".to_string(),
+            Token::LeftBrace => "'{'".to_string(),
+            Token::RightBrace => "'}'".to_string(),
+            Token::LeftParen => "'('".to_string(),
+            Token::RightParen => "')'".to_string(),
+            Token::Comma => "','".to_string(),
+            Token::Equals => "'='".to_string(),
+            Token::End => "end of file".to_string(),
+        }
+    }
+}
+
+pub fn tokenize(source: &str) -> Result<Vec<(Token, Position)>, Error> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    let mut position = Position { line: 1, column: 1 };
+
+    let advance = |i: &mut usize, position: &mut Position, count: usize| {
+        for _ in 0..count {
+            if chars[*i] == '\n' {
+                position.line += 1;
+                position.column = 1;
+            } else {
+                position.column += 1;
+            }
+            *i += 1;
+        }
+    };
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start = position;
+        let token = match c {
+            _ if c.is_whitespace() => {
+                advance(&mut i, &mut position, 1);
+                continue;
+            }
+            '#' => {
+                while i < chars.len() && chars[i] != '\n' {
+                    advance(&mut i, &mut position, 1);
+                }
+                continue;
+            }
+            '{' => Token::LeftBrace,
+            '}' => Token::RightBrace,
+            '(' => Token::LeftParen,
+            ')' => Token::RightParen,
+            ',' => Token::Comma,
+            '=' => Token::Equals,
+            '"' => {
+                let length = chars[i + 1..]
+                    .iter()
+                    .take_while(|&&c| c != '"' && c != '\n')
+                    .count();
+                if chars.get(i + 1 + length) != Some(&'"') {
+                    return Err(Error::new(start, "unterminated string"));
+                }
+                let text = chars[i + 1..i + 1 + length].iter().collect();
+                advance(&mut i, &mut position, length + 2);
+                tokens.push((Token::Text(text), start));
+                continue;
+            }
+            _ if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' => {
+                let mut length = 1;
+                while let Some(&next) = chars.get(i + length) {
+                    let exponent_sign =
+                        (next == '-' || next == '+') && matches!(chars[i + length - 1], 'e' | 'E');
+                    if next.is_ascii_digit()
+                        || next == '.'
+                        || next == 'e'
+                        || next == 'E'
+                        || exponent_sign
+                    {
+                        length += 1;
+                    } else {
+                        break;
+                    }
+                }
+                let text: String = chars[i..i + length].iter().collect();
+                let number = text
+                    .parse()
+                    .map_err(|_| Error::new(start, &format!("invalid number '{}'", text)))?;
+                advance(&mut i, &mut position, length);
+                tokens.push((Token::Number(number), start));
+                continue;
+            }
+            _ if c.is_alphabetic() || c == '_' => {
+                let length = chars[i..]
+                    .iter()
+                    .take_while(|c| c.is_alphanumeric() || **c == '_')
+                    .count();
+                let name = chars[i..i + length].iter().collect();
+                advance(&mut i, &mut position, length);
+                tokens.push((Token::Ident(name), start));
+                continue;
+            }
+            _ => return Err(Error::new(start, &format!("unexpected character '{}'", c))),
+        };
+        advance(&mut i, &mut position, 1);
+        tokens.push((token, start));
+    }
+    tokens.push((Token::End, position));
+    Ok(tokens)
+}
@@ -0,0 +1,120 @@
+use crate::scenefile::lexer::Token;
+use crate::scenefile::{Error, Field, Item, Position, Value, ValueKind};
+
+struct Parser {
+    tokens: Vec<(Token, Position)>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &(Token, Position) {
+        &self.tokens[self.next]
+    }
+    fn advance(&mut self) -> (Token, Position) {
+        let token = self.tokens[self.next].clone();
+        if token.0 != Token::End {
+            self.next += 1;
+        }
+        token
+    }
+    fn expect(&mut self, expected: Token) -> Result<Position, Error> {
+        let (token, position) = self.advance();
+        if token != expected {
+            return Err(Error::new(
+                position,
+                &format!(
+                    "expected {}, found {}",
+                    expected.describe(),
+                    token.describe()
+                ),
+            ));
+        }
+        Ok(position)
+    }
+    fn ident(&mut self, what: &str) -> Result<(String, Position), Error> {
+        match self.advance() {
+            (Token::Ident(name), position) => Ok((name, position)),
+            (token, position) => Err(Error::new(
+                position,
+                &format!("expected {}, found {}", what, token.describe()),
+            )),
+        }
+    }
+
+    fn item(&mut self) -> Result<Item, Error> {
+        let (kind, position) = self.ident("an item such as 'sphere' or 'camera'")?;
+        self.expect(Token::LeftBrace)?;
+        let mut fields = vec![];
+        while self.peek().0 != Token::RightBrace {
+            let (name, position) = self.ident("a field name or '}'")?;
+            self.expect(Token::Equals)?;
+            let value = self.value()?;
+            fields.push(Field {
+                name,
+                position,
+                value,
+            });
+        }
+        self.advance();
+        Ok(Item {
+            kind,
+            position,
+            fields,
+        })
+    }
+
+    // Parses values separated by commas up to the closing parenthesis.
+    fn arguments(&mut self) -> Result<Vec<Value>, Error> {
+        let mut values = vec![];
+        if self.peek().0 == Token::RightParen {
+            self.advance();
+            return Ok(values);
+        }
+        loop {
+            values.push(self.value()?);
+            match self.advance() {
+                (Token::Comma, _) => continue,
+                (Token::RightParen, _) => return Ok(values),
+                (token, position) => {
+                    return Err(Error::new(
+                        position,
+                        &format!("expected ',' or ')', found {}", token.describe()),
+                    ))
+                }
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, Error> {
+        let (token, position) = self.advance();
+        let kind = match token {
+            Token::Number(n) => ValueKind::Number(n),
+            Token::Text(text) => ValueKind::Text(text),
+            Token::LeftParen => ValueKind::Tuple(self.arguments()?),
+            Token::Ident(name) => {
+                if self.peek().0 == Token::LeftParen {
+                    self.advance();
+                    ValueKind::Call(name, self.arguments()?)
+                } else {
+                    ValueKind::Word(name)
+                }
+            }
+            token => {
+                return Err(Error::new(
+                    position,
+                    &format!("expected a value, found {}", token.describe()),
+                ))
+            }
+        };
+        Ok(Value { kind, position })
+    }
+}
+
+pub fn parse(tokens: Vec<(Token, Position)>) -> Result<Vec<Item>, Error> {
+    let mut parser = Parser { tokens, next: 0 };
+    let mut items = vec![];
+    while parser.peek().0 != Token::End {
+        items.push(parser.item()?);
+    }
+    Ok(items)
+}
@@ -0,0 +1,306 @@
+use crate::bodies::bodyprops::{Material, Phase};
+use crate::bodies::cone::Caps;
+use crate::bodies::motion::Motion;
+use crate::bodies::{
+    Body, BodyProps, Capsule, Cone, Csg, Cube, Cylinder, Disk, DistanceField, Heightfield, Medium,
+    Quad, Rect, Sphere, Texture, Torus, Transformed, Triangle, TriangleMesh, BVH,
+};
+use crate::import::Model;
+use crate::matrix::Matrix4;
+use crate::point::Point;
+use crate::raytracer::Camera;
+use crate::scene::{Background, RenderSettings, Scene};
+use crate::scenefile::{builder, Error, Field, Item, Position, Value, ValueKind};
+use std::any::Any;
+use std::fmt;
+
+const NOWHERE: Position = Position { line: 0, column: 0 };
+
+impl Value {
+    fn from_kind(kind: ValueKind) -> Self {
+        Value {
+            kind,
+            position: NOWHERE,
+        }
+    }
+    pub fn named(name: &str) -> Self {
+        Self::from_kind(ValueKind::Word(name.to_string()))
+    }
+    pub fn list(values: Vec<Value>) -> Self {
+        Self::from_kind(ValueKind::Tuple(values))
+    }
+    pub fn quoted(text: &str) -> Self {
+        Self::from_kind(ValueKind::Text(text.to_string()))
+    }
+    pub fn called(name: &str, arguments: Vec<Value>) -> Self {
+        Self::from_kind(ValueKind::Call(name.to_string(), arguments))
+    }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Self {
+        Value::from_kind(ValueKind::Number(n))
+    }
+}
+
+impl From<usize> for Value {
+    fn from(n: usize) -> Self {
+        Value::from(n as f64)
+    }
+}
+
+impl From<Point> for Value {
+    fn from(p: Point) -> Self {
+        Value::from_kind(ValueKind::Tuple(vec![p.x.into(), p.y.into(), p.z.into()]))
+    }
+}
+
+impl<V: Into<Value>, const N: usize> From<[V; N]> for Value {
+    fn from(values: [V; N]) -> Self {
+        Value::list(values.map(Into::into).into())
+    }
+}
+
+// Only the first three rows, the last one of an affine transform is implied.
+impl From<Matrix4> for Value {
+    fn from(matrix: Matrix4) -> Self {
+        let [a, b, c, _] = matrix.rows();
+        Value::from([a, b, c])
+    }
+}
+
+impl From<Caps> for Value {
+    fn from(caps: Caps) -> Self {
+        Value::named(match caps {
+            Caps::Both => "both",
+            Caps::Base => "base",
+            Caps::Top => "top",
+            Caps::None => "none",
+        })
+    }
+}
+
+impl From<Motion> for Value {
+    fn from(motion: Motion) -> Self {
+        [motion.start, motion.end].into()
+    }
+}
+
+impl From<Material> for Value {
+    fn from(material: Material) -> Self {
+        match material {
+            Material::Lambertian => Value::named("lambertian"),
+            Material::Ether => Value::named("ether"),
+            Material::Metal(fuzziness) => Value::called("metal", vec![fuzziness.into()]),
+            Material::Dielectric(index) => Value::called("dielectric", vec![index.into()]),
+            Material::Volume(Phase::Isotropic) => Value::named("isotropic"),
+            Material::Volume(Phase::HenyeyGreenstein(g)) => {
+                Value::called("henyey_greenstein", vec![g.into()])
+            }
+        }
+    }
+}
+
+impl From<Texture> for Value {
+    fn from(texture: Texture) -> Self {
+        let color = |name, c: Point| Value::called(name, vec![c.x.into(), c.y.into(), c.z.into()]);
+        match texture {
+            Texture::SolidColor(c) => color("solid", c),
+            Texture::Noise(c) => color("noise", c),
+            Texture::VertexColor(c) => color("vertex_color", c),
+            Texture::Checkered(even, odd) => {
+                Value::called("checkered", vec![even.into(), odd.into()])
+            }
+        }
+    }
+}
+
+impl Item {
+    pub fn new(kind: &str) -> Self {
+        Item {
+            kind: kind.to_string(),
+            position: NOWHERE,
+            fields: vec![],
+        }
+    }
+    pub fn field<V: Into<Value>>(mut self, name: &str, value: V) -> Self {
+        self.fields.push(Field {
+            name: name.to_string(),
+            position: NOWHERE,
+            value: value.into(),
+        });
+        self
+    }
+    pub fn body_props(self, body_props: &BodyProps) -> Self {
+        self.field("material", body_props.material())
+            .field("texture", body_props.texture())
+    }
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.fields.insert(
+            0,
+            Field {
+                name: "name".to_string(),
+                position: NOWHERE,
+                value: Value::quoted(name),
+            },
+        );
+        self
+    }
+    // Places the item by `to_world`, after the matrix it may already have.
+    pub fn transformed(mut self, to_world: Matrix4) -> Result<Self, Error> {
+        if !self.fields.iter().any(|f| f.name == "matrix") {
+            return Ok(self.field("matrix", to_world));
+        }
+        for field in &mut self.fields {
+            if field.name == "matrix" || field.name == "end_matrix" {
+                field.value = (to_world * builder::matrix(&field.value)?).into();
+            }
+        }
+        Ok(self)
+    }
+    // Places the item by `to_world` at the start of `motion` and by
+    // `end_to_world` at its end.
+    pub fn moved(
+        self,
+        to_world: Matrix4,
+        end_to_world: Matrix4,
+        motion: Motion,
+    ) -> Result<Self, Error> {
+        if self.fields.iter().any(|f| f.name == "motion") {
+            return Err(Error::without_position(&format!(
+                "a moving {} can not be exported inside a moving body",
+                self.kind
+            )));
+        }
+        let matrix = match self.fields.iter().find(|f| f.name == "matrix") {
+            Some(field) => builder::matrix(&field.value)?,
+            None => Matrix4::identity(),
+        };
+        Ok(self
+            .transformed(to_world)?
+            .field("end_matrix", end_to_world * matrix)
+            .field("motion", motion))
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let list = |f: &mut fmt::Formatter, values: &[Value]| {
+            let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+            write!(f, "({})", values.join(", "))
+        };
+        match &self.kind {
+            ValueKind::Number(n) => write!(f, "{}", n),
+            ValueKind::Text(text) => write!(f, "\"{}\"", text),
+            ValueKind::Word(word) => write!(f, "{}", word),
+            ValueKind::Tuple(values) => list(f, values),
+            ValueKind::Call(name, values) => {
+                write!(f, "{}", name)?;
+                list(f, values)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Item {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} {{", self.kind)?;
+        for field in &self.fields {
+            writeln!(f, "    {} = {}", field.name, field.value)?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+fn settings(settings: &RenderSettings) -> Item {
+    let item = Item::new("settings")
+        .field("width", settings.width)
+        .field("height", settings.height)
+        .field("samples", settings.samples_per_pixel)
+        .field("max_depth", settings.max_depth);
+    match settings.seed {
+        Some(seed) => item.field("seed", seed as f64),
+        None => item,
+    }
+}
+
+fn camera(camera: &Camera) -> Item {
+    Item::new("camera")
+        .field("look_from", camera.look_from())
+        .field("look_at", camera.look_at())
+        .field("vup", camera.vup())
+        .field("vfov", camera.vfov())
+        .field("aperture", camera.aperture())
+        .field("focus_dist", camera.focus_dist())
+        .field("shutter", [camera.shutter().0, camera.shutter().1])
+}
+
+fn background(background: &Background) -> Item {
+    match background {
+        Background::Solid(color) => Item::new("background").field("color", *color),
+        Background::Gradient(bottom, top) => Item::new("background")
+            .field("bottom", *bottom)
+            .field("top", *top),
+    }
+}
+
+// Bodies that a scene file can describe.
+pub trait Export {
+    // Appends the scene file items that recreate this body.
+    fn to_items(&self, items: &mut Vec<Item>) -> Result<(), Error>;
+}
+
+type Exporter = fn(&dyn Any) -> Option<&dyn Export>;
+
+fn exporter<T: Export + 'static>(body: &dyn Any) -> Option<&dyn Export> {
+    body.downcast_ref::<T>().map(|body| body as &dyn Export)
+}
+
+// Appends the items that recreate `body`, when it is one of the bodies that
+// can be exported.
+pub fn export(body: &dyn Body, items: &mut Vec<Item>) -> Result<(), Error> {
+    let exporters: [Exporter; 18] = [
+        exporter::<BVH>,
+        exporter::<Capsule>,
+        exporter::<Cone>,
+        exporter::<Csg>,
+        exporter::<Cube>,
+        exporter::<Cylinder>,
+        exporter::<Disk>,
+        exporter::<DistanceField>,
+        exporter::<Heightfield>,
+        exporter::<Medium>,
+        exporter::<Model>,
+        exporter::<Quad>,
+        exporter::<Rect>,
+        exporter::<Sphere>,
+        exporter::<Torus>,
+        exporter::<Transformed>,
+        exporter::<Triangle>,
+        exporter::<TriangleMesh>,
+    ];
+    let any: &dyn Any = body;
+    match exporters.iter().find_map(|exporter| exporter(any)) {
+        Some(body) => body.to_items(items),
+        None => Err(Error::without_position(&format!(
+            "{:?} can not be exported",
+            body
+        ))),
+    }
+}
+
+// Writes `scene` as a scene file that renders with `settings`. The selected
+// camera comes first, so that it is also the one used when loading the file.
+pub fn write(scene: &Scene, render_settings: &RenderSettings) -> Result<String, Error> {
+    let mut items = vec![settings(render_settings), camera(scene.camera())];
+    for other in scene.cameras() {
+        if !std::ptr::eq(other, scene.camera()) {
+            items.push(camera(other));
+        }
+    }
+    items.push(background(scene.background()));
+    export(scene.world(), &mut items)?;
+
+    let items: Vec<String> = items.iter().map(|item| item.to_string()).collect();
+    Ok(items.join("\n"))
+}
@@ -1,9 +1,31 @@
-use crate::bodies::{Body, BodyProps, Cube, Sphere, Texture, BVH};
+use crate::bodies::{Body, BodyProps, Cube, Sphere, Texture, Transformed, BVH};
+use crate::matrix::Matrix4;
 use crate::point::Point;
-use crate::raytracer::{Camera, Tracer};
+use crate::random;
+use crate::raytracer::Camera;
+use crate::scene::{RenderSettings, Scene};
 use rand::Rng;
-#[allow(dead_code)]
-pub fn three_balls() -> Tracer {
+use std::sync::Arc;
+
+pub type SceneBuilder = fn() -> Scene;
+
+pub const SCENES: &[(&str, SceneBuilder)] = &[
+    ("three_balls", three_balls),
+    ("square", square),
+    ("two_spheres", two_spheres),
+    ("book_cover", book_cover),
+    ("phone_wallpaper", phone_wallpaper),
+    ("spiral", spiral),
+];
+
+pub fn by_name(name: &str) -> Option<Scene> {
+    SCENES
+        .iter()
+        .find(|(scene_name, _)| *scene_name == name)
+        .map(|(_, build)| build())
+}
+
+pub fn three_balls() -> Scene {
     let floor = Sphere::new(
         Point::new(0., -100.5, -1.),
         100.0,
@@ -40,33 +62,36 @@ pub fn three_balls() -> Tracer {
         Box::new(ball_2),
         Box::new(ball_3),
     ]);
-    let mut tracer = Tracer::new(400, (400. / aspect_ratio) as usize, camera, 100, 50);
-    tracer.render(&world);
-    tracer
+    Scene::new(
+        world,
+        camera,
+        RenderSettings::new(400, (400. / aspect_ratio) as usize, 100, 50),
+    )
 }
-#[allow(dead_code)]
-pub fn square() -> Tracer {
-    let mut body_list: Vec<Box<dyn Body>> = vec![];
-    body_list.push(Box::new(Sphere::new(
-        Point::new(0., -100.5, -1.),
-        100.0,
-        BodyProps::matte(Texture::new_color(0.2, 0.2, 0.2)),
-    )));
-    body_list.push(Box::new(Cube::new(
-        Point::new(-1.5, 0., 0.),
-        Point::new(-0.5, 1., 1.),
-        BodyProps::matte(Texture::new_color(0.8, 0.2, 0.2)),
-    )));
-    body_list.push(Box::new(Cube::new(
-        Point::new(0., 0., 0.),
-        Point::new(1., 1., 1.),
-        BodyProps::metal(Texture::new_color(0.7, 0.7, 0.7), 0.0),
-    )));
-    body_list.push(Box::new(Cube::new(
-        Point::new(1.5, 0., 0.),
-        Point::new(2.5, 1., 1.),
-        BodyProps::matte(Texture::new_color(0.5, 0.7, 0.2)),
-    )));
+pub fn square() -> Scene {
+    let body_list: Vec<Box<dyn Body>> = vec![
+        Box::new(Sphere::new(
+            Point::new(0., -100.5, -1.),
+            100.0,
+            BodyProps::matte(Texture::new_color(0.2, 0.2, 0.2)),
+        )),
+        Box::new(Cube::new(
+            Point::new(-1.5, 0., 0.),
+            Point::new(-0.5, 1., 1.),
+            BodyProps::matte(Texture::new_color(0.8, 0.2, 0.2)),
+        )),
+        Box::new(Cube::new(
+            Point::new(0., 0., 0.),
+            Point::new(1., 1., 1.),
+            BodyProps::metal(Texture::new_color(0.7, 0.7, 0.7), 0.0),
+        )),
+        Box::new(Cube::new(
+            Point::new(1.5, 0., 0.),
+            Point::new(2.5, 1., 1.),
+            BodyProps::matte(Texture::new_color(0.5, 0.7, 0.2)),
+        )),
+    ];
+
     let aspect_ratio = 16. / 9.;
     let look_from = Point::new(4., 3., 5.);
     let look_at = Point::new(0., 0., 0.);
@@ -81,24 +106,26 @@ pub fn square() -> Tracer {
     );
 
     let world = BVH::new(body_list);
-    let mut tracer = Tracer::new(400, (400. / aspect_ratio) as usize, camera, 100, 50);
-    tracer.render(&world);
-    tracer
+    Scene::new(
+        world,
+        camera,
+        RenderSettings::new(400, (400. / aspect_ratio) as usize, 100, 50),
+    )
 }
 
-#[allow(dead_code)]
-pub fn two_spheres() -> Tracer {
-    let mut body_list: Vec<Box<dyn Body>> = vec![];
-    body_list.push(Box::new(Sphere::new(
-        Point::new(0., -1000., 0.),
-        1000.,
-        BodyProps::matte(Texture::Noise(Point::new(0.2, 0.3, 0.1))),
-    )));
-    body_list.push(Box::new(Sphere::new(
-        Point::new(0., 2., 0.),
-        2.,
-        BodyProps::matte(Texture::Noise(Point::new(0.2, 0.1, 0.3))),
-    )));
+pub fn two_spheres() -> Scene {
+    let body_list: Vec<Box<dyn Body>> = vec![
+        Box::new(Sphere::new(
+            Point::new(0., -1000., 0.),
+            1000.,
+            BodyProps::matte(Texture::Noise(Point::new(0.2, 0.3, 0.1))),
+        )),
+        Box::new(Sphere::new(
+            Point::new(0., 2., 0.),
+            2.,
+            BodyProps::matte(Texture::Noise(Point::new(0.2, 0.1, 0.3))),
+        )),
+    ];
 
     let aspect_ratio = 16. / 9.;
     let look_from = Point::new(13., 2., 3.);
@@ -114,12 +141,13 @@ pub fn two_spheres() -> Tracer {
     );
 
     let world = BVH::new(body_list);
-    let mut tracer = Tracer::new(400, (400. / aspect_ratio) as usize, camera, 100, 50);
-    tracer.render(&world);
-    tracer
+    Scene::new(
+        world,
+        camera,
+        RenderSettings::new(400, (400. / aspect_ratio) as usize, 100, 50),
+    )
 }
-#[allow(dead_code)]
-pub fn book_cover() -> Tracer {
+pub fn book_cover() -> Scene {
     let mut body_list: Vec<Box<dyn Body>> = vec![];
     body_list.push(Box::new(Sphere::new(
         Point::new(0., -1000., -1.),
@@ -127,7 +155,7 @@ pub fn book_cover() -> Tracer {
         BodyProps::matte(Texture::new_color(0.5, 0.5, 0.5)),
     )));
 
-    let mut rng = rand::thread_rng();
+    let mut rng = random::rng();
 
     for a in -11..11 {
         for b in -11..11 {
@@ -190,84 +218,81 @@ pub fn book_cover() -> Tracer {
     );
 
     let world = BVH::new(body_list);
-    let mut tracer = Tracer::new(image_width, image_height, camera, 500, 50);
-    tracer.render(&world);
-    tracer
+    Scene::new(
+        world,
+        camera,
+        RenderSettings::new(image_width, image_height, 500, 50),
+    )
 }
 
-#[allow(dead_code)]
-pub fn phone_wallpaper() -> Tracer {
-    let mut body_list: Vec<Box<dyn Body>> = vec![];
-
-    body_list.push(Box::new(Sphere::new(
-        Point::new(0., -1000., 0.),
-        1000.0,
-        BodyProps::metal(Texture::new_color(0.7, 0.6, 0.5), 0.0),
-    )));
-
-    body_list.push(Box::new(Sphere::new(
-        Point::new(0., 1.8, 0.),
-        1.8,
-        BodyProps::metal(Texture::new_color(0.1, 0.2, 0.5), 0.3),
-    )));
-    body_list.push(Box::new(Sphere::new(
-        Point::new(1.5, 0.8, 5.),
-        0.8,
-        BodyProps::matte(Texture::new_color(0.7, 0.2, 0.5)),
-    )));
-    body_list.push(Box::new(Sphere::new(
-        Point::new(-2., 1.5, 4.),
-        1.5,
-        BodyProps::metal(Texture::new_color(0.8, 0.6, 0.2), 0.),
-    )));
-    body_list.push(Box::new(Sphere::new(
-        Point::new(-2., 0.4, 5.8),
-        0.4,
-        BodyProps::glass(1.5),
-    )));
-
-    body_list.push(Box::new(Cube::new_as_sphere(
-        Point::new(-2.3, 0.15, 6.3),
-        0.15,
-        BodyProps::matte(Texture::new_color(0.0, 0.1, 0.1)),
-    )));
-    body_list.push(Box::new(Sphere::new(
-        Point::new(2., 0.3, 5.9),
-        0.3,
-        BodyProps::metal(Texture::new_color(0.8, 0.8, 0.8), 0.),
-    )));
-    body_list.push(Box::new(Sphere::new(
-        Point::new(1., 1.5, 3.),
-        1.5,
-        BodyProps::glass(1.5),
-    )));
-    body_list.push(Box::new(Cube::new_as_sphere(
-        Point::new(0.6, 0.4, 6.),
-        0.4,
-        BodyProps::matte(Texture::new_color(0.3, 0.6, 0.4)),
-    )));
-
-    body_list.push(Box::new(Sphere::new(
-        Point::new(-1.1, 0.5, -12.1),
-        0.6,
-        BodyProps::matte(Texture::new_color(0.9, 0.2, 0.2)),
-    )));
-    body_list.push(Box::new(Sphere::new(
-        Point::new(-1.6, 0.4, -11.),
-        0.5,
-        BodyProps::metal(Texture::new_color(0.5, 0.8, 0.5), 0.2),
-    )));
-    body_list.push(Box::new(Cube::new_as_sphere(
-        Point::new(-0.8, 0.2, -11.0),
-        0.3,
-        BodyProps::matte(Texture::new_color(0.3, 0.2, 0.7)),
-    )));
-
-    body_list.push(Box::new(Sphere::new(
-        Point::new(-2., 0.3, 5.8),
-        0.4,
-        BodyProps::glass(1.5),
-    )));
+pub fn phone_wallpaper() -> Scene {
+    let body_list: Vec<Box<dyn Body>> = vec![
+        Box::new(Sphere::new(
+            Point::new(0., -1000., 0.),
+            1000.0,
+            BodyProps::metal(Texture::new_color(0.7, 0.6, 0.5), 0.0),
+        )),
+        Box::new(Sphere::new(
+            Point::new(0., 1.8, 0.),
+            1.8,
+            BodyProps::metal(Texture::new_color(0.1, 0.2, 0.5), 0.3),
+        )),
+        Box::new(Sphere::new(
+            Point::new(1.5, 0.8, 5.),
+            0.8,
+            BodyProps::matte(Texture::new_color(0.7, 0.2, 0.5)),
+        )),
+        Box::new(Sphere::new(
+            Point::new(-2., 1.5, 4.),
+            1.5,
+            BodyProps::metal(Texture::new_color(0.8, 0.6, 0.2), 0.),
+        )),
+        Box::new(Sphere::new(
+            Point::new(-2., 0.4, 5.8),
+            0.4,
+            BodyProps::glass(1.5),
+        )),
+        Box::new(Cube::new_as_sphere(
+            Point::new(-2.3, 0.15, 6.3),
+            0.15,
+            BodyProps::matte(Texture::new_color(0.0, 0.1, 0.1)),
+        )),
+        Box::new(Sphere::new(
+            Point::new(2., 0.3, 5.9),
+            0.3,
+            BodyProps::metal(Texture::new_color(0.8, 0.8, 0.8), 0.),
+        )),
+        Box::new(Sphere::new(
+            Point::new(1., 1.5, 3.),
+            1.5,
+            BodyProps::glass(1.5),
+        )),
+        Box::new(Cube::new_as_sphere(
+            Point::new(0.6, 0.4, 6.),
+            0.4,
+            BodyProps::matte(Texture::new_color(0.3, 0.6, 0.4)),
+        )),
+        Box::new(Sphere::new(
+            Point::new(-1.1, 0.5, -12.1),
+            0.6,
+            BodyProps::matte(Texture::new_color(0.9, 0.2, 0.2)),
+        )),
+        Box::new(Sphere::new(
+            Point::new(-1.6, 0.4, -11.),
+            0.5,
+            BodyProps::metal(Texture::new_color(0.5, 0.8, 0.5), 0.2),
+        )),
+        Box::new(Cube::new_as_sphere(
+            Point::new(-0.8, 0.2, -11.0),
+            0.3,
+            BodyProps::matte(Texture::new_color(0.3, 0.2, 0.7)),
+        )),
+        Box::new(Sphere::new(
+            Point::new(-2., 0.3, 5.8),
+            0.4,
+            BodyProps::glass(1.5),
+        )),
+    ];
 
     let image_width: usize = 720;
     let image_height: usize = 1480;
@@ -285,7 +310,59 @@ pub fn phone_wallpaper() -> Tracer {
     );
 
     let world = BVH::new(body_list);
-    let mut tracer = Tracer::new(image_width, image_height, camera, 500, 50);
-    tracer.render(&world);
-    tracer
+    Scene::new(
+        world,
+        camera,
+        RenderSettings::new(image_width, image_height, 500, 50),
+    )
+}
+
+pub fn spiral() -> Scene {
+    let mut body_list: Vec<Box<dyn Body>> = vec![Box::new(Sphere::new(
+        Point::new(0., -1000., 0.),
+        1000.0,
+        BodyProps::matte(Texture::new_color(0.5, 0.5, 0.5)),
+    ))];
+    // One small cube, placed a few thousand times.
+    let brick: Arc<dyn Body> = Arc::new(Cube::new(
+        Point::new(-0.5, -0.5, -0.5),
+        Point::new(0.5, 0.5, 0.5),
+        BodyProps::metal(Texture::new_color(0.8, 0.6, 0.2), 0.1),
+    ));
+    for i in 0..3000 {
+        let turn = i as f64 * 7.;
+        let to_world = Matrix4::rotation(Point::new(0., 1., 0.), turn)
+            * Matrix4::translation(Point::new(
+                1.5 + i as f64 * 0.001,
+                0.1 + i as f64 * 0.0012,
+                0.,
+            ))
+            * Matrix4::rotation(Point::new(1., 1., 0.), turn * 3.)
+            * Matrix4::scaling(Point::new(0.15, 0.1, 0.05));
+        body_list.push(Box::new(Transformed::new(brick.clone(), to_world)));
+    }
+    body_list.push(Box::new(Sphere::new(
+        Point::new(0., 1.2, 0.),
+        1.,
+        BodyProps::glass(1.5),
+    )));
+
+    let aspect_ratio = 16. / 9.;
+    let look_from = Point::new(0., 6., 10.);
+    let look_at = Point::new(0., 1.8, 0.);
+    let camera = Camera::new(
+        look_from,
+        look_at,
+        Point::new(0., 1., 0.),
+        35.,
+        aspect_ratio,
+        0.,
+        (look_from - look_at).length(),
+    );
+
+    Scene::new(
+        BVH::new(body_list),
+        camera,
+        RenderSettings::new(400, (400. / aspect_ratio) as usize, 100, 50),
+    )
 }
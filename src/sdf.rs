@@ -0,0 +1,20 @@
+pub mod fractals;
+pub mod operators;
+pub mod primitives;
+
+use crate::point::Point;
+use crate::scenefile::Value;
+use std::fmt::Debug;
+
+pub use fractals::{Mandelbulb, MengerSponge};
+pub use operators::{Repeat, Round, SmoothUnion, Translate, Twist, Union};
+pub use primitives::{Cuboid, Cylinder, Sphere, Torus};
+
+// A shape given by the distance to its surface, which is negative inside.
+// The distance may be underestimated, but never overestimated, so that a
+// ray can safely step that far.
+pub trait Sdf: Sync + Send + Debug {
+    fn distance(&self, p: Point) -> f64;
+    // The scene file value that recreates this shape.
+    fn to_value(&self) -> Value;
+}
@@ -0,0 +1,96 @@
+use crate::point::Point;
+use crate::scenefile::Value;
+use crate::sdf::{Cuboid, Sdf};
+
+// The Mandelbulb of `power`, which fits in a sphere of radius 1.2 for the
+// usual power of 8. Its distance is estimated from how fast points escape.
+#[derive(Debug)]
+pub struct Mandelbulb {
+    pub power: f64,
+    pub iterations: usize,
+}
+
+impl Sdf for Mandelbulb {
+    fn distance(&self, p: Point) -> f64 {
+        let mut z = p;
+        let mut slope = 1.;
+        let mut r = z.length();
+        for _ in 0..self.iterations {
+            if r > 2. {
+                break;
+            }
+            let theta = (z.y / r).acos() * self.power;
+            let phi = z.z.atan2(z.x) * self.power;
+            slope = r.powf(self.power - 1.) * self.power * slope + 1.;
+            z = r.powf(self.power)
+                * Point::new(
+                    theta.sin() * phi.cos(),
+                    theta.cos(),
+                    theta.sin() * phi.sin(),
+                )
+                + p;
+            r = z.length();
+        }
+        0.5 * r.ln() * r / slope
+    }
+    fn to_value(&self) -> Value {
+        Value::called(
+            "mandelbulb",
+            vec![self.power.into(), self.iterations.into()],
+        )
+    }
+}
+
+// A cube from -1 to 1 with crosses cut out of it, again and again.
+#[derive(Debug)]
+pub struct MengerSponge {
+    pub iterations: usize,
+}
+
+impl Sdf for MengerSponge {
+    fn distance(&self, p: Point) -> f64 {
+        let cube = Cuboid {
+            half_size: Point::new(1., 1., 1.),
+        };
+        let mut distance = cube.distance(p);
+        let mut scale = 1.;
+        for _ in 0..self.iterations {
+            // Where in the cell of the next smaller holes the point is.
+            let cell = |x: f64| (1. - 3. * ((x * scale).rem_euclid(2.) - 1.).abs()).abs();
+            let (x, y, z) = (cell(p.x), cell(p.y), cell(p.z));
+            scale *= 3.;
+            let cross = x.max(y).min(y.max(z)).min(z.max(x)) - 1.;
+            distance = distance.max(cross / scale);
+        }
+        distance
+    }
+    fn to_value(&self) -> Value {
+        Value::called("menger", vec![self.iterations.into()])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Mandelbulb, MengerSponge};
+    use crate::point::Point;
+    use crate::sdf::Sdf;
+
+    #[test]
+    fn test_fractals() {
+        let sponge = MengerSponge { iterations: 3 };
+        // The middle and the middle of each face are cut out, the corners
+        // are not.
+        assert!(sponge.distance(Point::new(0., 0., 0.)) > 0.);
+        assert!(sponge.distance(Point::new(0., 0., 0.99)) > 0.);
+        assert!(sponge.distance(Point::new(0.99, 0.99, 0.99)) < 0.);
+        assert!((sponge.distance(Point::new(0., 0., 3.)) - 2.).abs() < 1e-12);
+
+        let bulb = Mandelbulb {
+            power: 8.,
+            iterations: 12,
+        };
+        assert!(bulb.distance(Point::new(0., 0.1, 0.2)) < 1e-3);
+        let far = bulb.distance(Point::new(0., 3., 0.));
+        assert!(far > 0.5 && far < 2.);
+    }
+}
@@ -0,0 +1,163 @@
+use crate::point::Point;
+use crate::scenefile::Value;
+use crate::sdf::Sdf;
+
+#[derive(Debug)]
+pub struct Translate {
+    pub offset: Point,
+    pub shape: Box<dyn Sdf>,
+}
+
+impl Sdf for Translate {
+    fn distance(&self, p: Point) -> f64 {
+        self.shape.distance(p - self.offset)
+    }
+    fn to_value(&self) -> Value {
+        Value::called("translate", vec![self.offset.into(), self.shape.to_value()])
+    }
+}
+
+#[derive(Debug)]
+pub struct Union {
+    pub a: Box<dyn Sdf>,
+    pub b: Box<dyn Sdf>,
+}
+
+impl Sdf for Union {
+    fn distance(&self, p: Point) -> f64 {
+        self.a.distance(p).min(self.b.distance(p))
+    }
+    fn to_value(&self) -> Value {
+        Value::called("union", vec![self.a.to_value(), self.b.to_value()])
+    }
+}
+
+// A union that blends the shapes together where they are closer than
+// `radius` to each other.
+#[derive(Debug)]
+pub struct SmoothUnion {
+    pub radius: f64,
+    pub a: Box<dyn Sdf>,
+    pub b: Box<dyn Sdf>,
+}
+
+impl Sdf for SmoothUnion {
+    fn distance(&self, p: Point) -> f64 {
+        let (a, b, k) = (self.a.distance(p), self.b.distance(p), self.radius);
+        let h = (0.5 + 0.5 * (b - a) / k).clamp(0., 1.);
+        b + (a - b) * h - k * h * (1. - h)
+    }
+    fn to_value(&self) -> Value {
+        Value::called(
+            "smooth_union",
+            vec![self.radius.into(), self.a.to_value(), self.b.to_value()],
+        )
+    }
+}
+
+// Copies of the shape every `period` along each axis, or only one along
+// axes where the period is zero. The shape has to fit in a single cell.
+#[derive(Debug)]
+pub struct Repeat {
+    pub period: Point,
+    pub shape: Box<dyn Sdf>,
+}
+
+impl Sdf for Repeat {
+    fn distance(&self, p: Point) -> f64 {
+        let cell = |x: f64, period: f64| {
+            if period > 0. {
+                x - period * (x / period).round()
+            } else {
+                x
+            }
+        };
+        let q = Point::new(
+            cell(p.x, self.period.x),
+            cell(p.y, self.period.y),
+            cell(p.z, self.period.z),
+        );
+        self.shape.distance(q)
+    }
+    fn to_value(&self) -> Value {
+        Value::called("repeat", vec![self.period.into(), self.shape.to_value()])
+    }
+}
+
+// Turns the shape around the y axis by `degrees` for every unit up. The
+// shape is stretched by the twist, which the distance makes up for within
+// `reach` of the axis.
+#[derive(Debug)]
+pub struct Twist {
+    pub degrees: f64,
+    pub reach: f64,
+    pub shape: Box<dyn Sdf>,
+}
+
+impl Sdf for Twist {
+    fn distance(&self, p: Point) -> f64 {
+        let rate = self.degrees.to_radians();
+        let (sin, cos) = (-rate * p.y).sin_cos();
+        let q = Point::new(cos * p.x - sin * p.z, p.y, sin * p.x + cos * p.z);
+        self.shape.distance(q) / (1. + (rate * self.reach).powi(2)).sqrt()
+    }
+    fn to_value(&self) -> Value {
+        Value::called(
+            "twist",
+            vec![
+                self.degrees.into(),
+                self.reach.into(),
+                self.shape.to_value(),
+            ],
+        )
+    }
+}
+
+// Grows the shape by `radius`, which rounds off its edges.
+#[derive(Debug)]
+pub struct Round {
+    pub radius: f64,
+    pub shape: Box<dyn Sdf>,
+}
+
+impl Sdf for Round {
+    fn distance(&self, p: Point) -> f64 {
+        self.shape.distance(p) - self.radius
+    }
+    fn to_value(&self) -> Value {
+        Value::called("round", vec![self.radius.into(), self.shape.to_value()])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Repeat, SmoothUnion, Twist};
+    use crate::point::Point;
+    use crate::sdf::{Sdf, Sphere};
+
+    #[test]
+    fn test_operators() {
+        let blend = SmoothUnion {
+            radius: 0.5,
+            a: Box::new(Sphere { radius: 1. }),
+            b: Box::new(Sphere { radius: 1. }),
+        };
+        // Where both are equally far the blend bulges out by a quarter of
+        // its radius.
+        assert_eq!(blend.distance(Point::new(3., 0., 0.)), 2. - 0.125);
+
+        let grid = Repeat {
+            period: Point::new(4., 0., 4.),
+            shape: Box::new(Sphere { radius: 1. }),
+        };
+        assert_eq!(grid.distance(Point::new(8., 0., -4.)), -1.);
+        assert_eq!(grid.distance(Point::new(9., 2., 0.)), 5f64.sqrt() - 1.);
+
+        let twist = Twist {
+            degrees: 90.,
+            reach: 0.,
+            shape: Box::new(Sphere { radius: 1. }),
+        };
+        assert!((twist.distance(Point::new(1., 1., 1.)) - (3f64.sqrt() - 1.)).abs() < 1e-12);
+    }
+}
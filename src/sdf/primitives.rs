@@ -0,0 +1,105 @@
+use crate::point::Point;
+use crate::scenefile::Value;
+use crate::sdf::Sdf;
+
+// The shapes are centred on the origin, and round ones go around the y axis.
+
+#[derive(Debug)]
+pub struct Sphere {
+    pub radius: f64,
+}
+
+impl Sdf for Sphere {
+    fn distance(&self, p: Point) -> f64 {
+        p.length() - self.radius
+    }
+    fn to_value(&self) -> Value {
+        Value::called("sphere", vec![self.radius.into()])
+    }
+}
+
+// A box reaching `half_size` out from the origin along each axis.
+#[derive(Debug)]
+pub struct Cuboid {
+    pub half_size: Point,
+}
+
+impl Sdf for Cuboid {
+    fn distance(&self, p: Point) -> f64 {
+        let q = Point::new(p.x.abs(), p.y.abs(), p.z.abs()) - self.half_size;
+        let outside = Point::new(q.x.max(0.), q.y.max(0.), q.z.max(0.)).length();
+        outside + q.x.max(q.y).max(q.z).min(0.)
+    }
+    fn to_value(&self) -> Value {
+        Value::called("box", vec![self.half_size.into()])
+    }
+}
+
+#[derive(Debug)]
+pub struct Torus {
+    pub major_radius: f64,
+    pub minor_radius: f64,
+}
+
+impl Sdf for Torus {
+    fn distance(&self, p: Point) -> f64 {
+        let around = (p.x * p.x + p.z * p.z).sqrt() - self.major_radius;
+        (around * around + p.y * p.y).sqrt() - self.minor_radius
+    }
+    fn to_value(&self) -> Value {
+        Value::called(
+            "torus",
+            vec![self.major_radius.into(), self.minor_radius.into()],
+        )
+    }
+}
+
+// A closed cylinder from -`half_height` to `half_height` along y.
+#[derive(Debug)]
+pub struct Cylinder {
+    pub radius: f64,
+    pub half_height: f64,
+}
+
+impl Sdf for Cylinder {
+    fn distance(&self, p: Point) -> f64 {
+        let side = (p.x * p.x + p.z * p.z).sqrt() - self.radius;
+        let end = p.y.abs() - self.half_height;
+        let outside = (side.max(0.).powi(2) + end.max(0.).powi(2)).sqrt();
+        outside + side.max(end).min(0.)
+    }
+    fn to_value(&self) -> Value {
+        Value::called(
+            "cylinder",
+            vec![self.radius.into(), self.half_height.into()],
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Cuboid, Cylinder, Torus};
+    use crate::point::Point;
+    use crate::sdf::Sdf;
+
+    #[test]
+    fn test_distances() {
+        let cuboid = Cuboid {
+            half_size: Point::new(1., 2., 3.),
+        };
+        assert_eq!(cuboid.distance(Point::new(0., 0., 0.)), -1.);
+        assert_eq!(cuboid.distance(Point::new(4., 6., 0.)), 5.);
+        let torus = Torus {
+            major_radius: 2.,
+            minor_radius: 0.5,
+        };
+        assert_eq!(torus.distance(Point::new(0., 0., 2.)), -0.5);
+        assert_eq!(torus.distance(Point::new(0., 1., 0.)), 5f64.sqrt() - 0.5);
+        let cylinder = Cylinder {
+            radius: 1.,
+            half_height: 1.,
+        };
+        assert_eq!(cylinder.distance(Point::new(0., 3., 0.)), 2.);
+        assert_eq!(cylinder.distance(Point::new(4., 5., 0.)), 5.);
+    }
+}
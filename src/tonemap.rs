@@ -0,0 +1,133 @@
+use crate::point::Point;
+use crate::raytracer::Pixel;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Operator {
+    Clamp,
+    Reinhard,
+    ReinhardExtended(f64),
+    AcesFilmic,
+    Hable,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Transfer {
+    Srgb,
+    Gamma(f64),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ToneMapper {
+    operator: Operator,
+    exposure: f64,
+    transfer: Transfer,
+}
+
+fn hable_curve(x: f64) -> f64 {
+    let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+    ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f
+}
+
+impl Operator {
+    pub fn apply(&self, x: f64) -> f64 {
+        let x = x.max(0.);
+        match self {
+            Operator::Clamp => x.min(1.),
+            Operator::Reinhard => x / (1. + x),
+            Operator::ReinhardExtended(white) => x * (1. + x / (white * white)) / (1. + x),
+            // Narkowicz's fit of the ACES reference rendering transform
+            Operator::AcesFilmic => (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14),
+            // John Hable's Uncharted 2 curve with its usual exposure bias and
+            // linear white point
+            Operator::Hable => hable_curve(2. * x) / hable_curve(11.2),
+        }
+        .clamp(0., 1.)
+    }
+}
+
+impl Transfer {
+    pub fn encode(&self, x: f64) -> f64 {
+        match self {
+            Transfer::Srgb => {
+                if x <= 0.0031308 {
+                    12.92 * x
+                } else {
+                    1.055 * x.powf(1. / 2.4) - 0.055
+                }
+            }
+            Transfer::Gamma(gamma) => x.powf(1. / gamma),
+        }
+    }
+    // The inverse of `encode`, for colours that are stored encoded.
+    pub fn decode(&self, x: f64) -> f64 {
+        match self {
+            Transfer::Srgb => {
+                if x <= 0.04045 {
+                    x / 12.92
+                } else {
+                    ((x + 0.055) / 1.055).powf(2.4)
+                }
+            }
+            Transfer::Gamma(gamma) => x.powf(*gamma),
+        }
+    }
+}
+
+impl ToneMapper {
+    pub fn new(operator: Operator, exposure: f64, transfer: Transfer) -> Self {
+        ToneMapper {
+            operator,
+            exposure,
+            transfer,
+        }
+    }
+    pub fn default() -> Self {
+        ToneMapper {
+            operator: Operator::Clamp,
+            exposure: 0.,
+            transfer: Transfer::Gamma(2.),
+        }
+    }
+
+    fn map_channel(&self, value: f64) -> f64 {
+        let exposed = value * 2f64.powf(self.exposure);
+        self.transfer.encode(self.operator.apply(exposed))
+    }
+    pub fn map(&self, radiance: Point) -> Pixel {
+        Pixel::from_point(Point::new(
+            self.map_channel(radiance.x),
+            self.map_channel(radiance.y),
+            self.map_channel(radiance.z),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Operator, ToneMapper, Transfer};
+    use crate::point::Point;
+
+    #[test]
+    fn test_default_is_clamped_square_root() {
+        let pixel = ToneMapper::default().map(Point::new(0.25, 4., -1.));
+        assert_eq!(pixel.to_rgb(), [128, 255, 0]);
+    }
+    #[test]
+    fn test_operators_are_monotonic() {
+        for operator in [
+            Operator::Reinhard,
+            Operator::ReinhardExtended(4.),
+            Operator::AcesFilmic,
+            Operator::Hable,
+        ] {
+            let mut previous = 0.;
+            for i in 1..100 {
+                let value = operator.apply(i as f64 * 0.1);
+                assert!(value >= previous, "{:?}", operator);
+                previous = value;
+            }
+        }
+        assert!((Operator::ReinhardExtended(4.).apply(4.) - 1.).abs() < 1e-12);
+        assert!((Transfer::Srgb.encode(1.) - 1.).abs() < 1e-12);
+    }
+}